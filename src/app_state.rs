@@ -0,0 +1,162 @@
+use embassy_futures::select::{select3, select4, Either3, Either4};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::watch::{Receiver, Watch};
+
+use crate::battery::monitor::{BatteryRx, BATTERY_PERCENT};
+use crate::ble::state::{BleStateRx, BLE_STATE};
+use crate::dock::{DockedRx, DOCKED};
+use crate::gnss::positioning::GnssPositioning;
+use crate::gnss::watch::{GnssStateRx, GNSS_WATCH};
+use crate::lora::driver::{TxConfirmedRx, TX_CONFIRMED};
+use crate::power::{PowerSavingRx, POWER_SAVING};
+
+/// Tasks that render from the combined state: the display
+const APP_STATE_RECEIVERS: usize = 1;
+
+/// Everything the device shows about itself, merged from the individual watches by `aggregate`
+///
+/// Tasks that only care about one source keep using its own watch; this is for the ones that
+/// render all of it and would otherwise wait on each source separately.
+pub static APP_STATE: Watch<CriticalSectionRawMutex, AppState, APP_STATE_RECEIVERS> = Watch::new();
+
+pub type AppStateRx = Receiver<'static, CriticalSectionRawMutex, AppState, APP_STATE_RECEIVERS>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppState {
+    pub is_ble_connected: bool,
+
+    /// Whether the BLE radio came up at all
+    pub is_ble_available: bool,
+
+    /// Latest valid fix, `None` until there is one
+    pub positioning: Option<GnssPositioning>,
+
+    pub is_docked: bool,
+
+    /// Position reports transmitted since boot, see `TX_CONFIRMED`
+    pub reports_confirmed: u32,
+
+    /// Charge left in the battery, `None` until the first measurement and always without the
+    /// `battery-sense` feature
+    pub battery_percent: Option<u8>,
+
+    /// Sleeping between fixes, see `power::PowerMode::SleepBetweenFixes`
+    pub is_power_saving: bool,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self {
+            is_ble_connected: false,
+            is_ble_available: true,
+            positioning: None,
+            is_docked: false,
+            reports_confirmed: 0,
+            battery_percent: None,
+            is_power_saving: false,
+        }
+    }
+}
+
+/// Publish a new `APP_STATE` whenever one of the sources it's merged from changes
+///
+/// Takes one receiver from each of `BLE_STATE`, `GNSS_WATCH`, `DOCKED`, `TX_CONFIRMED`,
+/// `BATTERY_PERCENT` and `POWER_SAVING`, and only publishes when the merged state actually
+/// differs, so a source repeating itself doesn't wake the display.
+#[embassy_executor::task]
+pub async fn aggregate() {
+    match (
+        BLE_STATE.receiver(),
+        GNSS_WATCH.receiver(),
+        DOCKED.receiver(),
+        TX_CONFIRMED.receiver(),
+        BATTERY_PERCENT.receiver(),
+        POWER_SAVING.receiver(),
+    ) {
+        (
+            Some(ble_rx),
+            Some(gnss_rx),
+            Some(docked_rx),
+            Some(tx_confirmed_rx),
+            Some(battery_rx),
+            Some(power_saving_rx),
+        ) => {
+            merge(
+                ble_rx,
+                gnss_rx,
+                docked_rx,
+                tx_confirmed_rx,
+                battery_rx,
+                power_saving_rx,
+            )
+            .await
+        }
+        _ => defmt::error!(
+            "Failed to get BLE, GPS, dock, TX confirmation, battery or power saving receiver"
+        ),
+    }
+}
+
+async fn merge(
+    mut ble_rx: BleStateRx,
+    mut gnss_rx: GnssStateRx,
+    mut docked_rx: DockedRx,
+    mut tx_confirmed_rx: TxConfirmedRx,
+    mut battery_rx: BatteryRx,
+    mut power_saving_rx: PowerSavingRx,
+) {
+    let sender = APP_STATE.sender();
+    let mut state = AppState::default();
+    sender.send(state.clone());
+
+    loop {
+        let mut next = state.clone();
+
+        match select4(
+            ble_rx.changed(),
+            gnss_rx.changed(),
+            docked_rx.changed(),
+            select3(
+                tx_confirmed_rx.changed(),
+                battery_rx.changed(),
+                power_saving_rx.changed(),
+            ),
+        )
+        .await
+        {
+            Either4::First(ble_state) => {
+                if ble_state.connection_status != state.is_ble_connected {
+                    defmt::info!(
+                        "BLE connection status changed: {}",
+                        ble_state.connection_status
+                    );
+                }
+                if ble_state.available != state.is_ble_available {
+                    defmt::warn!("BLE available: {}", ble_state.available);
+                }
+
+                next.is_ble_connected = ble_state.connection_status;
+                next.is_ble_available = ble_state.available;
+            }
+            Either4::Second(positioning) => {
+                if positioning != state.positioning {
+                    defmt::info!(
+                        "GPS position updated: {:?}",
+                        defmt::Debug2Format(&positioning)
+                    );
+                }
+
+                next.positioning = positioning;
+            }
+            Either4::Third(docked) => next.is_docked = docked,
+            Either4::Fourth(Either3::First(reports)) => next.reports_confirmed = reports,
+            Either4::Fourth(Either3::Second(percent)) => next.battery_percent = Some(percent),
+            Either4::Fourth(Either3::Third(power_saving)) => next.is_power_saving = power_saving,
+        }
+
+        if next != state {
+            state = next;
+            sender.send(state.clone());
+        }
+    }
+}
@@ -0,0 +1,82 @@
+// ESP32-specific modules
+#[cfg(feature = "esp32")]
+pub mod monitor;
+
+/// Charge left at a cell voltage, from full down to empty: (millivolts, percent)
+pub type DischargeCurve = &'static [(u16, u8)];
+
+/// A single LiPo cell at rest, the Heltec V3's usual battery
+pub const LIPO_CURVE: DischargeCurve = &[
+    (4200, 100),
+    (4150, 95),
+    (4110, 90),
+    (4080, 85),
+    (4020, 80),
+    (3980, 75),
+    (3950, 70),
+    (3910, 65),
+    (3870, 60),
+    (3850, 55),
+    (3840, 50),
+    (3820, 45),
+    (3800, 40),
+    (3790, 35),
+    (3770, 30),
+    (3750, 25),
+    (3730, 20),
+    (3710, 15),
+    (3690, 10),
+    (3610, 5),
+    (3270, 0),
+];
+
+/// Charge left in percent at `millivolts`, interpolated between the points of `curve`
+///
+/// Above the first point it's the first point's charge, below the last the last's.
+pub fn percent(curve: DischargeCurve, millivolts: u16) -> u8 {
+    let Some(&(full_millivolts, full)) = curve.first() else {
+        return 0;
+    };
+    if millivolts >= full_millivolts {
+        return full;
+    }
+
+    for pair in curve.windows(2) {
+        let ((upper_millivolts, upper), (lower_millivolts, lower)) = (pair[0], pair[1]);
+        if millivolts >= lower_millivolts {
+            let span = upper_millivolts.saturating_sub(lower_millivolts).max(1) as u32;
+            let above = (millivolts - lower_millivolts) as u32;
+
+            return lower + (upper.saturating_sub(lower) as u32 * above / span) as u8;
+        }
+    }
+
+    curve.last().map_or(0, |&(_, empty)| empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_points_on_the_curve() {
+        assert_eq!(percent(LIPO_CURVE, 4200), 100);
+        assert_eq!(percent(LIPO_CURVE, 3840), 50);
+        assert_eq!(percent(LIPO_CURVE, 3270), 0);
+    }
+
+    #[test]
+    fn test_interpolates_between_points() {
+        // Halfway between 3610 mV (5%) and 3690 mV (10%)
+        assert_eq!(percent(LIPO_CURVE, 3650), 7);
+        assert_eq!(percent(LIPO_CURVE, 4000), 77);
+    }
+
+    #[test]
+    fn test_clamps_outside_the_curve() {
+        // Still charging, or a board without a battery
+        assert_eq!(percent(LIPO_CURVE, 4350), 100);
+        assert_eq!(percent(LIPO_CURVE, 0), 0);
+        assert_eq!(percent(&[], 3700), 0);
+    }
+}
@@ -0,0 +1,87 @@
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::watch::{Receiver, Watch};
+use embassy_time::{Duration, Timer};
+use esp_hal::analog::adc::{Adc, AdcCalCurve, AdcConfig, Attenuation};
+use esp_hal::gpio::{GpioPin, Level, Output};
+use esp_hal::peripherals::ADC1;
+
+use super::{percent, DischargeCurve, LIPO_CURVE};
+
+/// Consumers of `BATTERY_PERCENT`: the app state aggregator and BLE
+const BATTERY_RECEIVERS: usize = 2;
+
+/// Readings averaged into each measurement, to smooth out ADC noise
+const SAMPLES: u32 = 8;
+
+/// Time for the sense divider to settle after it's switched in
+const SETTLE_TIME: Duration = Duration::from_millis(10);
+
+/// Charge left in the battery in percent, republished on every measurement
+///
+/// Nothing is published without the `battery-sense` feature, which starts `monitor`.
+pub static BATTERY_PERCENT: Watch<CriticalSectionRawMutex, u8, BATTERY_RECEIVERS> = Watch::new();
+
+pub type BatteryRx = Receiver<'static, CriticalSectionRawMutex, u8, BATTERY_RECEIVERS>;
+
+/// How the battery voltage reaches the ADC, and how to read it
+pub struct Config {
+    /// Time between measurements
+    pub interval: Duration,
+
+    /// Battery voltage over the voltage at the sense pin; 4.9 for the Heltec V3's 390k/100k
+    /// divider
+    pub divider_ratio: f32,
+
+    /// Level on the control pin that switches the divider in; low on the V3.0 and V3.1, high on
+    /// the V3.2
+    pub enable_level: Level,
+
+    pub curve: DischargeCurve,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            divider_ratio: 4.9,
+            enable_level: Level::Low,
+            curve: LIPO_CURVE,
+        }
+    }
+}
+
+/// Measure the battery on the Heltec V3's sense pin, GPIO1, every `config.interval`
+///
+/// `control` switches the divider in, and is only held at `config.enable_level` while measuring
+/// so that the divider doesn't drain the battery in between.
+#[embassy_executor::task]
+pub async fn monitor(adc1: ADC1, sense: GpioPin<1>, mut control: Output<'static>, config: Config) {
+    let mut adc_config = AdcConfig::new();
+    let mut pin = adc_config.enable_pin_with_cal::<_, AdcCalCurve<ADC1>>(sense, Attenuation::_11dB);
+    let mut adc = Adc::new(adc1, adc_config);
+    let sender = BATTERY_PERCENT.sender();
+
+    loop {
+        control.set_level(config.enable_level);
+        Timer::after(SETTLE_TIME).await;
+
+        // Calibrated readings, in millivolts at the pin
+        let mut total: u32 = 0;
+        for _ in 0..SAMPLES {
+            total += loop {
+                if let Ok(millivolts) = adc.read_oneshot(&mut pin) {
+                    break millivolts as u32;
+                }
+                embassy_futures::yield_now().await;
+            };
+        }
+        control.set_level(!config.enable_level);
+
+        let millivolts = ((total / SAMPLES) as f32 * config.divider_ratio) as u16;
+        let percent = percent(config.curve, millivolts);
+        defmt::info!("Battery: {} mV, {}%", millivolts, percent);
+        sender.send(percent);
+
+        Timer::after(config.interval).await;
+    }
+}
@@ -0,0 +1,120 @@
+use bt_hci::param::BdAddr;
+use embedded_storage::{ReadStorage, Storage};
+use esp_storage::{FlashStorage, FlashStorageError};
+use heapless::Vec;
+use trouble_host::prelude::*;
+
+use crate::lora::persist::SETTINGS_OFFSET;
+
+/// Centrals remembered at once; bonding another forgets the one bonded longest ago
+pub const MAX_BONDS: usize = 4;
+
+/// Where bonds are kept: the sector after the radio settings
+const BONDS_OFFSET: u32 = SETTINGS_OFFSET + FlashStorage::ERASE_SIZE as u32;
+
+/// Marks stored bonds, so that erased flash isn't taken for them
+const BONDS_MAGIC: [u8; 4] = *b"SBBK";
+
+/// Bytes per bond, see `encode`
+const BOND_SIZE: usize = 40;
+
+/// The magic, the number of bonds, then `MAX_BONDS` slots
+const BONDS_SIZE: usize = 5 + MAX_BONDS * BOND_SIZE;
+
+/// Set in a bond's first byte when the central shared an identity resolving key
+const HAS_IRK: u8 = 0x01;
+
+/// The bonds last stored, oldest first; none if there aren't any or they can't be read
+///
+/// A slot that fails its checksum is dropped on its own, the others are still used.
+pub fn load() -> Vec<BondInformation, MAX_BONDS> {
+    let mut bonds = Vec::new();
+
+    let mut bytes = [0u8; BONDS_SIZE];
+    if let Err(e) = FlashStorage::new().read(BONDS_OFFSET, &mut bytes) {
+        defmt::warn!(
+            "Failed to read the BLE bonds: {:?}",
+            defmt::Debug2Format(&e)
+        );
+        return bonds;
+    }
+    if bytes[..4] != BONDS_MAGIC {
+        return bonds;
+    }
+
+    let count = (bytes[4] as usize).min(MAX_BONDS);
+    for slot in bytes[5..].chunks_exact(BOND_SIZE).take(count) {
+        match decode(slot) {
+            Some(bond) => {
+                let _ = bonds.push(bond);
+            }
+            None => defmt::warn!("Dropping a corrupt BLE bond"),
+        }
+    }
+
+    bonds
+}
+
+/// Add `bond` to the stored ones, in place of any earlier bond with the same central
+///
+/// Rewrites the whole sector, which stalls the CPU for tens of milliseconds.
+pub fn remember(bond: BondInformation) -> Result<(), FlashStorageError> {
+    let mut bonds = load();
+    bonds.retain(|stored| stored.identity.bd_addr != bond.identity.bd_addr);
+    if bonds.is_full() {
+        bonds.remove(0);
+    }
+    let _ = bonds.push(bond);
+
+    let mut bytes = [0xFFu8; BONDS_SIZE];
+    bytes[..4].copy_from_slice(&BONDS_MAGIC);
+    bytes[4] = bonds.len() as u8;
+    for (slot, bond) in bytes[5..].chunks_exact_mut(BOND_SIZE).zip(&bonds) {
+        slot.copy_from_slice(&encode(bond));
+    }
+
+    FlashStorage::new().write(BONDS_OFFSET, &bytes)
+}
+
+/// | Byte | Field                                                    |
+/// |------|----------------------------------------------------------|
+/// | 0    | Flags, `HAS_IRK`                                         |
+/// | 1    | Address, least significant byte first                    |
+/// | 7    | Long-term key, `u128` LE                                 |
+/// | 23   | Identity resolving key, `u128` LE, zero without one      |
+/// | 39   | Inverted byte sum of bytes 0-38                          |
+fn encode(bond: &BondInformation) -> [u8; BOND_SIZE] {
+    let mut bytes = [0u8; BOND_SIZE];
+    if let Some(irk) = bond.identity.irk {
+        bytes[0] = HAS_IRK;
+        bytes[23..39].copy_from_slice(&irk.0.to_le_bytes());
+    }
+    bytes[1..7].copy_from_slice(bond.identity.bd_addr.raw());
+    bytes[7..23].copy_from_slice(&bond.ltk.0.to_le_bytes());
+    bytes[39] = !checksum(&bytes[..39]);
+
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> Option<BondInformation> {
+    if bytes[39] != !checksum(&bytes[..39]) {
+        return None;
+    }
+
+    let mut addr = [0u8; 6];
+    addr.copy_from_slice(&bytes[1..7]);
+    let ltk = u128::from_le_bytes(bytes[7..23].try_into().ok()?);
+    let irk = u128::from_le_bytes(bytes[23..39].try_into().ok()?);
+
+    Some(BondInformation::new(
+        Identity {
+            bd_addr: BdAddr::new(addr),
+            irk: (bytes[0] & HAS_IRK != 0).then(|| IdentityResolvingKey::new(irk)),
+        },
+        LongTermKey::new(ltk),
+    ))
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
@@ -1,20 +1,215 @@
 use bt_hci::param::{AddrKind, BdAddr};
+use embassy_time::{Duration, Instant};
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+use esp_storage::FlashStorage;
+use heapless::String;
+use static_cell::StaticCell;
+use trouble_host::prelude::AdvertisementParameters;
 use trouble_host::{Address, HostResources};
 
+use crate::flashlog::storage::{REGION_OFFSET, REGION_SECTORS};
+
 pub const DEVICE_SERVICE_UUID: u128 = 0x17ada41d_b564_4a77_ad1a_22cf554002fc;
 
 const L2CAP_MTU: usize = 255;
+
+/// Largest notification that fits the default ATT MTU of 23 bytes
+pub const NUS_CHUNK_SIZE: usize = 20;
+
+/// Room for `device::BUILD_INFO`, which is truncated to fit
+pub const BUILD_INFO_SIZE: usize = 32;
 const CONNECTIONS_MAX: usize = 1;
 const L2CAP_CHANNELS_MAX: usize = 2;
 
 pub type Resources = HostResources<CONNECTIONS_MAX, L2CAP_CHANNELS_MAX, L2CAP_MTU>;
 
+/// Longest provisioned name; with its 2-byte header it still fits the 31-byte scan response
+pub const MAX_NAME_LEN: usize = 20;
+
+/// Shortest advertising interval the Bluetooth spec allows
+const ADV_INTERVAL_MIN: Duration = Duration::from_millis(20);
+
+/// Longest advertising interval the Bluetooth spec allows
+const ADV_INTERVAL_MAX: Duration = Duration::from_millis(10_240);
+
+/// Flash offset of the provisioned identity, the sector right after the flash log
+///
+/// The firmware only reads it; write it when provisioning a unit, e.g. with
+/// `espflash write-bin 0x500000 identity.bin`. See `Config::from_storage` for the layout.
+pub const IDENTITY_OFFSET: u32 = REGION_OFFSET + REGION_SECTORS * FlashStorage::ERASE_SIZE as u32;
+
+/// Marks a provisioned identity, so that erased flash, or anything else, isn't taken for one
+const IDENTITY_MAGIC: [u8; 4] = *b"SBBI";
+
+const IDENTITY_SIZE: usize = 32;
+
+/// The provisioned name, which has to outlive the GATT server and advertising
+static STORED_NAME: StaticCell<String<MAX_NAME_LEN>> = StaticCell::new();
+
 pub struct Config {
     /// Name of the BLE device
     pub name: &'static str,
 
     /// Public address of the BLE device
     pub address: Address,
+
+    /// Health flags: checked every second, notified on change and every 30 s regardless
+    pub status: NotifyConfig,
+
+    /// Latest fix: notified as each new one arrives, at most every second
+    pub telemetry: NotifyConfig,
+
+    /// Recent errors: checked every second, notified when one is recorded
+    pub errors: NotifyConfig,
+
+    /// Battery level: notified as each measurement changes it, at most every 5 s
+    pub battery: NotifyConfig,
+
+    /// Shortest time between advertisements while waiting for a central
+    ///
+    /// Longer intervals save power on battery, at the cost of phones taking longer to find the
+    /// device; shorten them while provisioning. Clamped to 20 ms to 10.24 s.
+    pub adv_interval_min: Duration,
+
+    /// Longest time between advertisements, the controller picks within the range
+    pub adv_interval_max: Duration,
+
+    /// Whether centrals have to pair before they can see the position or change anything
+    pub pairing: Pairing,
+}
+
+/// What a central needs before it can read the position and waypoint, get telemetry
+/// notifications, or write any characteristic
+///
+/// Status, battery, errors and the build info stay readable either way. Phones pair on their own
+/// the first time a write is refused for lack of encryption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pairing {
+    /// Nothing; any central in range can follow the device and reconfigure it
+    Open,
+
+    /// An encrypted link, set up by pairing without a passkey
+    ///
+    /// Bonds are kept in flash, see `bonds`, so a phone that paired once reconnects without
+    /// pairing again, across resets too. Just Works keeps out anyone merely listening, but not an
+    /// attacker relaying the pairing itself; the board has no keypad to enter a passkey on, and
+    /// no way to show one other than the display, which is usually off.
+    JustWorks,
+}
+
+/// When a characteristic is notified, each running on its own schedule
+///
+/// The value is sampled every `min_interval`, or taken as it changes where its source can be
+/// waited on, and notified when it has changed. `offset` delays the first sample after
+/// connecting, so characteristics sharing an interval don't all queue their notifications at once.
+pub struct NotifyConfig {
+    /// Shortest time between notifications, and how often the value is checked for changes
+    pub min_interval: Duration,
+
+    /// Longest time between notifications while the value doesn't change; `None` to only
+    /// notify changes
+    pub max_interval: Option<Duration>,
+
+    /// Delay before the first check after connecting
+    pub offset: Duration,
+}
+
+impl Config {
+    /// Advertising parameters with the intervals set here
+    ///
+    /// The transmit power is left to the controller: legacy advertising, which is all the stack
+    /// does here, has no parameter for it, so setting one would be silently ignored.
+    pub fn advertisement_parameters(&self) -> AdvertisementParameters {
+        let interval_min = self
+            .adv_interval_min
+            .max(ADV_INTERVAL_MIN)
+            .min(ADV_INTERVAL_MAX);
+        let interval_max = self
+            .adv_interval_max
+            .max(interval_min)
+            .min(ADV_INTERVAL_MAX);
+
+        AdvertisementParameters {
+            interval_min,
+            interval_max,
+            ..Default::default()
+        }
+    }
+
+    /// The defaults, with the name and address provisioned at `IDENTITY_OFFSET` if there are any
+    ///
+    /// | Byte | Field                                                        |
+    /// |------|--------------------------------------------------------------|
+    /// | 0    | `SBBI`                                                       |
+    /// | 4    | Name length                                                  |
+    /// | 5    | Name, UTF-8, `MAX_NAME_LEN` bytes with the rest zero-padded  |
+    /// | 25   | Address, least significant byte first                        |
+    /// | 31   | Inverted byte sum of bytes 0-30                              |
+    ///
+    /// Only the first call can use a stored name; later ones get the default.
+    pub fn from_storage() -> Self {
+        let defaults = Self::default();
+
+        let mut bytes = [0u8; IDENTITY_SIZE];
+        if let Err(e) = FlashStorage::new().read(IDENTITY_OFFSET, &mut bytes) {
+            defmt::warn!(
+                "Failed to read the BLE identity: {:?}",
+                defmt::Debug2Format(&e)
+            );
+            return defaults;
+        }
+
+        let Some((name, addr)) = decode_identity(&bytes) else {
+            defmt::info!("No BLE identity provisioned, using the defaults");
+            return defaults;
+        };
+        let Some(name) = STORED_NAME.try_init(name) else {
+            return defaults;
+        };
+        defmt::info!("Provisioned BLE identity: {}", name.as_str());
+
+        Self {
+            name: name.as_str(),
+            address: Address {
+                kind: AddrKind::PUBLIC,
+                addr: BdAddr::new(addr),
+            },
+            ..defaults
+        }
+    }
+}
+
+fn decode_identity(bytes: &[u8; IDENTITY_SIZE]) -> Option<(String<MAX_NAME_LEN>, [u8; 6])> {
+    let sum = bytes[..31]
+        .iter()
+        .fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+    if bytes[..4] != IDENTITY_MAGIC || bytes[31] != !sum {
+        return None;
+    }
+
+    let name = bytes[5..5 + MAX_NAME_LEN].get(..bytes[4] as usize)?;
+    let name = String::try_from(core::str::from_utf8(name).ok()?).ok()?;
+
+    Some((
+        name,
+        [
+            bytes[25], bytes[26], bytes[27], bytes[28], bytes[29], bytes[30],
+        ],
+    ))
+}
+
+impl NotifyConfig {
+    /// Whether to notify now, given whether the value `changed` and when it was `last_notified`
+    pub fn is_due(&self, changed: bool, last_notified: Option<Instant>) -> bool {
+        let Some(last_notified) = last_notified else {
+            return true;
+        };
+
+        changed
+            || self
+                .max_interval
+                .is_some_and(|max_interval| last_notified.elapsed() >= max_interval)
+    }
 }
 
 impl Default for Config {
@@ -25,6 +220,29 @@ impl Default for Config {
                 kind: AddrKind::PUBLIC,
                 addr: BdAddr::new([0x48, 0xca, 0x43, 0x3b, 0x0f, 0xa8]),
             },
+            status: NotifyConfig {
+                min_interval: Duration::from_secs(1),
+                max_interval: Some(Duration::from_secs(30)),
+                offset: Duration::from_millis(0),
+            },
+            telemetry: NotifyConfig {
+                min_interval: Duration::from_secs(1),
+                max_interval: None,
+                offset: Duration::from_millis(500),
+            },
+            errors: NotifyConfig {
+                min_interval: Duration::from_secs(1),
+                max_interval: None,
+                offset: Duration::from_millis(250),
+            },
+            battery: NotifyConfig {
+                min_interval: Duration::from_secs(5),
+                max_interval: None,
+                offset: Duration::from_millis(750),
+            },
+            adv_interval_min: Duration::from_millis(160),
+            adv_interval_max: Duration::from_millis(160),
+            pairing: Pairing::JustWorks,
         }
     }
 }
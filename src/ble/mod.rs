@@ -1,30 +1,77 @@
+use crate::battery::monitor::BATTERY_PERCENT;
+use crate::device::BUILD_INFO;
+use crate::display::page::{Page, PageRequest, PAGE_REQUESTS};
+use crate::dock::{is_docked, set_docked_from_ble};
+use crate::errorlog::recent::recent_errors;
+use crate::gnss::positioning::BLE_TELEMETRY_SIZE;
+use crate::gnss::precision::{CoordinatePrecision, COORDINATE_PRECISION};
+use crate::gnss::watch::{GNSS_WATCH, NMEA_PASSTHROUGH, NMEA_PASSTHROUGH_ENABLED};
+use crate::lora::driver::{
+    check_settings, queue_command, radio_settings, LoraCommand, TxPacket, REPORT_FORMAT,
+};
+use crate::lora::settings::RadioSettings;
+use crate::lora::{health::HealthMonitor, report::ReportFormat};
+use crate::nav::{target, waypoint_from_ble_bytes, waypoint_to_ble_bytes};
+use crate::watchdog::{self, supervisor::TaskWatchdog};
 use bt_hci::controller::ExternalController;
-use config::{Config, Resources, DEVICE_SERVICE_UUID};
-use embassy_futures::{join::join, select::select};
-use embassy_time::Timer;
+use config::{Config, Pairing, Resources, BUILD_INFO_SIZE, DEVICE_SERVICE_UUID, NUS_CHUNK_SIZE};
+use core::cell::Cell;
+use core::sync::atomic::Ordering;
+use embassy_futures::{join::join, select::select4};
+use embassy_time::{with_timeout, Duration, Instant, Timer};
 use error::Error;
 use esp_hal::peripherals::BT;
+use esp_hal::rng::Rng;
 use esp_wifi::{ble::controller::BleConnector, EspWifiController};
-use service::DeviceService;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use service::{BatteryService, DeviceService, UartService};
 use state::StateController;
 use trouble_host::prelude::*;
 
+mod bonds;
 mod config;
 mod error;
 mod service;
 pub mod state;
 
+/// Upper bound on queueing a single notification
+///
+/// When the central walks out of range the controller only reports the disconnect once the
+/// connection supervision timeout expires; until then a notify can stall indefinitely waiting for
+/// a TX buffer. Treat a stall longer than this as a lost link.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the connection's RSSI is read into `State::rssi`
+const RSSI_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time the BLE task may go without feeding the watchdog before it counts as hung
+const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest advertising runs before it's restarted, so that the connection loop still checks in
+/// with the watchdog while no central connects
+const ADVERTISE_TIMEOUT: Duration = Duration::from_secs(20);
+
 /// BLE stack and its connection state
 pub struct Ble<'a, C: Controller> {
     config: Config,
+    stack: &'a Stack<'a, C>,
     peripheral: Peripheral<'a, C>,
     server: Server<'a>,
     state_controller: StateController,
+
+    /// Whether the current connection is encrypted, see `Config::pairing`
+    encrypted: Cell<bool>,
+
+    /// Fed by the connection loop, and while connected by each of the connection's tasks
+    watchdog: TaskWatchdog,
 }
 
 #[gatt_server]
 pub struct Server {
     device_service: DeviceService,
+    battery_service: BatteryService,
+    uart_service: UartService,
 }
 
 impl<'a, C: Controller> Ble<'a, C> {
@@ -33,20 +80,49 @@ impl<'a, C: Controller> Ble<'a, C> {
     /// * `peripheral` - The BLE peripheral interface
     /// * `stack` - Reference to the BLE stack
     /// * `config` - BLE configuration parameters
-    fn new(peripheral: Peripheral<'a, C>, config: Config) -> Result<Self, Error> {
+    fn new(
+        peripheral: Peripheral<'a, C>,
+        stack: &'a Stack<'a, C>,
+        config: Config,
+    ) -> Result<Self, Error> {
         let server = Server::new_with_config(GapConfig::Peripheral(PeripheralConfig {
             name: config.name,
             appearance: &appearance::outdoor_sports_activity::LOCATION_AND_NAVIGATION_POD,
         }))
         .map_err(|_| Error::GattError)?;
 
+        // Fixed for the life of the firmware, so set once rather than on every connection
+        let build_info = BUILD_INFO.as_bytes();
+        let build_info = &build_info[..build_info.len().min(BUILD_INFO_SIZE)];
+        server
+            .set(
+                &server.device_service.build_info,
+                &heapless::Vec::from_slice(build_info).unwrap_or_default(),
+            )
+            .map_err(|_| Error::GattError)?;
+
         let state_controller = StateController::new();
 
+        // Bonded centrals re-encrypt with their stored keys instead of pairing again
+        if config.pairing != Pairing::Open {
+            for bond in bonds::load() {
+                if let Err(e) = stack.add_bond_information(bond) {
+                    defmt::warn!(
+                        "Failed to restore a BLE bond: {:?}",
+                        defmt::Debug2Format(&e)
+                    );
+                }
+            }
+        }
+
         Ok(Self {
             peripheral,
+            stack,
             server,
             config,
             state_controller,
+            encrypted: Cell::new(false),
+            watchdog: watchdog::supervisor::register("BLE", WATCHDOG_TIMEOUT),
         })
     }
 
@@ -63,7 +139,7 @@ impl<'a, C: Controller> Ble<'a, C> {
             peripheral, runner, ..
         } = stack.build();
 
-        let mut ble = Self::new(peripheral, config)?;
+        let mut ble = Self::new(peripheral, stack, config)?;
 
         join(
             ble_task(runner),
@@ -77,26 +153,66 @@ impl<'a, C: Controller> Ble<'a, C> {
     /// Run the main BLE connection loop, handling advertising and events
     async fn run_connection_loop(&mut self) {
         loop {
+            self.watchdog.feed();
             embassy_futures::yield_now().await;
 
-            match advertise(self.config.name, &mut self.peripheral).await {
-                Ok(conn) => {
+            let params = self.config.advertisement_parameters();
+            let advertised = with_timeout(
+                ADVERTISE_TIMEOUT,
+                advertise(self.config.name, &params, &mut self.peripheral),
+            )
+            .await;
+            match advertised {
+                // No central yet; advertising starts over
+                Err(_) => {}
+                Ok(Ok(conn)) => {
                     defmt::info!("BLE connected");
                     self.state_controller.set_connected();
+                    self.encrypted.set(false);
+
+                    // Reads should reflect the format actually in use
+                    let report_format = &self.server.device_service.report_format;
+                    let _ = self
+                        .server
+                        .set(report_format, &REPORT_FORMAT.load(Ordering::Relaxed));
+                    let coordinate_precision = &self.server.device_service.coordinate_precision;
+                    let _ = self.server.set(
+                        coordinate_precision,
+                        &COORDINATE_PRECISION.load(Ordering::Relaxed),
+                    );
+                    let dock_mode = &self.server.device_service.dock_mode;
+                    let _ = self.server.set(dock_mode, &is_docked());
+                    let waypoint = &self.server.device_service.waypoint;
+                    let _ = self
+                        .server
+                        .set(waypoint, &waypoint_to_ble_bytes(target::target()));
+                    self.refresh_radio_settings();
 
                     // Run all connection-dependent tasks
-                    select(
+                    select4(
                         // BLE tasks
                         self.gatt_events_task(&conn),
-                        self.telemetry_task(&conn),
+                        self.status_notify_task(&conn),
+                        self.telemetry_notify_task(&conn),
+                        select4(
+                            self.nmea_passthrough_task(&conn),
+                            self.rssi_poll_task(&conn),
+                            self.error_notify_task(&conn),
+                            self.battery_notify_task(&conn),
+                        ),
                     )
                     .await;
 
-                    // Handle disconnection regardless of which task exited
+                    // Handle disconnection regardless of which task exited; `conn` is dropped at
+                    // the end of this arm, which frees the connection slot for re-advertising
                     defmt::info!("BLE disconnected");
                     self.state_controller.set_disconnected();
+
+                    // Don't keep queueing sentences nobody will receive
+                    NMEA_PASSTHROUGH_ENABLED.store(false, Ordering::Relaxed);
+                    NMEA_PASSTHROUGH.clear();
                 }
-                Err(_) => {
+                Ok(Err(_)) => {
                     defmt::error!("Error establishing a BLE connection");
                     self.state_controller.set_disconnected();
                     Timer::after_secs(1).await;
@@ -108,22 +224,219 @@ impl<'a, C: Controller> Ble<'a, C> {
     /// Handle GATT events for the BLE server
     async fn gatt_events_task(&self, conn: &Connection<'_>) -> Result<(), Error> {
         let level = &self.server.device_service.status;
+        let nmea_passthrough = &self.server.device_service.nmea_passthrough;
+        let report_format = &self.server.device_service.report_format;
+        let coordinate_precision = &self.server.device_service.coordinate_precision;
+        let display_page = &self.server.device_service.display_page;
+        let dock_mode = &self.server.device_service.dock_mode;
+        let waypoint = &self.server.device_service.waypoint;
+        let connection_info = &self.server.device_service.connection_info;
+        let command = &self.server.device_service.command;
+        let lora_frequency = &self.server.device_service.lora_frequency;
+        let lora_spreading_factor = &self.server.device_service.lora_spreading_factor;
+        let lora_node_id = &self.server.device_service.lora_node_id;
+        let telemetry = &self.server.device_service.telemetry;
         loop {
+            self.watchdog.feed();
             embassy_futures::yield_now().await;
 
             match conn.next().await {
-                ConnectionEvent::Disconnected { reason: _ } => break,
+                // Also fires on link loss, once the supervision timeout expires
+                ConnectionEvent::Disconnected { reason } => {
+                    defmt::info!("BLE link closed: {:?}", defmt::Debug2Format(&reason));
+                    break;
+                }
+                ConnectionEvent::PairingComplete {
+                    security_level,
+                    bond,
+                } => {
+                    defmt::info!(
+                        "BLE link secured: {:?}",
+                        defmt::Debug2Format(&security_level)
+                    );
+                    self.encrypted.set(security_level.encrypted());
+
+                    if let Some(bond) = bond {
+                        if let Err(e) = bonds::remember(bond) {
+                            defmt::warn!(
+                                "Failed to store the BLE bond: {:?}",
+                                defmt::Debug2Format(&e)
+                            );
+                        }
+                    }
+                }
+                ConnectionEvent::PairingFailed(e) => {
+                    defmt::warn!("BLE pairing failed: {:?}", defmt::Debug2Format(&e));
+                }
                 ConnectionEvent::Gatt { data } => match data.process(&self.server).await {
                     Ok(Some(event)) => {
+                        // Refused writes are answered with this instead of being stored
+                        let mut rejection: Option<AttErrorCode> = None;
+
                         match &event {
+                            // The central is expected to pair and try again
+                            GattEvent::Read(event)
+                                if !self.is_authorized()
+                                    && (event.handle() == telemetry.handle
+                                        || event.handle() == waypoint.handle) =>
+                            {
+                                rejection = Some(AttErrorCode::INSUFFICIENT_ENCRYPTION);
+                            }
+                            GattEvent::Write(_) if !self.is_authorized() => {
+                                rejection = Some(AttErrorCode::INSUFFICIENT_ENCRYPTION);
+                            }
                             GattEvent::Read(event) => {
                                 if event.handle() == level.handle {
                                     let _value = self.server.get(&level);
                                 }
+
+                                // Refreshed on demand, the reply is built when it's accepted
+                                if event.handle() == connection_info.handle {
+                                    let _ = self.server.set(
+                                        connection_info,
+                                        &self.state_controller.state().connection_info(),
+                                    );
+                                }
+
+                                if event.handle() == lora_frequency.handle
+                                    || event.handle() == lora_spreading_factor.handle
+                                    || event.handle() == lora_node_id.handle
+                                {
+                                    self.refresh_radio_settings();
+                                }
+                            }
+                            GattEvent::Write(event) => {
+                                if event.handle() == nmea_passthrough.handle {
+                                    let enabled = event.data().first().is_some_and(|&b| b != 0);
+                                    defmt::info!("NMEA passthrough enabled: {}", enabled);
+
+                                    NMEA_PASSTHROUGH_ENABLED.store(enabled, Ordering::Relaxed);
+                                    if !enabled {
+                                        NMEA_PASSTHROUGH.clear();
+                                    }
+                                }
+
+                                if event.handle() == report_format.handle {
+                                    let byte = event.data().first().copied().unwrap_or_default();
+
+                                    rejection = match ReportFormat::try_from(byte) {
+                                        Ok(format) => {
+                                            defmt::info!(
+                                                "Report format set to {:?}",
+                                                defmt::Debug2Format(&format)
+                                            );
+                                            Self::request_radio_settings(|settings| {
+                                                settings.report_format = format
+                                            })
+                                            .err()
+                                        }
+                                        Err(_) => {
+                                            defmt::warn!("Unknown report format: {}", byte);
+                                            Some(AttErrorCode::VALUE_NOT_ALLOWED)
+                                        }
+                                    };
+                                }
+
+                                if event.handle() == coordinate_precision.handle {
+                                    rejection = match event.data() {
+                                        &[decimals] => match CoordinatePrecision::new(decimals) {
+                                            Some(precision) => {
+                                                Self::request_radio_settings(|settings| {
+                                                    settings.coordinate_precision = precision
+                                                })
+                                                .err()
+                                            }
+                                            None => Some(AttErrorCode::VALUE_NOT_ALLOWED),
+                                        },
+                                        _ => Some(AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH),
+                                    };
+                                }
+
+                                if event.handle() == dock_mode.handle {
+                                    let docked = event.data().first().is_some_and(|&b| b != 0);
+
+                                    if !set_docked_from_ble(docked) {
+                                        defmt::warn!("Ignoring dock mode write, BLE trigger off");
+                                    }
+                                }
+
+                                if event.handle() == waypoint.handle {
+                                    let bytes = event.data();
+
+                                    if bytes.iter().all(|&b| b == 0) {
+                                        defmt::info!("Waypoint cleared");
+                                        target::set_target(None);
+                                    } else if let Some(waypoint) = waypoint_from_ble_bytes(bytes) {
+                                        defmt::info!(
+                                            "Waypoint set to {}, {}",
+                                            waypoint.0,
+                                            waypoint.1
+                                        );
+                                        target::set_target(Some(waypoint));
+                                    } else {
+                                        defmt::warn!("Ignoring invalid waypoint");
+                                    }
+                                }
+
+                                if event.handle() == command.handle {
+                                    Self::handle_command(event.data());
+                                }
+
+                                if event.handle() == lora_frequency.handle {
+                                    rejection = match <[u8; 4]>::try_from(event.data()) {
+                                        Ok(bytes) => Self::request_radio_settings(|settings| {
+                                            settings.frequency = u32::from_le_bytes(bytes)
+                                        })
+                                        .err(),
+                                        Err(_) => {
+                                            Some(AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH)
+                                        }
+                                    };
+                                }
+
+                                if event.handle() == lora_spreading_factor.handle {
+                                    rejection = match event.data() {
+                                        &[spreading_factor] => {
+                                            Self::request_radio_settings(|settings| {
+                                                settings.spreading_factor = spreading_factor
+                                            })
+                                            .err()
+                                        }
+                                        _ => Some(AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH),
+                                    };
+                                }
+
+                                if event.handle() == lora_node_id.handle {
+                                    rejection = match <[u8; 4]>::try_from(event.data()) {
+                                        Ok(bytes) => Self::request_radio_settings(|settings| {
+                                            settings.node_id = u32::from_le_bytes(bytes)
+                                        })
+                                        .err(),
+                                        Err(_) => {
+                                            Some(AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH)
+                                        }
+                                    };
+                                }
+
+                                if event.handle() == display_page.handle {
+                                    let index = event.data().first().copied().unwrap_or_default();
+
+                                    match Page::try_from(index) {
+                                        Ok(page) => {
+                                            let _ = PAGE_REQUESTS.try_send(PageRequest::Show(page));
+                                        }
+                                        Err(_) => {
+                                            defmt::warn!("Ignoring unknown display page: {}", index)
+                                        }
+                                    }
+                                }
                             }
-                            GattEvent::Write(event) => if event.handle() == level.handle {},
                         }
-                        if let Ok(reply) = event.accept() {
+                        let reply = match rejection {
+                            Some(code) => event.reject(code),
+                            None => event.accept(),
+                        };
+                        if let Ok(reply) = reply {
                             reply.send().await;
                         }
                     }
@@ -135,22 +448,285 @@ impl<'a, C: Controller> Ble<'a, C> {
         Ok(())
     }
 
-    async fn telemetry_task(&self, conn: &Connection<'_>) -> Result<(), Error> {
-        let mut counter: u8 = 0;
+    /// Notify the health flags on the schedule in `Config::status`
+    async fn status_notify_task(&self, conn: &Connection<'_>) -> Result<(), Error> {
         let status = self.server.device_service.status;
+        let schedule = &self.config.status;
+        let mut health = HealthMonitor::new();
+        let mut last_flags: Option<u8> = None;
+        let mut last_notified: Option<Instant> = None;
+
+        Timer::after(schedule.offset).await;
+
+        loop {
+            self.watchdog.feed();
+            let flags = u8::from(health.sample());
+            if schedule.is_due(last_flags != Some(flags), last_notified) {
+                if !Self::notify_within(status.notify(&self.server, conn, &flags)).await {
+                    break;
+                }
+
+                defmt::info!("Status flags: {=u8:#b}", flags);
+                last_flags = Some(flags);
+                last_notified = Some(Instant::now());
+            }
+
+            Timer::after(schedule.min_interval).await;
+        }
+        Ok(())
+    }
+
+    /// Notify each new fix as `GnssPositioning::to_ble_bytes`, no more often than the schedule in
+    /// `Config::telemetry` allows; nothing is sent before the first fix
+    ///
+    /// Fixes that arrive while the schedule holds notifications back are skipped, so the next
+    /// notification always carries the latest one.
+    async fn telemetry_notify_task(&self, conn: &Connection<'_>) -> Result<(), Error> {
+        let telemetry = self.server.device_service.telemetry;
+        let schedule = &self.config.telemetry;
+        let mut last_bytes: Option<[u8; BLE_TELEMETRY_SIZE]> = None;
+        let mut last_notified: Option<Instant> = None;
+
+        // Released when the connection ends, for the next one to take
+        let Some(mut gnss_rx) = GNSS_WATCH.receiver() else {
+            defmt::error!("No GNSS receiver left for BLE telemetry");
+            return core::future::pending().await;
+        };
+
+        Timer::after(schedule.offset).await;
+
+        loop {
+            self.watchdog.feed();
+            let fix = match schedule.max_interval {
+                // Wake up in time to repeat the last fix if no new one comes
+                Some(max_interval) => with_timeout(max_interval, gnss_rx.changed())
+                    .await
+                    .unwrap_or_else(|_| GNSS_WATCH.try_get().flatten()),
+                None => gnss_rx.changed().await,
+            };
+            // A fix restored from flash is only for the display
+            let fix = fix.filter(|positioning| !positioning.is_stale);
+            if let Some(bytes) = fix.map(|positioning| positioning.to_ble_bytes()) {
+                // Held back until the link is secured, and then sent with the next fix
+                if self.is_authorized() && schedule.is_due(last_bytes != Some(bytes), last_notified)
+                {
+                    if !Self::notify_within(telemetry.notify(&self.server, conn, &bytes)).await {
+                        break;
+                    }
+
+                    last_bytes = Some(bytes);
+                    last_notified = Some(Instant::now());
+                }
+            }
+
+            Timer::after(schedule.min_interval).await;
+        }
+        Ok(())
+    }
+
+    /// Notify the recent errors on the schedule in `Config::errors`, once one has been recorded
+    async fn error_notify_task(&self, conn: &Connection<'_>) -> Result<(), Error> {
+        let error_log = self.server.device_service.error_log;
+        let schedule = &self.config.errors;
+        let mut last_recorded: Option<u32> = None;
+        let mut last_notified: Option<Instant> = None;
+
+        Timer::after(schedule.offset).await;
 
         loop {
-            counter = counter.wrapping_add(1);
+            self.watchdog.feed();
+            let errors = recent_errors();
+            let bytes = errors.to_bytes();
+            let changed = last_recorded != Some(errors.recorded());
+            if changed {
+                // Kept current for reads as well, errors from before connecting included
+                let _ = self.server.set(&error_log, &bytes);
+            }
 
-            if status.notify(&self.server, conn, &counter).await.is_err() {
-                break;
+            if errors.recorded() > 0 && schedule.is_due(changed, last_notified) {
+                if !Self::notify_within(error_log.notify(&self.server, conn, &bytes)).await {
+                    break;
+                }
+
+                defmt::info!("Error log: {}", bytes);
+                last_notified = Some(Instant::now());
             }
+            last_recorded = Some(errors.recorded());
 
-            defmt::info!("Counter: {}", counter);
-            Timer::after_secs(1).await;
+            Timer::after(schedule.min_interval).await;
         }
         Ok(())
     }
+
+    /// Notify each battery measurement that changes the level, no more often than the schedule in
+    /// `Config::battery` allows; nothing is sent before the first measurement
+    async fn battery_notify_task(&self, conn: &Connection<'_>) -> Result<(), Error> {
+        let level = self.server.battery_service.level;
+        let schedule = &self.config.battery;
+        let mut last_percent: Option<u8> = None;
+        let mut last_notified: Option<Instant> = None;
+
+        // Released when the connection ends, for the next one to take
+        let Some(mut battery_rx) = BATTERY_PERCENT.receiver() else {
+            defmt::error!("No battery receiver left for BLE");
+            return core::future::pending().await;
+        };
+
+        Timer::after(schedule.offset).await;
+
+        loop {
+            self.watchdog.feed();
+            let percent = battery_rx.changed().await;
+            // Kept current for reads as well
+            let _ = self.server.set(&level, &percent);
+
+            if schedule.is_due(last_percent != Some(percent), last_notified) {
+                if !Self::notify_within(level.notify(&self.server, conn, &percent)).await {
+                    break;
+                }
+
+                last_percent = Some(percent);
+                last_notified = Some(Instant::now());
+            }
+
+            Timer::after(schedule.min_interval).await;
+        }
+        Ok(())
+    }
+
+    /// Read the connection's RSSI into the published state every `RSSI_POLL_INTERVAL`
+    ///
+    /// A failed read is left for the other tasks to notice if the link is gone.
+    async fn rssi_poll_task(&self, conn: &Connection<'_>) -> Result<(), Error> {
+        loop {
+            self.watchdog.feed();
+            match conn.rssi(self.stack).await {
+                Ok(rssi) => self.state_controller.set_rssi(rssi),
+                Err(e) => defmt::debug!("Failed to read RSSI: {:?}", defmt::Debug2Format(&e)),
+            }
+
+            Timer::after(RSSI_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Decode a write to the `command` characteristic and pass it on to the LoRa task
+    fn handle_command(data: &[u8]) {
+        let Some((&opcode, arguments)) = data.split_first() else {
+            return;
+        };
+
+        let command = match opcode {
+            0x01 => match TxPacket::from_slice(arguments) {
+                Ok(packet) if !packet.is_empty() => LoraCommand::Send(packet),
+                _ => {
+                    defmt::warn!("Ignoring transmit command without a packet");
+                    return;
+                }
+            },
+            0x02 => match arguments.first() {
+                Some(&spreading_factor) => LoraCommand::SetSpreadingFactor(spreading_factor),
+                None => {
+                    defmt::warn!("Ignoring spreading factor command without one");
+                    return;
+                }
+            },
+            _ => {
+                defmt::warn!("Ignoring unknown command {=u8:#x}", opcode);
+                return;
+            }
+        };
+
+        if let Err(e) = queue_command(command) {
+            defmt::warn!("Dropping command: {:?}", defmt::Debug2Format(&e));
+        }
+    }
+
+    /// Queue the live radio settings with `change` made, or the error to refuse the write with
+    ///
+    /// The change is checked here so that the central hears about a bad value, rather than the
+    /// LoRa task quietly ignoring it later.
+    fn request_radio_settings(change: impl FnOnce(&mut RadioSettings)) -> Result<(), AttErrorCode> {
+        let Some(mut settings) = radio_settings() else {
+            defmt::warn!("Refusing radio settings before the radio is up");
+            return Err(AttErrorCode::UNLIKELY_ERROR);
+        };
+        change(&mut settings);
+
+        if let Err(e) = check_settings(&settings) {
+            defmt::warn!(
+                "Refusing {} Hz, SF{}, node {=u32:#x}: {:?}",
+                settings.frequency,
+                settings.spreading_factor,
+                settings.node_id,
+                defmt::Debug2Format(&e)
+            );
+            return Err(AttErrorCode::VALUE_NOT_ALLOWED);
+        }
+
+        queue_command(LoraCommand::Reconfigure(settings)).map_err(|e| {
+            defmt::warn!("Dropping radio settings: {:?}", defmt::Debug2Format(&e));
+            AttErrorCode::INSUFFICIENT_RESOURCES
+        })
+    }
+
+    /// Show the frequency, spreading factor and node ID the radio is actually using
+    fn refresh_radio_settings(&self) {
+        let Some(settings) = radio_settings() else {
+            return;
+        };
+
+        let service = &self.server.device_service;
+        let _ = self
+            .server
+            .set(&service.lora_frequency, &settings.frequency);
+        let _ = self
+            .server
+            .set(&service.lora_spreading_factor, &settings.spreading_factor);
+        let _ = self.server.set(&service.lora_node_id, &settings.node_id);
+    }
+
+    /// Whether the current connection may see the position and change settings, see `Pairing`
+    fn is_authorized(&self) -> bool {
+        self.config.pairing == Pairing::Open || self.encrypted.get()
+    }
+
+    /// Wait out a notification, `false` if the link should be torn down
+    async fn notify_within<E>(notify: impl core::future::Future<Output = Result<(), E>>) -> bool {
+        match with_timeout(NOTIFY_TIMEOUT, notify).await {
+            Ok(Ok(())) => true,
+            Ok(Err(_)) => {
+                defmt::warn!("Notify failed, tearing down connection");
+                false
+            }
+            Err(_) => {
+                defmt::warn!("Notify stalled, assuming the link is lost");
+                false
+            }
+        }
+    }
+
+    /// Stream raw NMEA sentences over the UART service TX characteristic, chunked to fit the MTU
+    async fn nmea_passthrough_task(&self, conn: &Connection<'_>) -> Result<(), Error> {
+        let tx = self.server.uart_service.tx;
+
+        loop {
+            self.watchdog.feed();
+            let sentence = NMEA_PASSTHROUGH.receive().await;
+
+            for chunk in sentence.chunks(NUS_CHUNK_SIZE) {
+                // Chunks are at most NUS_CHUNK_SIZE long, so this can't fail
+                let value = heapless::Vec::from_slice(chunk).unwrap_or_default();
+
+                match with_timeout(NOTIFY_TIMEOUT, tx.notify(&self.server, conn, &value)).await {
+                    Ok(Ok(())) => {}
+                    _ => {
+                        defmt::warn!("NMEA passthrough notify failed, tearing down connection");
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
 }
 
 /// Run the BLE host stack task
@@ -164,9 +740,10 @@ async fn ble_task<C: Controller>(mut runner: Runner<'_, C>) {
     }
 }
 
-/// Advertise the BLE device for incoming connections
+/// Advertise the BLE device with `params` until a central connects
 async fn advertise<'a, C: Controller>(
     name: &'a str,
+    params: &AdvertisementParameters,
     peripheral: &mut Peripheral<'a, C>,
 ) -> Result<Connection<'a>, BleHostError<C::Error>> {
     let mut advertiser_data = [0; 31];
@@ -187,7 +764,7 @@ async fn advertise<'a, C: Controller>(
 
     match peripheral
         .advertise(
-            &Default::default(),
+            params,
             Advertisement::ConnectableScannableUndirected {
                 adv_data: &advertiser_data[..adv_len],
                 scan_data: &scan_data[..scan_len],
@@ -206,8 +783,10 @@ async fn advertise<'a, C: Controller>(
 }
 
 /// Initialize and start the BLE module (entry point for the BLE module)
+///
+/// `rng` seeds the keys generated for pairing.
 #[embassy_executor::task]
-pub async fn start(bt: BT, init: EspWifiController<'static>) {
+pub async fn start(bt: BT, init: EspWifiController<'static>, mut rng: Rng) {
     defmt::info!("starting BLE");
     let connector = BleConnector::new(&init, bt);
 
@@ -215,8 +794,15 @@ pub async fn start(bt: BT, init: EspWifiController<'static>) {
 
     let mut resources = Resources::new();
 
-    let config = Config::default();
-    let stack = trouble_host::new(controller, &mut resources).set_random_address(config.address);
+    let config = Config::from_storage();
+    // The hardware RNG only draws on radio noise once the radio is up, which `init` guarantees
+    let mut seed = [0u8; 32];
+    rng.read(&mut seed);
+    let mut pairing_rng = ChaCha12Rng::from_seed(seed);
+
+    let stack = trouble_host::new(controller, &mut resources)
+        .set_random_address(config.address)
+        .set_random_generator_seed(&mut pairing_rng);
 
     Ble::start(&stack, config).await.unwrap();
 }
@@ -1,15 +1,106 @@
-use trouble_host::prelude::gatt_service;
+use trouble_host::prelude::{characteristic, gatt_service, service};
 
-use super::config::DEVICE_SERVICE_UUID;
+use crate::errorlog::ERROR_LOG_SIZE;
+use crate::nav::WAYPOINT_SIZE;
+
+use super::config::{BUILD_INFO_SIZE, DEVICE_SERVICE_UUID, NUS_CHUNK_SIZE};
 
 #[gatt_service(uuid = DEVICE_SERVICE_UUID)]
 pub struct DeviceService {
+    /// `lora::packet::HealthFlags`, notified as set in `Config::status`
     #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf13", read, notify)]
     pub status: u8,
 
+    /// Latest fix, laid out as documented on `GnssPositioning::to_ble_bytes`; zeroed until then,
+    /// notified as set in `Config::telemetry`
     #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf15", read, notify)]
     pub telemetry: [u8; 24],
 
+    /// Recent `errorlog::ErrorCode`s, the newest first and zero-padded, notified as set in
+    /// `Config::errors`
     #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf14", read, notify)]
-    pub error_log: [u8; 7],
+    pub error_log: [u8; ERROR_LOG_SIZE],
+
+    /// Write `true` to stream every raw NMEA sentence over the UART service's TX characteristic
+    #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf16", read, write)]
+    pub nmea_passthrough: bool,
+
+    /// On-air position format: 0 compact, 1 standard, 2 full, 3 delta (see
+    /// `lora::report::ReportFormat`); others are refused, and accepted ones are kept across resets
+    /// like `lora_frequency`
+    #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf17", read, write)]
+    pub report_format: u8,
+
+    /// Decimal places, 0 to 7, that compact reports and the display round coordinates to (see
+    /// `gnss::precision::CoordinatePrecision`); kept across resets like `lora_frequency`
+    #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf21", read, write)]
+    pub coordinate_precision: u8,
+
+    /// Display page to show: 0 status, 1 coordinates, 2 radio, 3 satellites, 4 navigation,
+    /// 5 diagnostics
+    #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf18", write)]
+    pub display_page: u8,
+
+    /// Write `true` to enter dock mode and `false` to leave it, unless the BLE trigger is disabled
+    /// (see `dock::Config`)
+    #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf1b", read, write)]
+    pub dock_mode: bool,
+
+    /// Waypoint for the navigation page, as read by `nav::waypoint_from_ble_bytes`; write
+    /// zeroes to clear it
+    #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf1d", read, write)]
+    pub waypoint: [u8; WAYPOINT_SIZE],
+
+    /// LoRa receive frequency in Hz, `u32` LE; writes outside the region's band are refused, and
+    /// accepted ones are kept across resets
+    #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf1e", read, write)]
+    pub lora_frequency: u32,
+
+    /// LoRa spreading factor, 5 to 12; refused where the region's dwell time limit rules it out,
+    /// and otherwise kept across resets like `lora_frequency`
+    #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf1f", read, write)]
+    pub lora_spreading_factor: u8,
+
+    /// LoRa node ID, the source of every frame sent, `u24` in a `u32` LE; the broadcast ID
+    /// `0xFFFFFF` is refused, and accepted ones are kept across resets like `lora_frequency`
+    #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf20", read, write)]
+    pub lora_node_id: u32,
+
+    /// Radio commands, an opcode followed by its arguments; unknown opcodes are ignored
+    ///
+    /// | Opcode | Arguments                                               |
+    /// |--------|---------------------------------------------------------|
+    /// | `0x01` | A packet to transmit over LoRa, its type byte first     |
+    /// | `0x02` | Spreading factor to switch to, one byte, 5 to 12        |
+    #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf1c", write)]
+    pub command: [u8; 16],
+
+    /// Current connection's uptime in seconds, then disconnects since boot; both `u32` LE
+    #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf1a", read)]
+    pub connection_info: [u8; 8],
+
+    /// Firmware version, git hash and build date as ASCII, see `device::BUILD_INFO`
+    #[characteristic(uuid = "17a8a05b-5da4-44ae-82a5-6d660b08cf19", read)]
+    pub build_info: heapless::Vec<u8, BUILD_INFO_SIZE>,
+}
+
+/// Standard Battery Service, which phones and generic BLE tools show without any setup
+#[gatt_service(uuid = service::BATTERY)]
+pub struct BatteryService {
+    /// Charge left in percent, notified as set in `Config::battery`; stays 0 without the
+    /// `battery-sense` feature
+    #[characteristic(uuid = characteristic::BATTERY_LEVEL, read, notify)]
+    pub level: u8,
+}
+
+/// Nordic UART Service, understood by most generic BLE serial terminals
+#[gatt_service(uuid = "6e400001-b5a3-f393-e0a9-e50e24dcca9e")]
+pub struct UartService {
+    /// Central to peripheral; currently unused
+    #[characteristic(uuid = "6e400002-b5a3-f393-e0a9-e50e24dcca9e", write)]
+    pub rx: heapless::Vec<u8, NUS_CHUNK_SIZE>,
+
+    /// Peripheral to central
+    #[characteristic(uuid = "6e400003-b5a3-f393-e0a9-e50e24dcca9e", notify)]
+    pub tx: heapless::Vec<u8, NUS_CHUNK_SIZE>,
 }
@@ -1,4 +1,6 @@
+use core::cell::Cell;
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, watch::Watch};
+use embassy_time::{Duration, Instant};
 
 const WATCH_BUFFER_SIZE: usize = 4;
 
@@ -12,13 +14,22 @@ type BleStateTx =
     embassy_sync::watch::Sender<'static, CriticalSectionRawMutex, State, WATCH_BUFFER_SIZE>;
 
 /// Current state of the BLE connection
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct State {
     /// Indicates whether a BLE connection is active
     pub connection_status: bool,
 
     /// Received Signal Strength Indicator (RSSI), if available
     pub rssi: Option<i8>,
+
+    /// Whether the radio came up at all; `false` means the device is running without BLE
+    pub available: bool,
+
+    /// When the current connection was established; `None` while disconnected
+    pub connected_since: Option<Instant>,
+
+    /// Connections lost or closed since boot
+    pub disconnect_count: u32,
 }
 
 impl Default for State {
@@ -26,12 +37,39 @@ impl Default for State {
         Self {
             connection_status: false,
             rssi: None,
+            available: true,
+            connected_since: None,
+            disconnect_count: 0,
         }
     }
 }
 
+impl State {
+    /// How long the current connection has been up
+    pub fn connected_for(&self) -> Option<Duration> {
+        self.connected_since.map(|since| since.elapsed())
+    }
+
+    /// Value of the connection info characteristic: uptime in seconds (0 while disconnected),
+    /// then the disconnect count, both `u32` little-endian
+    pub fn connection_info(&self) -> [u8; 8] {
+        let uptime = self
+            .connected_for()
+            .map_or(0, |uptime| uptime.as_secs().min(u32::MAX as u64) as u32);
+
+        let mut info = [0u8; 8];
+        info[..4].copy_from_slice(&uptime.to_le_bytes());
+        info[4..].copy_from_slice(&self.disconnect_count.to_le_bytes());
+
+        info
+    }
+}
+
+/// Keeps `BLE_STATE` up to date
+///
+/// Updates take `&self`, so the tasks serving a connection can share it.
 pub struct StateController {
-    state: State,
+    state: Cell<State>,
     sender: BleStateTx,
 }
 
@@ -39,19 +77,57 @@ impl StateController {
     pub fn new() -> Self {
         let state = State::default();
         let sender = BLE_STATE.sender();
-        sender.send(state.clone());
+        sender.send(state);
+
+        Self {
+            state: Cell::new(state),
+            sender,
+        }
+    }
 
-        Self { state, sender }
+    pub fn state(&self) -> State {
+        self.state.get()
     }
 
-    pub fn set_connected(&mut self) {
-        self.state.connection_status = true;
-        self.sender.send(self.state.clone());
+    /// Apply `update` to the state and publish the result
+    fn update(&self, update: impl FnOnce(&mut State)) {
+        let mut state = self.state.get();
+        update(&mut state);
+
+        self.state.set(state);
+        self.sender.send(state);
     }
 
-    pub fn set_disconnected(&mut self) {
-        self.state.connection_status = false;
-        self.state.rssi = None;
-        self.sender.send(self.state.clone());
+    pub fn set_connected(&self) {
+        self.update(|state| {
+            state.connection_status = true;
+            state.connected_since = Some(Instant::now());
+        });
     }
+
+    /// Also called when a connection attempt fails, which doesn't count as a disconnect
+    pub fn set_disconnected(&self) {
+        self.update(|state| {
+            if state.connection_status {
+                state.disconnect_count = state.disconnect_count.wrapping_add(1);
+            }
+
+            state.connection_status = false;
+            state.connected_since = None;
+            state.rssi = None;
+        });
+    }
+
+    /// Signal strength of the current connection, in dBm
+    pub fn set_rssi(&self, rssi: i8) {
+        self.update(|state| state.rssi = Some(rssi));
+    }
+}
+
+/// Publish that BLE could not be brought up, so the rest of the device knows to run without it
+pub fn set_unavailable() {
+    BLE_STATE.sender().send(State {
+        available: false,
+        ..State::default()
+    });
 }
@@ -0,0 +1,23 @@
+use esp_hal::efuse::Efuse;
+
+/// Firmware version from the crate manifest
+pub const FIRMWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Short hash of the commit the firmware was built from, `unknown` outside a git checkout
+pub const GIT_HASH: &str = env!("NOMAD_GIT_HASH");
+
+/// Version, commit and UTC build date in one line, e.g. `0.1.0 1bb1639 2026-10-16`
+pub const BUILD_INFO: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " ",
+    env!("NOMAD_GIT_HASH"),
+    " ",
+    env!("NOMAD_BUILD_DATE")
+);
+
+/// Short identifier unique to this board, taken from the low three bytes of the factory MAC
+pub fn device_id() -> u32 {
+    let mac = Efuse::read_base_mac_address();
+
+    u32::from_be_bytes([0, mac[3], mac[4], mac[5]])
+}
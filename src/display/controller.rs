@@ -1,161 +1,716 @@
 use crate::{
-    ble::state::{BleStateRx, BLE_STATE},
-    gnss::{positioning::GnssPositioning, watch::GnssStateRx, watch::GNSS_WATCH},
+    app_state::{AppState, AppStateRx, APP_STATE},
+    device::{device_id, FIRMWARE_VERSION, GIT_HASH},
+    errorlog::{self, recent::last_error, ErrorCode},
+    gnss::{
+        positioning::GnssPositioning,
+        precision::CoordinatePrecision,
+        satellites::SatelliteInfo,
+        watch::{fix_age, GNSS_SATELLITES},
+    },
+    indicator::FLASH_DURATION,
+    lora::{
+        driver::{RADIO_STATS, REPORT_FORMAT},
+        report::ReportFormat,
+    },
+    nav::{bearing_to, distance_to, heading_to_compass, relative_bearing, target::target},
+    watchdog,
 };
 use core::fmt::Write;
-use embassy_futures::select::{select, Either};
-use embassy_time::{Duration, Timer};
+use core::sync::atomic::{AtomicBool, Ordering};
+use embassy_futures::select::{select, select3, Either, Either3};
+use embassy_time::{Duration, Instant, Timer};
 use embedded_graphics::prelude::Point;
 use heapless::String;
 
-use super::DisplayDevice;
+#[cfg(feature = "diagnostics")]
+use super::diagnostics;
+use super::page::{draw_lines, Line, Page, PageRequest, PAGE_REQUESTS};
+use super::{
+    DisplayDevice, DisplayInitError, DisplaySize, Icon, BATTERY_LEVELS, DEFAULT_BRIGHTNESS,
+    ICON_SIZE, SIGNAL_LEVELS,
+};
+
+/// How long the boot splash stays up before the status layout takes over
+const SPLASH_DURATION: Duration = Duration::from_secs(2);
+
+/// Idle time before the panel blanks, unless the caller picks another
+pub const DEFAULT_SLEEP_AFTER: Duration = Duration::from_secs(300);
+
+/// How long without a BLE client before the panel dims, e.g. left on a nightstand
+const DIM_AFTER: Duration = Duration::from_secs(60);
+
+/// Panel brightness while dimmed
+const DIM_BRIGHTNESS: u8 = 0;
+
+/// Left edge of the GNSS icons in the status bar, clear of the Bluetooth icon and its mark
+const STATUS_BAR_GNSS_X: i32 = 24;
+
+/// Left edge of the battery icon in the status bar, its percentage following it
+const STATUS_BAR_BATTERY_X: i32 = 90;
+
+/// Panel width in pixels, the same for every `DisplaySize`
+const PANEL_WIDTH: u32 = 128;
+
+/// Space left around the navigation page's arrow
+const NAVIGATION_ARROW_MARGIN: u32 = 4;
+
+/// Largest radius of the navigation page's arrow, clear of 13 characters of text on its left
+const NAVIGATION_ARROW_MAX_RADIUS: u32 = 20;
+
+/// Satellites in use per bar of the GNSS signal icon
+const SATELLITES_PER_BAR: u32 = 3;
+
+/// Consecutive failed updates after which the I2C bus and panel get reset
+const FAILURES_BEFORE_RECOVERY: u32 = 3;
+
+/// Time between checks that the panel is still connected, or has come back
+const PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest the run loop may take to come round before it counts as hung; it wakes at least
+/// every `PROBE_INTERVAL`, and a recovery or flash adds a few seconds at most
+const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether the panel answers on the I2C bus; cleared while it's unplugged
+pub static DISPLAY_PRESENT: AtomicBool = AtomicBool::new(true);
 
 pub struct DisplayController {
     display: DisplayDevice<'static>,
 
-    ble_rx: BleStateRx,
-    gps_rx: GnssStateRx,
+    state_rx: AppStateRx,
+    state: AppState,
+
+    /// Flash the panel on every transmitted report
+    flash_on_tx: bool,
+
+    page: Page,
+
+    /// Updates that failed in a row, reset by a successful one or a recovery attempt
+    consecutive_failures: u32,
+
+    /// Cleared when the panel stops answering; nothing is drawn until it's back
+    is_present: bool,
 
-    is_ble_connected: bool,
-    positioning: Option<GnssPositioning>,
+    /// When the last BLE client went away, or the controller started if none has connected yet
+    disconnected_since: Option<Instant>,
+    brightness: u8,
 
-    last_update: Option<embassy_time::Instant>,
+    /// Idle time before the panel blanks to save power and spare it burn-in; `None` keeps it on
+    sleep_after: Option<Duration>,
+    is_asleep: bool,
+    last_activity: Instant,
 }
 
 impl DisplayController {
-    pub fn new(display: DisplayDevice<'static>, ble_rx: BleStateRx, gps_rx: GnssStateRx) -> Self {
+    pub fn new(
+        display: DisplayDevice<'static>,
+        state_rx: AppStateRx,
+        flash_on_tx: bool,
+        sleep_after: Option<Duration>,
+    ) -> Self {
         Self {
             display,
-            ble_rx,
-            gps_rx,
-            is_ble_connected: false,
-            positioning: None,
-            last_update: None,
+            state_rx,
+            state: AppState::default(),
+            flash_on_tx,
+            page: Page::default(),
+            consecutive_failures: 0,
+            is_present: true,
+            disconnected_since: Some(Instant::now()),
+            brightness: DEFAULT_BRIGHTNESS,
+            sleep_after,
+            is_asleep: false,
+            last_activity: Instant::now(),
         }
     }
 
-    fn update_display(&mut self) -> Result<(), &'static str> {
-        self.display.clear().unwrap();
+    /// Firmware version and commit, device ID and whatever position is already known at boot
+    fn show_splash(&mut self) -> Result<(), DisplayInitError> {
+        self.display.clear()?;
 
-        // BLE status
-        let mut ble_status: String<16> = String::new();
-        write!(
-            &mut ble_status,
-            "[{}] BLE",
-            if self.is_ble_connected { "X" } else { " " }
-        )
-        .unwrap_or_default();
-        self.display.draw_text(&ble_status, Point::zero()).unwrap();
+        let mut version: String<32> = String::new();
+        write!(&mut version, "Nomad v{} {}", FIRMWARE_VERSION, GIT_HASH).unwrap_or_default();
+        self.display.draw_text(&version, Point::zero())?;
+
+        let mut id: String<16> = String::new();
+        write!(&mut id, "ID {:06X}", device_id()).unwrap_or_default();
+        self.display.draw_text(&id, Point::new(0, 12))?;
+
+        // Reading the watch marks the value as seen, so keep it for the status layout too
+        if let Some(state) = self.state_rx.try_get() {
+            self.state = state;
+        }
+
+        match &self.state.positioning {
+            Some(position) => {
+                let precision = CoordinatePrecision::current();
+                let mut latitude: String<32> = String::new();
+                let mut longitude: String<32> = String::new();
+                write!(&mut latitude, "{}", precision.display(position.latitude))
+                    .unwrap_or_default();
+                write!(&mut longitude, "{}", precision.display(position.longitude))
+                    .unwrap_or_default();
+
+                self.display.draw_text("LAST KNOWN", Point::new(0, 28))?;
+                self.display.draw_text(&latitude, Point::new(0, 40))?;
+                self.display.draw_text(&longitude, Point::new(0, 52))?;
+            }
+            None => {
+                self.display
+                    .draw_text("No last known fix", Point::new(0, 28))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draw the current page, trying once more if that fails; a NACK from a glitch on the bus
+    /// usually doesn't repeat
+    fn update_display(&mut self) -> Result<(), DisplayInitError> {
+        self.draw_page().or_else(|e| {
+            defmt::warn!("Display update failed, retrying: {:?}", e);
+            self.draw_page()
+        })
+    }
+
+    fn draw_page(&mut self) -> Result<(), DisplayInitError> {
+        match self.page {
+            Page::Status => self.draw_status(),
+            Page::Coordinates => self.draw_coordinates(),
+            Page::Radio => self.draw_radio(),
+            Page::Satellites => self.draw_satellites(),
+            Page::Navigation => self.draw_navigation(),
+            #[cfg(feature = "diagnostics")]
+            Page::Diagnostics => diagnostics::draw(&mut self.display),
+        }
+    }
+
+    fn draw_status(&mut self) -> Result<(), DisplayInitError> {
+        self.display.clear()?;
+
+        // The tall panel fits five rows under the status bar, the last error at the bottom; the
+        // 32 px one only has room for the two coordinates, spread out to fill it
+        let pitch = match self.display.size() {
+            DisplaySize::Size128x64 => 10,
+            DisplaySize::Size128x32 => 11,
+        };
+
+        // Status bar: Bluetooth while a client is connected, crossed out if the radio failed,
+        // then the satellite glyph with bars for the satellites in use
+        if !self.state.is_ble_available {
+            self.display.draw_icon(Icon::Bluetooth, Point::zero())?;
+            self.display
+                .draw_text("x", Point::new(ICON_SIZE as i32 + 1, 0))?;
+        } else if self.state.is_ble_connected {
+            self.display.draw_icon(Icon::Bluetooth, Point::zero())?;
+        }
+
+        let satellites = self
+            .state
+            .positioning
+            .as_ref()
+            .and_then(|p| p.satellites)
+            .unwrap_or(0);
+        self.display
+            .draw_icon(Icon::Satellite, Point::new(STATUS_BAR_GNSS_X, 0))?;
+        self.display.draw_icon(
+            Icon::Signal(signal_level(satellites)),
+            Point::new(STATUS_BAR_GNSS_X + ICON_SIZE as i32 + 2, 0),
+        )?;
+
+        // Battery level, once there's a measurement
+        if let Some(battery_percent) = self.state.battery_percent {
+            self.display.draw_icon(
+                Icon::Battery(battery_level(battery_percent)),
+                Point::new(STATUS_BAR_BATTERY_X, 0),
+            )?;
+
+            let mut battery_status: String<8> = String::new();
+            write!(&mut battery_status, "{}%", battery_percent).unwrap_or_default();
+            self.display.draw_text(
+                &battery_status,
+                Point::new(STATUS_BAR_BATTERY_X + ICON_SIZE as i32 + 2, 0),
+            )?;
+        }
 
         // GPS status
         let mut gps_status_latitude: String<64> = String::new();
         let mut gps_status_longitude: String<64> = String::new();
-        if let Some(position) = &self.positioning {
-            write!(&mut gps_status_latitude, "{}", position.latitude).unwrap_or_default();
-            write!(&mut gps_status_longitude, "{}", position.longitude).unwrap_or_default();
+        if let Some(position) = &self.state.positioning {
+            let precision = CoordinatePrecision::current();
+            write!(
+                &mut gps_status_latitude,
+                "{}{}",
+                precision.display(position.latitude),
+                stale_marker(position)
+            )
+            .unwrap_or_default();
+            write!(
+                &mut gps_status_longitude,
+                "{}{}",
+                precision.display(position.longitude),
+                stale_marker(position)
+            )
+            .unwrap_or_default();
         } else {
             write!(&mut gps_status_latitude, "No GPS fix").unwrap_or_default();
             write!(&mut gps_status_longitude, "").unwrap_or_default();
         }
         self.display
-            .draw_text(&gps_status_latitude, Point::new(0, 16))
-            .unwrap();
+            .draw_text(&gps_status_latitude, Point::new(0, pitch))?;
 
         self.display
-            .draw_text(&gps_status_longitude, Point::new(0, 32))
-            .unwrap();
+            .draw_text(&gps_status_longitude, Point::new(0, 2 * pitch))?;
 
-        // Additional status info
-        let mut update_time: String<32> = String::new();
-        if let Some(instant) = self.last_update {
-            write!(
-                &mut update_time,
-                "Updated: {}ms ago",
-                instant.elapsed().as_millis()
-            )
+        // Altitude, signed so below-sea-level fixes read correctly
+        if let Some(altitude) = self
+            .state
+            .positioning
+            .as_ref()
+            .and_then(|p| p.altitude_metres())
+        {
+            let mut altitude_status: String<16> = String::new();
+            write!(&mut altitude_status, "ALT {}m", altitude).unwrap_or_default();
+            self.display
+                .draw_text(&altitude_status, Point::new(0, 3 * pitch))?;
+        }
+
+        // How old the fix is, not when the panel was drawn; while docked the position isn't
+        // being reported anyway
+        if self.state.is_docked {
+            self.display.draw_text("DOCKED", Point::new(0, 4 * pitch))?;
+        } else {
+            let mut fix_status: Line = Line::new();
+            match fix_age().map(|age| age.as_secs()) {
+                Some(age) if age < 120 => write!(&mut fix_status, "FIX {}s old", age),
+                Some(age) => write!(&mut fix_status, "FIX {}m old", age / 60),
+                None => write!(&mut fix_status, "FIX none yet"),
+            }
             .unwrap_or_default();
+            if let Some(satellites) = self.state.positioning.as_ref().and_then(|p| p.satellites) {
+                write!(&mut fix_status, ", {} sats", satellites).unwrap_or_default();
+            }
             self.display
-                .draw_text(&update_time, Point::new(0, 48))
-                .unwrap();
+                .draw_text(&fix_status, Point::new(0, 4 * pitch))?;
+        }
+
+        if let Some(error) = last_error() {
+            let mut error_status: String<24> = String::new();
+            write!(&mut error_status, "ERR {}", error.label()).unwrap_or_default();
+            self.display
+                .draw_text(&error_status, Point::new(0, 5 * pitch))?;
         }
 
         Ok(())
     }
 
+    fn draw_coordinates(&mut self) -> Result<(), DisplayInitError> {
+        let mut lines: [Line; 6] = Default::default();
+
+        let Some(position) = &self.state.positioning else {
+            write!(&mut lines[0], "No GPS fix").unwrap_or_default();
+            return draw_lines(&mut self.display, &lines[..1]);
+        };
+
+        let precision = CoordinatePrecision::current();
+        write!(
+            &mut lines[0],
+            "LAT {}{}",
+            precision.display(position.latitude),
+            stale_marker(position)
+        )
+        .unwrap_or_default();
+        write!(
+            &mut lines[1],
+            "LON {}{}",
+            precision.display(position.longitude),
+            stale_marker(position)
+        )
+        .unwrap_or_default();
+        match position.altitude_metres() {
+            Some(altitude) => write!(&mut lines[2], "ALT {}m", altitude),
+            None => write!(&mut lines[2], "ALT --"),
+        }
+        .unwrap_or_default();
+        match position.speed_kmh() {
+            Some(speed) => write!(&mut lines[3], "SPD {:.1}km/h", speed),
+            None => write!(&mut lines[3], "SPD --"),
+        }
+        .unwrap_or_default();
+        match position.heading {
+            Some(heading) => write!(
+                &mut lines[4],
+                "HDG {:.0} {}",
+                heading,
+                heading_to_compass(heading)
+            ),
+            None => write!(&mut lines[4], "HDG --"),
+        }
+        .unwrap_or_default();
+        write!(&mut lines[5], "{}", position.datetime.time()).unwrap_or_default();
+
+        draw_lines(&mut self.display, &lines)
+    }
+
+    fn draw_radio(&mut self) -> Result<(), DisplayInitError> {
+        let radio = RADIO_STATS.lock(|stats| stats.get());
+        let format = ReportFormat::try_from(REPORT_FORMAT.load(Ordering::Relaxed));
+
+        let mut lines: [Line; 4] = Default::default();
+        write!(&mut lines[0], "TX {}", radio.packets_sent).unwrap_or_default();
+        write!(
+            &mut lines[1],
+            "RX {} CRC {}",
+            radio.packets_received, radio.crc_error_count
+        )
+        .unwrap_or_default();
+        match radio.last_rssi {
+            Some(rssi) => write!(&mut lines[2], "RSSI {}dBm", rssi),
+            None => write!(&mut lines[2], "RSSI --"),
+        }
+        .unwrap_or_default();
+        write!(&mut lines[3], "FMT {:?}", format.unwrap_or_default()).unwrap_or_default();
+
+        draw_lines(&mut self.display, &lines)
+    }
+
+    /// Satellites used for the fix, then how many are in view and the strongest of them
+    fn draw_satellites(&mut self) -> Result<(), DisplayInitError> {
+        let mut lines: [Line; 6] = Default::default();
+
+        match self.state.positioning.as_ref().and_then(|p| p.satellites) {
+            Some(satellites) => write!(&mut lines[0], "SATS {}", satellites),
+            None => write!(&mut lines[0], "SATS --"),
+        }
+        .unwrap_or_default();
+
+        // Taken as it is now, the page redraws every second while it's up
+        let Some(mut sky) = GNSS_SATELLITES.try_get() else {
+            write!(&mut lines[1], "VIEW --").unwrap_or_default();
+            return draw_lines(&mut self.display, &lines[..2]);
+        };
+        write!(
+            &mut lines[1],
+            "VIEW {} TRACK {}",
+            sky.in_view(),
+            sky.tracked()
+        )
+        .unwrap_or_default();
+
+        // Untracked satellites, without an SNR, sort last
+        sky.satellites.sort_unstable_by(|a, b| {
+            let snr = |satellite: &SatelliteInfo| satellite.snr.unwrap_or(f32::MIN);
+            snr(b).total_cmp(&snr(a))
+        });
+        let strongest = sky
+            .satellites
+            .iter()
+            .filter_map(|satellite| Some((satellite.prn, satellite.snr?)));
+        for (line, (prn, snr)) in lines[2..].iter_mut().zip(strongest) {
+            write!(line, "PRN {:>3} {:.0}dBHz", prn, snr).unwrap_or_default();
+        }
+
+        draw_lines(&mut self.display, &lines)
+    }
+
+    /// Distance and bearing to the waypoint, with an arrow towards it
+    ///
+    /// The arrow is relative to the direction of travel while moving, and to north otherwise.
+    fn draw_navigation(&mut self) -> Result<(), DisplayInitError> {
+        let mut lines: [Line; 3] = Default::default();
+
+        let Some(position) = &self.state.positioning else {
+            write!(&mut lines[0], "No GPS fix").unwrap_or_default();
+            return draw_lines(&mut self.display, &lines[..1]);
+        };
+        let Some(waypoint) = target() else {
+            write!(&mut lines[0], "No waypoint").unwrap_or_default();
+            return draw_lines(&mut self.display, &lines[..1]);
+        };
+
+        let distance = distance_to(position, waypoint);
+        let bearing = bearing_to(position, waypoint);
+        let (arrow, reference) = match relative_bearing(position, bearing) {
+            Some(relative) => (relative, "CRS up"),
+            None => (bearing, "N up"),
+        };
+
+        let marker = stale_marker(position);
+        if distance < 1_000.0 {
+            write!(&mut lines[0], "DST {:.0}m{}", distance, marker)
+        } else {
+            write!(&mut lines[0], "DST {:.1}km{}", distance / 1_000.0, marker)
+        }
+        .unwrap_or_default();
+        write!(&mut lines[1], "BRG {:.0}", bearing).unwrap_or_default();
+        write!(&mut lines[2], "{}", reference).unwrap_or_default();
+        draw_lines(&mut self.display, &lines)?;
+
+        let radius = (self.display.size().height() / 2 - NAVIGATION_ARROW_MARGIN)
+            .min(NAVIGATION_ARROW_MAX_RADIUS);
+        let center = Point::new(
+            (PANEL_WIDTH - radius - NAVIGATION_ARROW_MARGIN) as i32,
+            (self.display.size().height() / 2) as i32,
+        );
+        self.display.draw_arrow(center, radius, arrow)
+    }
+
+    /// Log a failed update, and try to recover the display after too many in a row
+    ///
+    /// A loose connector makes flushes fail for a while; rather than giving up, the bus and panel
+    /// are reset every `FAILURES_BEFORE_RECOVERY` failures until the display answers again. A
+    /// panel that doesn't answer a probe at all is taken as unplugged instead, see `set_absent`.
+    fn check_result(&mut self, result: Result<(), DisplayInitError>, context: &str) -> bool {
+        let Err(e) = result else {
+            self.consecutive_failures = 0;
+            return true;
+        };
+
+        if !self.display.probe() {
+            self.set_absent();
+            return false;
+        }
+
+        self.consecutive_failures += 1;
+        errorlog::recent::record(ErrorCode::Display);
+        defmt::error!(
+            "Display error {} ({} in a row): {:?}",
+            context,
+            self.consecutive_failures,
+            e
+        );
+
+        if self.consecutive_failures >= FAILURES_BEFORE_RECOVERY {
+            defmt::warn!("Resetting the display bus and panel");
+            self.consecutive_failures = 0;
+
+            match self.display.recover() {
+                Ok(()) => defmt::info!("Display recovered"),
+                Err(e) => defmt::error!("Display recovery failed: {:?}", e),
+            }
+        }
+
+        false
+    }
+
+    /// Stop drawing until a probe finds the panel again, so an unplugged one doesn't flood the log
+    fn set_absent(&mut self) {
+        if self.is_present {
+            defmt::warn!("Display not responding, assuming it was unplugged");
+        }
+
+        self.is_present = false;
+        self.consecutive_failures = 0;
+        DISPLAY_PRESENT.store(false, Ordering::Relaxed);
+    }
+
+    /// Check the panel is still there, and bring it back up once it's plugged in again
+    fn probe(&mut self) {
+        if self.is_present {
+            if !self.display.probe() {
+                self.set_absent();
+            }
+            return;
+        }
+
+        if !self.display.probe() {
+            return;
+        }
+
+        // It lost power while unplugged, so it needs a full reset and init
+        if let Err(e) = self.display.recover() {
+            defmt::warn!("Display answered but failed to initialize: {:?}", e);
+            return;
+        }
+
+        defmt::info!("Display reconnected");
+        self.is_present = true;
+        DISPLAY_PRESENT.store(true, Ordering::Relaxed);
+
+        self.redraw("after reconnecting");
+    }
+
+    /// Draw the current page if the panel is there, `true` if it was drawn
+    fn redraw(&mut self, context: &str) -> bool {
+        if !self.is_present {
+            return false;
+        }
+
+        let update = self.update_display();
+        self.check_result(update, context)
+    }
+
+    /// Invert the panel for `FLASH_DURATION`; short enough not to get in the way of reading it
+    async fn flash(&mut self) -> Result<(), DisplayInitError> {
+        self.display.set_invert(true)?;
+        Timer::after(FLASH_DURATION).await;
+
+        self.display.set_invert(false)
+    }
+
+    /// Dim the panel once no BLE client has been connected for `DIM_AFTER`, brighten it again
+    /// as soon as one connects
+    fn update_brightness(&mut self) {
+        if self.state.is_ble_connected {
+            self.disconnected_since = None;
+        } else if self.disconnected_since.is_none() {
+            self.disconnected_since = Some(Instant::now());
+        }
+
+        let brightness = match self.disconnected_since {
+            Some(since) if since.elapsed() >= DIM_AFTER => DIM_BRIGHTNESS,
+            _ => DEFAULT_BRIGHTNESS,
+        };
+        if brightness == self.brightness || !self.is_present {
+            return;
+        }
+
+        defmt::info!("Display brightness: {}", brightness);
+        let set = self.display.set_brightness(brightness);
+        if self.check_result(set, "setting brightness") {
+            self.brightness = brightness;
+        }
+    }
+
+    /// Note activity, turning the panel back on if it was asleep; `true` if it was
+    fn wake(&mut self) -> bool {
+        self.last_activity = Instant::now();
+        if !self.is_asleep {
+            return false;
+        }
+
+        defmt::info!("Display waking up");
+        self.is_asleep = false;
+
+        // Sent even if the panel is unplugged, so it comes back on when reconnected
+        let on = self.display.set_display_on(true);
+        self.check_result(on, "waking up");
+
+        true
+    }
+
+    /// Blank the panel once nothing has happened for `sleep_after`
+    fn sleep_when_idle(&mut self) {
+        let Some(sleep_after) = self.sleep_after else {
+            return;
+        };
+        if self.last_activity.elapsed() >= sleep_after {
+            self.sleep("idle");
+        }
+    }
+
+    /// Blank the panel until the next `wake`
+    fn sleep(&mut self, reason: &str) {
+        if self.is_asleep || !self.is_present {
+            return;
+        }
+
+        defmt::info!("Display {}, going to sleep", reason);
+        let off = self.display.set_display_on(false);
+        if self.check_result(off, "going to sleep") {
+            self.is_asleep = true;
+        }
+    }
+
+    fn handle_page_request(&mut self, request: PageRequest) {
+        self.page = match request {
+            PageRequest::Next => self.page.next(),
+            PageRequest::Show(page) => page,
+        };
+
+        defmt::info!("Display page: {}", self.page as u8);
+    }
+
     pub async fn run(mut self) {
+        // One-shot splash while the GPS is still cold-starting
+        let splash = self.show_splash();
+        self.check_result(splash, "on splash");
+        Timer::after(SPLASH_DURATION).await;
+
         // Initial display update
-        if let Err(e) = self.update_display() {
-            defmt::error!("Display error on startup: {:?}", e);
-        }
-        self.last_update = Some(embassy_time::Instant::now());
+        let update = self.update_display();
+        self.check_result(update, "on startup");
 
-        // Force update every 30 seconds no matter what
-        const FORCED_UPDATE_INTERVAL: Duration = Duration::from_secs(30);
-        let mut force_update_timer = Timer::after(FORCED_UPDATE_INTERVAL);
+        // Force an update periodically no matter what
+        let mut force_update_timer = Timer::after(self.page.refresh_interval());
+        let mut probe_timer = Timer::after(PROBE_INTERVAL);
+        let watchdog = watchdog::supervisor::register("display", WATCHDOG_TIMEOUT);
 
         loop {
+            watchdog.feed();
+
             let state_change = select(
-                select(self.ble_rx.changed(), self.gps_rx.changed()),
-                &mut force_update_timer,
+                self.state_rx.changed(),
+                select3(
+                    &mut force_update_timer,
+                    PAGE_REQUESTS.receive(),
+                    &mut probe_timer,
+                ),
             );
 
             match state_change.await {
-                // Either BLE or GPS state changed
-                Either::First(either) => {
-                    let mut should_update_display = false;
+                // BLE, GPS, dock or LoRa state changed
+                Either::First(state) => {
+                    let reported = state.reports_confirmed != self.state.reports_confirmed;
+                    // The report count alone doesn't show anywhere, it only triggers the flash
+                    let should_update_display = AppState {
+                        reports_confirmed: self.state.reports_confirmed,
+                        ..state.clone()
+                    } != self.state;
+                    let active = is_activity(&self.state, &state);
+                    self.state = state;
+                    // Waking from power saving leaves the panel off, for activity to turn back on
+                    if self.state.is_power_saving {
+                        self.sleep("power saving");
+                    } else if active {
+                        self.wake();
+                    }
+
+                    if should_update_display && !self.is_asleep && self.redraw("on update") {
+                        // Reset the force update timer after a successful update
+                        force_update_timer = Timer::after(self.page.refresh_interval());
+                    }
+
+                    if reported && self.flash_on_tx && self.is_present && !self.is_asleep {
+                        defmt::debug!(
+                            "Flashing display for report {}",
+                            self.state.reports_confirmed
+                        );
 
+                        let flash = self.flash().await;
+                        self.check_result(flash, "while flashing");
+                    }
+                }
+                // Probe timer elapsed
+                Either::Second(Either3::Third(_)) => {
+                    self.probe();
+                    probe_timer = Timer::after(PROBE_INTERVAL);
+                }
+                // Forced update timer elapsed, or the page was switched
+                Either::Second(either) => {
                     match either {
-                        Either::First(_) => {
-                            // BLE state changed
-                            if let Some(ble_state) = self.ble_rx.try_get() {
-                                if ble_state.connection_status != self.is_ble_connected {
-                                    defmt::info!(
-                                        "BLE connection status changed: {}",
-                                        ble_state.connection_status
-                                    );
-                                    self.is_ble_connected = ble_state.connection_status;
-                                    should_update_display = true;
-                                }
-                            }
-                        }
-                        Either::Second(_) => {
-                            // GPS state changed
-                            if let Some(gps_state) = self.gps_rx.try_get() {
-                                if self.positioning != gps_state {
-                                    defmt::info!(
-                                        "GPS position updated: {:?}",
-                                        defmt::Debug2Format(&gps_state)
-                                    );
-                                    self.positioning = gps_state;
-                                    should_update_display = true;
-                                }
+                        // A press that wakes the panel does only that
+                        Either3::Second(request) => {
+                            if !self.wake() {
+                                self.handle_page_request(request);
                             }
                         }
+                        _ => defmt::debug!("Forced display update timer elapsed"),
                     }
 
-                    if should_update_display {
-                        if let Err(e) = self.update_display() {
-                            defmt::error!("Display update error: {:?}", e);
-                        } else {
-                            self.last_update = Some(embassy_time::Instant::now());
-                            // Reset the force update timer after a successful update
-                            force_update_timer = Timer::after(FORCED_UPDATE_INTERVAL);
-                        }
-                    }
-                }
-                // Forced update timer elapsed
-                Either::Second(_) => {
-                    defmt::debug!("Forced display update timer elapsed");
-                    if let Err(e) = self.update_display() {
-                        defmt::error!("Display update error during forced update: {:?}", e);
-                    } else {
-                        self.last_update = Some(embassy_time::Instant::now());
+                    // The forced update alone doesn't wake a sleeping panel
+                    if !self.is_asleep {
+                        self.redraw("during forced update");
                     }
                     // Restart the force update timer
-                    force_update_timer = Timer::after(FORCED_UPDATE_INTERVAL);
+                    force_update_timer = Timer::after(self.page.refresh_interval());
                 }
             }
 
+            // The probe timer wakes the loop often enough to dim and sleep on time
+            self.update_brightness();
+            self.sleep_when_idle();
+
             // Short delay to prevent excessive CPU usage if many state changes happen
             Timer::after_millis(50).await;
         }
@@ -163,17 +718,22 @@ impl DisplayController {
 }
 
 #[embassy_executor::task]
-pub async fn start(mut display: DisplayDevice<'static>) {
+pub async fn start(
+    mut display: DisplayDevice<'static>,
+    flash_on_tx: bool,
+    sleep_after: Option<Duration>,
+) {
     defmt::info!("Starting display controller");
 
-    match (BLE_STATE.receiver(), GNSS_WATCH.receiver()) {
-        (Some(ble_rx), Some(gps_rx)) => {
-            let display_controller = DisplayController::new(display, ble_rx, gps_rx);
+    match APP_STATE.receiver() {
+        Some(state_rx) => {
+            let display_controller =
+                DisplayController::new(display, state_rx, flash_on_tx, sleep_after);
 
             display_controller.run().await;
         }
-        _ => {
-            defmt::error!("Failed to get BLE or GPS receiver");
+        None => {
+            defmt::error!("Failed to get app state receiver");
 
             if let Ok(()) = display.clear() {
                 let _ = display.draw_text("STATE ERROR", Point::zero());
@@ -183,3 +743,31 @@ pub async fn start(mut display: DisplayDevice<'static>) {
         }
     }
 }
+
+/// Whether going from `old` to `new` is worth waking the panel for: BLE, dock or fix status
+/// changing, rather than a held fix moving on by a second
+fn is_activity(old: &AppState, new: &AppState) -> bool {
+    old.is_ble_connected != new.is_ble_connected
+        || old.is_ble_available != new.is_ble_available
+        || old.is_docked != new.is_docked
+        || old.positioning.is_some() != new.positioning.is_some()
+}
+
+/// Marks values from a fix restored at boot, which may be well out of date
+fn stale_marker(position: &GnssPositioning) -> &'static str {
+    if position.is_stale {
+        "?"
+    } else {
+        ""
+    }
+}
+
+/// Bars for `satellites` in use, full strength from `SATELLITES_PER_BAR * SIGNAL_LEVELS` up
+fn signal_level(satellites: u32) -> u8 {
+    (satellites / SATELLITES_PER_BAR).min(SIGNAL_LEVELS as u32) as u8
+}
+
+/// Columns of the battery icon to fill for `percent`, rounded to the nearest
+fn battery_level(percent: u8) -> u8 {
+    ((percent.min(100) as u32 * BATTERY_LEVELS as u32 + 50) / 100) as u8
+}
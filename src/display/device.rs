@@ -1,84 +1,522 @@
 use embedded_graphics::{
     draw_target::DrawTarget,
+    geometry::{OriginDimensions, Size},
+    image::{Image, ImageRaw},
     mono_font::{iso_8859_1::FONT_6X10, MonoTextStyleBuilder},
     pixelcolor::BinaryColor,
     prelude::Point,
+    primitives::{Line, Primitive, PrimitiveStyle, Rectangle},
     text::{Baseline, Text},
-    Drawable,
+    Drawable, Pixel,
 };
-use esp_hal::{delay::Delay, gpio::Output, i2c::master::I2c, Async};
+use esp_hal::{
+    delay::Delay,
+    gpio::Output,
+    i2c::master::{Config, I2c},
+    Async,
+};
+use libm::{cosf, sinf};
 use ssd1306::{
     mode::{BufferedGraphicsMode, DisplayConfig},
     prelude::*,
-    size::DisplaySize128x64,
+    size::{DisplaySize128x32, DisplaySize128x64},
     I2CDisplayInterface, Ssd1306,
 };
 
-#[derive(Debug)]
+/// Height of a line of `draw_text`, the 6x10 font's
+pub const TEXT_HEIGHT: u32 = 10;
+
+/// Angle in degrees between an arrow's shaft and each barb of its head
+const ARROW_HEAD_ANGLE: f32 = 30.0;
+
+/// Edge length of a checkerboard square in the test pattern
+const TEST_PATTERN_CELL: u32 = 8;
+
+/// How long each static stage of the test pattern stays on screen
+const TEST_PATTERN_HOLD_MS: u32 = 2000;
+
+/// I2C address of the SSD1306 on the Heltec V3
+pub const DISPLAY_ADDRESS: u8 = 0x3C;
+
+/// Contrast the panel starts at, the ssd1306 crate's normal brightness
+pub const DEFAULT_BRIGHTNESS: u8 = 0x5F;
+
+/// Edge length of every icon, one byte per row
+pub const ICON_SIZE: u32 = 8;
+
+/// Bars in the signal icon at full strength
+pub const SIGNAL_LEVELS: u8 = 4;
+
+/// Columns filled in the battery icon when it's full
+pub const BATTERY_LEVELS: u8 = 5;
+
+const BLUETOOTH_ICON: [u8; 8] = [0x10, 0x18, 0x54, 0x38, 0x38, 0x54, 0x18, 0x10];
+
+const SATELLITE_ICON: [u8; 8] = [0xC0, 0xE0, 0x74, 0x38, 0x5C, 0x0E, 0x07, 0x03];
+
+/// All four bars; each is a single column, two pixels taller than the one before
+const SIGNAL_ICON: [u8; 8] = [0x02, 0x02, 0x0A, 0x0A, 0x2A, 0x2A, 0xAA, 0xAA];
+
+/// An empty battery outline with its terminal on the right
+const BATTERY_ICON: [u8; 8] = [0x00, 0xFE, 0x82, 0x83, 0x83, 0x82, 0xFE, 0x00];
+
+/// The columns inside `BATTERY_ICON`'s outline
+const BATTERY_INTERIOR: u8 = 0x7C;
+
+/// Small monochrome glyphs for the status bar, `ICON_SIZE` pixels square
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Icon {
+    Bluetooth,
+    Satellite,
+
+    /// Signal strength from 0 to `SIGNAL_LEVELS` bars; unlit bars keep their bottom pixel
+    Signal(u8),
+
+    /// Charge from 0 to `BATTERY_LEVELS` columns, filled from the left
+    Battery(u8),
+}
+
+impl Icon {
+    fn bitmap(self) -> [u8; 8] {
+        match self {
+            Icon::Bluetooth => BLUETOOTH_ICON,
+            Icon::Satellite => SATELLITE_ICON,
+            Icon::Signal(level) => {
+                // Bars sit two columns apart, so each level uncovers two more columns
+                let lit = 2 * level.min(SIGNAL_LEVELS) as u32;
+                let mask = !0xFFu8.checked_shr(lit).unwrap_or(0);
+
+                let mut bitmap = SIGNAL_ICON;
+                for row in &mut bitmap[..7] {
+                    *row &= mask;
+                }
+
+                bitmap
+            }
+            Icon::Battery(level) => {
+                let fill = BATTERY_INTERIOR & !(BATTERY_INTERIOR >> level.min(BATTERY_LEVELS));
+
+                let mut bitmap = BATTERY_ICON;
+                for row in &mut bitmap[2..6] {
+                    *row |= fill;
+                }
+
+                bitmap
+            }
+        }
+    }
+}
+
+#[derive(Debug, defmt::Format)]
 pub enum DisplayInitError {
     Reset,
     Init,
     Flush,
+    I2cConfig,
+
+    /// Drawing into the frame buffer failed; nothing was sent to the panel
+    Draw,
+}
+
+/// Panel sizes the driver can run, picked by what the board has fitted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, defmt::Format)]
+pub enum DisplaySize {
+    /// The Heltec V3's own panel
+    #[default]
+    Size128x64,
+
+    /// Fitted to some other boards; fits three rows of text
+    Size128x32,
+}
+
+impl DisplaySize {
+    pub const fn height(self) -> u32 {
+        match self {
+            DisplaySize::Size128x64 => 64,
+            DisplaySize::Size128x32 => 32,
+        }
+    }
+}
+
+type Panel128x64<'a> = Ssd1306<
+    I2CInterface<I2c<'a, Async>>,
+    DisplaySize128x64,
+    BufferedGraphicsMode<DisplaySize128x64>,
+>;
+
+type Panel128x32<'a> = Ssd1306<
+    I2CInterface<I2c<'a, Async>>,
+    DisplaySize128x32,
+    BufferedGraphicsMode<DisplaySize128x32>,
+>;
+
+/// The `ssd1306` driver for either panel size, which that crate makes part of the type
+enum Panel<'a> {
+    Size128x64(Panel128x64<'a>),
+    Size128x32(Panel128x32<'a>),
+}
+
+/// Evaluate `$body` with `$inner` bound to the driver inside `$panel`, whatever its size
+macro_rules! with_panel {
+    ($panel:expr, $inner:ident => $body:expr) => {
+        match $panel {
+            Panel::Size128x64($inner) => $body,
+            Panel::Size128x32($inner) => $body,
+        }
+    };
+}
+
+/// What the `ssd1306` driver fails with, the same for either size
+type PanelError = <Panel128x64<'static> as DrawTarget>::Error;
+
+impl Panel<'_> {
+    fn flush(&mut self) -> Result<(), PanelError> {
+        with_panel!(self, panel => panel.flush())
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), PanelError> {
+        with_panel!(self, panel => panel.set_display_on(on))
+    }
+
+    fn set_brightness(&mut self, brightness: Brightness) -> Result<(), PanelError> {
+        with_panel!(self, panel => panel.set_brightness(brightness))
+    }
+
+    fn set_invert(&mut self, invert: bool) -> Result<(), PanelError> {
+        with_panel!(self, panel => panel.set_invert(invert))
+    }
+}
+
+impl DrawTarget for Panel<'_> {
+    type Color = BinaryColor;
+    type Error = PanelError;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        with_panel!(self, panel => panel.draw_iter(pixels))
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        with_panel!(self, panel => panel.clear(color))
+    }
+}
+
+impl OriginDimensions for Panel<'_> {
+    fn size(&self) -> Size {
+        with_panel!(self, panel => panel.size())
+    }
 }
 
 pub struct DisplayDevice<'a> {
-    display: Ssd1306<
-        I2CInterface<I2c<'a, Async>>,
-        DisplaySize128x64,
-        BufferedGraphicsMode<DisplaySize128x64>,
-    >,
-    // oled_rst: Output<'a>,
+    /// Only ever `None` in the middle of `recover`, while the I2C bus is taken out of it
+    panel: Option<Panel<'a>>,
+    oled_rst: Output<'a>,
+
+    /// Reapplied to the I2C controller when recovering
+    i2c_config: Config,
+    size: DisplaySize,
+
+    /// Last level passed to `set_brightness`, reapplied after recovering
+    brightness: u8,
+
+    /// Last state passed to `set_display_on`, reapplied after recovering
+    is_on: bool,
 }
 
 impl<'a> DisplayDevice<'a> {
     /// Create a new Display instance
     pub fn new(
         i2c: I2c<'a, Async>,
+        i2c_config: Config,
         mut oled_rst: Output<'a>,
+        size: DisplaySize,
         delay: &mut Delay,
     ) -> Result<Self, DisplayInitError> {
-        let i2c_display_interface = I2CDisplayInterface::new_custom_address(i2c, 0x3C);
+        let mut panel = Self::panel_on(i2c, size);
+        Self::reset_panel(&mut panel, &mut oled_rst, delay)?;
 
-        let mut display = Ssd1306::new(
-            i2c_display_interface,
-            DisplaySize128x64,
-            DisplayRotation::Rotate0,
-        )
-        .into_buffered_graphics_mode();
+        Ok(Self {
+            panel: Some(panel),
+            oled_rst,
+            i2c_config,
+            size,
+            brightness: DEFAULT_BRIGHTNESS,
+            is_on: true,
+        })
+    }
 
-        display
-            .reset(&mut oled_rst, delay)
-            .map_err(|_| DisplayInitError::Reset)?;
+    fn panel_on(i2c: I2c<'a, Async>, size: DisplaySize) -> Panel<'a> {
+        let i2c_display_interface = I2CDisplayInterface::new_custom_address(i2c, DISPLAY_ADDRESS);
 
-        display.init().map_err(|_| DisplayInitError::Init)?;
+        match size {
+            DisplaySize::Size128x64 => Panel::Size128x64(
+                Ssd1306::new(
+                    i2c_display_interface,
+                    DisplaySize128x64,
+                    DisplayRotation::Rotate0,
+                )
+                .into_buffered_graphics_mode(),
+            ),
+            DisplaySize::Size128x32 => Panel::Size128x32(
+                Ssd1306::new(
+                    i2c_display_interface,
+                    DisplaySize128x32,
+                    DisplayRotation::Rotate0,
+                )
+                .into_buffered_graphics_mode(),
+            ),
+        }
+    }
 
-        Ok(Self {
-            display, /*, oled_rst */
+    fn reset_panel(
+        panel: &mut Panel<'a>,
+        oled_rst: &mut Output<'a>,
+        delay: &mut Delay,
+    ) -> Result<(), DisplayInitError> {
+        with_panel!(panel, panel => {
+            panel
+                .reset(oled_rst, delay)
+                .map_err(|_| DisplayInitError::Reset)?;
+
+            panel.init().map_err(|_| DisplayInitError::Init)
         })
     }
 
+    fn panel(&mut self) -> &mut Panel<'a> {
+        self.panel
+            .as_mut()
+            .expect("display panel taken outside recover")
+    }
+
+    /// Reset the I2C controller and the panel, then initialize the panel again
+    ///
+    /// For when flushes keep failing, e.g. after a loose connector glitched the bus: pulsing the
+    /// panel's reset line also makes it let go of SDA if it was stuck mid-transfer. Whatever was
+    /// on screen is lost.
+    pub fn recover(&mut self) -> Result<(), DisplayInitError> {
+        let panel = self
+            .panel
+            .take()
+            .expect("display panel taken outside recover");
+        let mut i2c = with_panel!(panel, panel => panel.release().release());
+        let reconfigured = i2c.apply_config(&self.i2c_config);
+
+        let mut panel = Self::panel_on(i2c, self.size);
+        let reset = Self::reset_panel(&mut panel, &mut self.oled_rst, &mut Delay::new());
+        self.panel = Some(panel);
+
+        reconfigured.map_err(|_| DisplayInitError::I2cConfig)?;
+        reset?;
+
+        self.set_brightness(self.brightness)?;
+        self.set_display_on(self.is_on)
+    }
+
+    pub fn size(&self) -> DisplaySize {
+        self.size
+    }
+
+    /// Whether the panel still acknowledges on the bus, checked by sending it a harmless command
+    ///
+    /// The frame buffer isn't touched, so this is cheap enough to run while nothing is drawn. The
+    /// command repeats the current on/off state, so probing doesn't wake a sleeping panel.
+    pub fn probe(&mut self) -> bool {
+        let is_on = self.is_on;
+        self.panel().set_display_on(is_on).is_ok()
+    }
+
+    /// Turn the panel on, or blank it while keeping the frame buffer
+    ///
+    /// The state is kept even if the command fails, so a recovered panel comes back the same way.
+    pub fn set_display_on(&mut self, on: bool) -> Result<(), DisplayInitError> {
+        self.is_on = on;
+
+        self.panel()
+            .set_display_on(on)
+            .map_err(|_| DisplayInitError::Flush)
+    }
+
     /// Clear the display
     pub fn clear(&mut self) -> Result<(), DisplayInitError> {
-        self.display.clear(BinaryColor::Off).unwrap();
-        self.display.flush().map_err(|_| DisplayInitError::Flush)?;
+        self.panel()
+            .clear(BinaryColor::Off)
+            .map_err(|_| DisplayInitError::Draw)?;
+        self.panel().flush().map_err(|_| DisplayInitError::Flush)?;
 
         Ok(())
     }
 
+    /// Draw `text` with its top left corner at `position`
+    ///
+    /// Text that would run off the bottom of the panel isn't drawn at all, so layouts written for
+    /// the taller panel lose their last rows on the shorter one rather than showing them cut off.
     pub fn draw_text(&mut self, text: &str, position: Point) -> Result<(), DisplayInitError> {
+        if position.y + TEXT_HEIGHT as i32 > self.size.height() as i32 {
+            defmt::debug!("Not drawing off-screen text: {}", text);
+            return Ok(());
+        }
+
         let text_style = MonoTextStyleBuilder::new()
             .font(&FONT_6X10)
             .text_color(BinaryColor::On)
             .build();
 
         Text::with_baseline(text, position, text_style, Baseline::Top)
-            .draw(&mut self.display)
-            .unwrap();
+            .draw(self.panel())
+            .map_err(|_| DisplayInitError::Draw)?;
 
         defmt::info!("Drawing: {}", text);
 
-        self.display.flush().unwrap();
+        self.panel().flush().map_err(|_| DisplayInitError::Flush)
+    }
+
+    /// Draw `icon` with its top left corner at `position`
+    pub fn draw_icon(&mut self, icon: Icon, position: Point) -> Result<(), DisplayInitError> {
+        let bitmap = icon.bitmap();
+        let raw = ImageRaw::<BinaryColor>::new(&bitmap, ICON_SIZE);
+
+        Image::new(&raw, position)
+            .draw(self.panel())
+            .map_err(|_| DisplayInitError::Draw)?;
+
+        self.panel().flush().map_err(|_| DisplayInitError::Flush)
+    }
+
+    /// Draw an arrow `radius` px either side of `center`, pointing `degrees` clockwise from up
+    pub fn draw_arrow(
+        &mut self,
+        center: Point,
+        radius: u32,
+        degrees: f32,
+    ) -> Result<(), DisplayInitError> {
+        let style = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+        let point_at = |degrees: f32, length: f32| {
+            let radians = degrees.to_radians();
+            center
+                + Point::new(
+                    (sinf(radians) * length) as i32,
+                    (-cosf(radians) * length) as i32,
+                )
+        };
+
+        let tip = point_at(degrees, radius as f32);
+        let shaft = Line::new(point_at(degrees + 180.0, radius as f32), tip);
+        shaft
+            .into_styled(style)
+            .draw(self.panel())
+            .map_err(|_| DisplayInitError::Draw)?;
+
+        // The head's barbs, half the radius long, swept back from the tip
+        for side in [-ARROW_HEAD_ANGLE, ARROW_HEAD_ANGLE] {
+            let barb = point_at(degrees + 180.0 + side, radius as f32 / 2.0) - center;
+            Line::new(tip, tip + barb)
+                .into_styled(style)
+                .draw(self.panel())
+                .map_err(|_| DisplayInitError::Draw)?;
+        }
+
+        self.panel().flush().map_err(|_| DisplayInitError::Flush)
+    }
+
+    /// Set the panel contrast, 0 dimmest to 255 brightest
+    ///
+    /// The lowest level also shortens the pre-charge period, dimming the panel further than
+    /// contrast alone; the same as the ssd1306 crate's `Brightness::DIMMEST`.
+    pub fn set_brightness(&mut self, level: u8) -> Result<(), DisplayInitError> {
+        let precharge = if level == 0 { 1 } else { 2 };
+        self.panel()
+            .set_brightness(Brightness::custom(precharge, level))
+            .map_err(|_| DisplayInitError::Flush)?;
+
+        self.brightness = level;
 
         Ok(())
     }
+
+    /// Swap lit and dark pixels on the whole panel, leaving the frame buffer as it is
+    pub fn set_invert(&mut self, invert: bool) -> Result<(), DisplayInitError> {
+        self.panel()
+            .set_invert(invert)
+            .map_err(|_| DisplayInitError::Flush)
+    }
+
+    /// Cycle through a panel test pattern for QA of newly assembled boards
+    ///
+    /// Shows a checkerboard and its inverse (every pixel is lit and dark at least once, stuck
+    /// pixels stand out), then nested borders (row/column addressing faults show as broken or
+    /// shifted edges), then sweeps a bar across and down the panel. Blocks for several seconds.
+    pub fn test_pattern(&mut self) -> Result<(), DisplayInitError> {
+        let delay = Delay::new();
+        let Size { width, height } = self.panel().size();
+
+        // Checkerboard, then its inverse
+        for phase in 0..2 {
+            self.panel()
+                .clear(BinaryColor::Off)
+                .map_err(|_| DisplayInitError::Draw)?;
+
+            for row in 0..height / TEST_PATTERN_CELL {
+                for column in 0..width / TEST_PATTERN_CELL {
+                    if (row + column) % 2 == phase {
+                        self.fill_rect(
+                            Point::new(
+                                (column * TEST_PATTERN_CELL) as i32,
+                                (row * TEST_PATTERN_CELL) as i32,
+                            ),
+                            Size::new(TEST_PATTERN_CELL, TEST_PATTERN_CELL),
+                        )?;
+                    }
+                }
+            }
+
+            self.panel().flush().map_err(|_| DisplayInitError::Flush)?;
+            delay.delay_millis(TEST_PATTERN_HOLD_MS);
+        }
+
+        // Nested borders, four pixels apart
+        self.panel()
+            .clear(BinaryColor::Off)
+            .map_err(|_| DisplayInitError::Draw)?;
+        for inset in (0..height / 2).step_by(4) {
+            Rectangle::new(
+                Point::new(inset as i32, inset as i32),
+                Size::new(width - 2 * inset, height - 2 * inset),
+            )
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(self.panel())
+            .map_err(|_| DisplayInitError::Draw)?;
+        }
+        self.panel().flush().map_err(|_| DisplayInitError::Flush)?;
+        delay.delay_millis(TEST_PATTERN_HOLD_MS);
+
+        // Sweep a vertical bar left to right, then a horizontal bar top to bottom
+        for x in (0..width).step_by(TEST_PATTERN_CELL as usize) {
+            self.panel()
+                .clear(BinaryColor::Off)
+                .map_err(|_| DisplayInitError::Draw)?;
+            self.fill_rect(
+                Point::new(x as i32, 0),
+                Size::new(TEST_PATTERN_CELL, height),
+            )?;
+            self.panel().flush().map_err(|_| DisplayInitError::Flush)?;
+        }
+        for y in (0..height).step_by(TEST_PATTERN_CELL as usize) {
+            self.panel()
+                .clear(BinaryColor::Off)
+                .map_err(|_| DisplayInitError::Draw)?;
+            self.fill_rect(Point::new(0, y as i32), Size::new(width, TEST_PATTERN_CELL))?;
+            self.panel().flush().map_err(|_| DisplayInitError::Flush)?;
+        }
+
+        self.clear()
+    }
+
+    fn fill_rect(&mut self, top_left: Point, size: Size) -> Result<(), DisplayInitError> {
+        Rectangle::new(top_left, size)
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(self.panel())
+            .map_err(|_| DisplayInitError::Draw)
+    }
 }
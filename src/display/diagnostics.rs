@@ -0,0 +1,62 @@
+use crate::{
+    ble::state::BLE_STATE,
+    gnss::watch::{DROPPED_SENTENCE_COUNT, UART_OVERFLOW_COUNT},
+    lora::driver::RADIO_STATS,
+};
+use core::fmt::Write;
+use core::sync::atomic::Ordering;
+use embassy_time::Instant;
+
+use super::page::{draw_lines, Line};
+use super::{DisplayDevice, DisplayInitError};
+
+/// Raw debug counters on a single page, for photographing when filing issues
+pub fn draw(display: &mut DisplayDevice<'_>) -> Result<(), DisplayInitError> {
+    let radio = RADIO_STATS.lock(|stats| stats.get());
+
+    let mut lines: [Line; 6] = Default::default();
+    write!(
+        &mut lines[0],
+        "TX {} RX {}",
+        radio.packets_sent, radio.packets_received
+    )
+    .unwrap_or_default();
+    match radio.crc_error_rate() {
+        Some(rate) => write!(
+            &mut lines[1],
+            "CRC {} ({:.1}%)",
+            radio.crc_error_count,
+            rate * 100.0
+        ),
+        None => write!(&mut lines[1], "CRC {}", radio.crc_error_count),
+    }
+    .unwrap_or_default();
+    write!(&mut lines[2], "RX ERR {}", radio.other_error_count).unwrap_or_default();
+    write!(
+        &mut lines[3],
+        "UART OVF {} DROP {}",
+        UART_OVERFLOW_COUNT.load(Ordering::Relaxed),
+        DROPPED_SENTENCE_COUNT.load(Ordering::Relaxed)
+    )
+    .unwrap_or_default();
+    write!(
+        &mut lines[4],
+        "HEAP {} UP {}s",
+        esp_alloc::HEAP.free(),
+        Instant::now().as_secs()
+    )
+    .unwrap_or_default();
+    let ble = BLE_STATE.try_get().unwrap_or_default();
+    match ble.connected_for() {
+        Some(uptime) => write!(
+            &mut lines[5],
+            "BLE {}s DROPS {}",
+            uptime.as_secs(),
+            ble.disconnect_count
+        ),
+        None => write!(&mut lines[5], "BLE -- DROPS {}", ble.disconnect_count),
+    }
+    .unwrap_or_default();
+
+    draw_lines(display, &lines)
+}
@@ -1,4 +1,10 @@
-pub use self::device::DisplayDevice;
+pub use self::device::{
+    DisplayDevice, DisplayInitError, DisplaySize, Icon, BATTERY_LEVELS, DEFAULT_BRIGHTNESS,
+    DISPLAY_ADDRESS, ICON_SIZE, SIGNAL_LEVELS,
+};
 
 pub mod controller;
 mod device;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+pub mod page;
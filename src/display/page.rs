@@ -0,0 +1,123 @@
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use embassy_time::{Duration, Timer};
+use embedded_graphics::prelude::Point;
+use esp_hal::gpio::Input;
+use heapless::String;
+
+use super::{DisplayDevice, DisplayInitError};
+
+/// Rows of the 6x10 font fit six to a 64 px panel
+const LINE_HEIGHT: i32 = 10;
+
+/// Characters per line, with a little slack over the 21 that fit the panel width
+pub const LINE_LENGTH: usize = 24;
+
+pub type Line = String<LINE_LENGTH>;
+
+/// Page requests not yet picked up by the display
+const PAGE_REQUEST_QUEUE_SIZE: usize = 4;
+
+/// What the display is showing; the discriminant is the index used over BLE
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Page {
+    /// BLE link, position and fix age
+    #[default]
+    Status = 0,
+
+    /// Position in full: coordinates, altitude, speed and heading
+    Coordinates = 1,
+
+    /// LoRa counters and signal strength
+    Radio = 2,
+
+    /// Satellites in use, in view and the strongest signals
+    Satellites = 3,
+
+    /// Distance and an arrow to the waypoint set over BLE
+    Navigation = 4,
+
+    /// Raw debug counters, only in builds with the `diagnostics` feature
+    #[cfg(feature = "diagnostics")]
+    Diagnostics = 5,
+}
+
+impl TryFrom<u8> for Page {
+    type Error = u8;
+
+    fn try_from(index: u8) -> Result<Self, Self::Error> {
+        match index {
+            0 => Ok(Page::Status),
+            1 => Ok(Page::Coordinates),
+            2 => Ok(Page::Radio),
+            3 => Ok(Page::Satellites),
+            4 => Ok(Page::Navigation),
+            #[cfg(feature = "diagnostics")]
+            5 => Ok(Page::Diagnostics),
+            _ => Err(index),
+        }
+    }
+}
+
+impl Page {
+    /// The page after this one, wrapping around to the first
+    pub fn next(self) -> Self {
+        Page::try_from(self as u8 + 1).unwrap_or_default()
+    }
+
+    /// How often to redraw without a state change
+    ///
+    /// Pages showing counters that move on their own refresh every second, and the status page
+    /// every few so that the fix age keeps counting once fixes stop arriving; the rest only need
+    /// the occasional redraw.
+    pub fn refresh_interval(self) -> Duration {
+        match self {
+            Page::Radio | Page::Satellites => Duration::from_secs(1),
+            Page::Status => Duration::from_secs(5),
+            #[cfg(feature = "diagnostics")]
+            Page::Diagnostics => Duration::from_secs(1),
+            _ => Duration::from_secs(30),
+        }
+    }
+}
+
+/// A request to change the page, from any input source
+#[derive(Debug, Clone, Copy)]
+pub enum PageRequest {
+    /// Move on to the next page (the USER button)
+    Next,
+
+    /// Jump straight to a page (BLE)
+    Show(Page),
+}
+
+/// Page requests for `DisplayController`, fed by the USER button and BLE alike
+pub static PAGE_REQUESTS: Channel<CriticalSectionRawMutex, PageRequest, PAGE_REQUEST_QUEUE_SIZE> =
+    Channel::new();
+
+/// Clear the display and draw `lines` top to bottom
+pub fn draw_lines(display: &mut DisplayDevice<'_>, lines: &[Line]) -> Result<(), DisplayInitError> {
+    display.clear()?;
+
+    for (row, line) in lines.iter().enumerate() {
+        display.draw_text(line, Point::new(0, row as i32 * LINE_HEIGHT))?;
+    }
+
+    Ok(())
+}
+
+/// Step through the pages on every press of `button`
+#[embassy_executor::task]
+pub async fn cycle_on_button(mut button: Input<'static>) {
+    loop {
+        button.wait_for_falling_edge().await;
+
+        if PAGE_REQUESTS.try_send(PageRequest::Next).is_err() {
+            defmt::debug!("Page request queue full, ignoring button press");
+        }
+
+        // Debounce
+        Timer::after_millis(50).await;
+        button.wait_for_high().await;
+    }
+}
@@ -0,0 +1,82 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::watch::{Receiver, Watch};
+use embassy_time::{Duration, Timer};
+use esp_hal::gpio::Input;
+
+/// Tasks that wait on dock mode changes: the app state aggregator
+const DOCK_RECEIVERS: usize = 1;
+
+/// How long the power-detect line has to settle after an edge before it's read
+const POWER_DETECT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Whether the device is docked; position broadcasts pause and the GNSS task slows down while it is
+///
+/// Tasks that only need the current mode poll it with `is_docked`; `app_state::aggregate` waits
+/// on changes.
+pub static DOCKED: Watch<CriticalSectionRawMutex, bool, DOCK_RECEIVERS> = Watch::new();
+
+pub type DockedRx = Receiver<'static, CriticalSectionRawMutex, bool, DOCK_RECEIVERS>;
+
+/// Whether writes to the BLE `dock_mode` characteristic are honoured, see `Config::ble`
+static BLE_TRIGGER: AtomicBool = AtomicBool::new(false);
+
+/// Which sources may put the device into dock mode, each enabled separately
+pub struct Config {
+    /// Follow a power-detect line on GPIO48, high while USB power is present; the stock board
+    /// doesn't route VBUS to a GPIO, so this needs a divider from VBUS
+    pub power_detect: bool,
+
+    /// Follow writes to the BLE `dock_mode` characteristic
+    pub ble: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            power_detect: false,
+            ble: true,
+        }
+    }
+}
+
+impl Config {
+    /// Apply the settings that aren't tied to a task; call once before spawning anything
+    pub fn apply(&self) {
+        BLE_TRIGGER.store(self.ble, Ordering::Relaxed);
+    }
+}
+
+/// Current mode; undocked until a trigger says otherwise
+pub fn is_docked() -> bool {
+    DOCKED.try_get().unwrap_or(false)
+}
+
+fn set_docked(docked: bool) {
+    if is_docked() != docked {
+        defmt::info!("Dock mode: {}", docked);
+        DOCKED.sender().send(docked);
+    }
+}
+
+/// Enter or leave dock mode as requested over BLE; `false` if that trigger is disabled
+pub fn set_docked_from_ble(docked: bool) -> bool {
+    if !BLE_TRIGGER.load(Ordering::Relaxed) {
+        return false;
+    }
+
+    set_docked(docked);
+
+    true
+}
+
+/// Follow the power-detect line: docked while it's high, undocked as soon as it drops
+#[embassy_executor::task]
+pub async fn power_detect(mut pin: Input<'static>) {
+    loop {
+        set_docked(pin.is_high());
+
+        pin.wait_for_any_edge().await;
+        Timer::after(POWER_DETECT_DEBOUNCE).await;
+    }
+}
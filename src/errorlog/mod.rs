@@ -0,0 +1,102 @@
+use defmt::Format;
+
+// ESP32-specific modules
+#[cfg(feature = "esp32")]
+pub mod recent;
+
+/// Error codes kept, as many as fit the BLE `error_log` characteristic
+pub const ERROR_LOG_SIZE: usize = 7;
+
+/// What went wrong; the discriminant is the code sent over BLE, where 0 means no error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Format)]
+#[repr(u8)]
+pub enum ErrorCode {
+    /// The LoRa radio failed to transmit, receive or get ready to
+    LoraRadio = 1,
+
+    /// The GNSS UART reported an error, e.g. its FIFO overflowed
+    GnssUart = 2,
+
+    /// Drawing to or flushing the display failed
+    Display = 3,
+}
+
+impl ErrorCode {
+    /// Short enough for a line on the display
+    pub const fn label(self) -> &'static str {
+        match self {
+            ErrorCode::LoraRadio => "LoRa radio",
+            ErrorCode::GnssUart => "GNSS UART",
+            ErrorCode::Display => "display",
+        }
+    }
+}
+
+/// The most recent errors, the newest first; older ones are dropped once it's full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ErrorRing {
+    codes: [Option<ErrorCode>; ERROR_LOG_SIZE],
+
+    /// Errors recorded since boot, dropped ones included
+    recorded: u32,
+}
+
+impl ErrorRing {
+    pub const fn new() -> Self {
+        Self {
+            codes: [None; ERROR_LOG_SIZE],
+            recorded: 0,
+        }
+    }
+
+    pub fn record(&mut self, code: ErrorCode) {
+        self.codes.copy_within(..ERROR_LOG_SIZE - 1, 1);
+        self.codes[0] = Some(code);
+        self.recorded = self.recorded.wrapping_add(1);
+    }
+
+    pub fn last(&self) -> Option<ErrorCode> {
+        self.codes[0]
+    }
+
+    pub fn recorded(&self) -> u32 {
+        self.recorded
+    }
+
+    /// The codes, the newest first, padded with zeros while there are fewer than
+    /// `ERROR_LOG_SIZE`
+    pub fn to_bytes(self) -> [u8; ERROR_LOG_SIZE] {
+        self.codes.map(|code| code.map_or(0, |code| code as u8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_newest_error_first() {
+        let mut ring = ErrorRing::new();
+        assert_eq!(ring.last(), None);
+        assert_eq!(ring.to_bytes(), [0; ERROR_LOG_SIZE]);
+
+        ring.record(ErrorCode::GnssUart);
+        ring.record(ErrorCode::LoraRadio);
+
+        assert_eq!(ring.last(), Some(ErrorCode::LoraRadio));
+        assert_eq!(ring.to_bytes(), [1, 2, 0, 0, 0, 0, 0]);
+        assert_eq!(ring.recorded(), 2);
+    }
+
+    #[test]
+    fn test_oldest_error_dropped_when_full() {
+        let mut ring = ErrorRing::new();
+        ring.record(ErrorCode::Display);
+        for _ in 0..ERROR_LOG_SIZE {
+            ring.record(ErrorCode::LoraRadio);
+        }
+
+        assert_eq!(ring.to_bytes(), [1; ERROR_LOG_SIZE]);
+        assert_eq!(ring.recorded(), ERROR_LOG_SIZE as u32 + 1);
+    }
+}
@@ -0,0 +1,29 @@
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+
+use super::{ErrorCode, ErrorRing};
+
+/// Errors recorded by any task since boot
+static RECENT_ERRORS: BlockingMutex<CriticalSectionRawMutex, Cell<ErrorRing>> =
+    BlockingMutex::new(Cell::new(ErrorRing::new()));
+
+/// Keep `code` among the recent errors shown on the display and over BLE
+///
+/// Only for errors worth a user's attention; routine ones, like a frame with a bad CRC at the
+/// edge of range, are only logged.
+pub fn record(code: ErrorCode) {
+    RECENT_ERRORS.lock(|cell| {
+        let mut errors = cell.get();
+        errors.record(code);
+        cell.set(errors);
+    });
+}
+
+pub fn recent_errors() -> ErrorRing {
+    RECENT_ERRORS.lock(Cell::get)
+}
+
+pub fn last_error() -> Option<ErrorCode> {
+    recent_errors().last()
+}
@@ -0,0 +1,573 @@
+use chrono::DateTime;
+use heapless::Vec;
+
+use crate::gnss::positioning::GnssPositioning;
+use crate::lora::packet::{decode_coordinate, encode_coordinate, round};
+
+// ESP32-specific modules
+#[cfg(feature = "esp32")]
+pub mod storage;
+
+/// Bytes per record, and per sector header
+pub const RECORD_SIZE: usize = 16;
+
+/// Flash program page; records are buffered and written a page at a time
+pub const PAGE_SIZE: usize = 256;
+
+/// Records buffered in RAM before they're written out
+pub const RECORDS_PER_BATCH: usize = PAGE_SIZE / RECORD_SIZE;
+
+/// Marks a sector that belongs to the log, "NMLG"
+const SECTOR_MAGIC: u32 = 0x4E4D_4C47;
+
+/// Altitude field of a record that had none
+const ALTITUDE_UNKNOWN: i16 = i16::MIN;
+
+/// Satellite count field of a record that had none
+const SATELLITES_UNKNOWN: u8 = u8::MAX;
+
+/// A reserved region of NOR flash, addressed from its start
+///
+/// Erased bytes read back as `0xFF`, and writes can only clear bits, so a byte can't be written
+/// twice without erasing the whole sector it's in first.
+pub trait Flash {
+    type Error;
+
+    /// Erase granularity in bytes; a multiple of `PAGE_SIZE`
+    const SECTOR_SIZE: u32;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error>;
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    fn erase(&mut self, sector: u32) -> Result<(), Self::Error>;
+}
+
+impl<T: Flash> Flash for &mut T {
+    type Error = T::Error;
+
+    const SECTOR_SIZE: u32 = T::SECTOR_SIZE;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        (**self).read(offset, bytes)
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        (**self).write(offset, bytes)
+    }
+
+    fn erase(&mut self, sector: u32) -> Result<(), Self::Error> {
+        (**self).erase(sector)
+    }
+}
+
+/// One breadcrumb; coordinates are kept to the 1e-7 degree of the on-air reports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogRecord {
+    /// Fix time, Unix seconds
+    pub timestamp: u32,
+    pub latitude: f64,
+    pub longitude: f64,
+
+    /// Metres above mean sea level
+    pub altitude: Option<i16>,
+
+    pub satellites: Option<u8>,
+}
+
+impl From<&GnssPositioning> for LogRecord {
+    fn from(positioning: &GnssPositioning) -> Self {
+        Self {
+            timestamp: u32::try_from(positioning.datetime.and_utc().timestamp())
+                .unwrap_or_default(),
+            latitude: positioning.latitude,
+            longitude: positioning.longitude,
+            altitude: positioning.altitude.map(|altitude| {
+                round(altitude as f64).clamp(ALTITUDE_UNKNOWN as f64 + 1.0, i16::MAX as f64) as i16
+            }),
+            satellites: positioning
+                .satellites
+                .map(|satellites| satellites.min(SATELLITES_UNKNOWN as u32 - 1) as u8),
+        }
+    }
+}
+
+/// A logged position as a fix, flagged `is_stale`; all a record lacks is left `None`
+impl From<&LogRecord> for GnssPositioning {
+    fn from(record: &LogRecord) -> Self {
+        Self {
+            datetime: DateTime::from_timestamp(record.timestamp as i64, 0)
+                .unwrap_or_default()
+                .naive_utc(),
+            latitude: record.latitude,
+            longitude: record.longitude,
+            speed: None,
+            heading: None,
+            altitude: record.altitude.map(f32::from),
+            satellites: record.satellites.map(u32::from),
+            fix_quality: None,
+            is_stale: true,
+        }
+    }
+}
+
+impl LogRecord {
+    /// Little-endian timestamp, latitude and longitude (`i32`, 1e-7 degree), altitude (`i16`),
+    /// satellites, then a checksum that catches records torn by a reset mid-write
+    fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0u8; RECORD_SIZE];
+
+        bytes[0..4].copy_from_slice(&self.timestamp.to_le_bytes());
+        bytes[4..8].copy_from_slice(&encode_coordinate(self.latitude));
+        bytes[8..12].copy_from_slice(&encode_coordinate(self.longitude));
+        bytes[12..14].copy_from_slice(&self.altitude.unwrap_or(ALTITUDE_UNKNOWN).to_le_bytes());
+        bytes[14] = self.satellites.unwrap_or(SATELLITES_UNKNOWN);
+        bytes[15] = checksum(&bytes[..15]);
+
+        bytes
+    }
+
+    fn decode(bytes: &[u8; RECORD_SIZE]) -> Option<Self> {
+        if bytes[15] != checksum(&bytes[..15]) {
+            return None;
+        }
+
+        let altitude = i16::from_le_bytes([bytes[12], bytes[13]]);
+
+        Some(Self {
+            timestamp: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            latitude: decode_coordinate([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            longitude: decode_coordinate([bytes[8], bytes[9], bytes[10], bytes[11]]),
+            altitude: (altitude != ALTITUDE_UNKNOWN).then_some(altitude),
+            satellites: (bytes[14] != SATELLITES_UNKNOWN).then_some(bytes[14]),
+        })
+    }
+}
+
+/// Inverted byte sum, so an all-`0xFF` erased slot never passes for a record
+fn checksum(bytes: &[u8]) -> u8 {
+    !bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+fn is_erased(bytes: &[u8]) -> bool {
+    bytes.iter().all(|&byte| byte == 0xFF)
+}
+
+/// Position records in a circular log across the sectors of a `Flash` region
+///
+/// Each sector starts with a header slot holding `SECTOR_MAGIC` and a sequence number that grows
+/// by one each time a sector is (re)started, followed by records. When the current sector fills
+/// up, the next one is erased and the log carries on there, so the oldest sector's worth of
+/// records is dropped and every sector is erased once per lap of the region.
+///
+/// Records are buffered and written `RECORDS_PER_BATCH` at a time, so up to a batch is lost on
+/// reset unless `flush` is called first.
+pub struct FlashLog<F: Flash> {
+    flash: F,
+    sectors: u32,
+
+    /// Sector being written, and its sequence number
+    sector: u32,
+    sequence: u32,
+
+    /// Next free slot in `sector`; slot 0 is the header
+    next_slot: u32,
+
+    pending: Vec<[u8; RECORD_SIZE], RECORDS_PER_BATCH>,
+}
+
+impl<F: Flash> FlashLog<F> {
+    /// Pick the log up where it left off in `sectors` sectors of `flash`, or start a new one
+    pub fn open(mut flash: F, sectors: u32) -> Result<Self, F::Error> {
+        let mut newest: Option<(u32, u32)> = None;
+        for sector in 0..sectors {
+            if let Some(sequence) = Self::read_header(&mut flash, sector)? {
+                if newest.is_none_or(|(_, newest)| sequence > newest) {
+                    newest = Some((sector, sequence));
+                }
+            }
+        }
+
+        let mut log = Self {
+            flash,
+            sectors,
+            sector: 0,
+            sequence: 0,
+            next_slot: 1,
+            pending: Vec::new(),
+        };
+
+        match newest {
+            Some((sector, sequence)) => {
+                log.sector = sector;
+                log.sequence = sequence;
+
+                // Past any torn record too; the slot can't be rewritten without an erase
+                let mut slot = [0u8; RECORD_SIZE];
+                while log.next_slot < log.slots_per_sector() {
+                    let offset = log.slot_offset(sector, log.next_slot);
+                    log.flash.read(offset, &mut slot)?;
+                    if is_erased(&slot) {
+                        break;
+                    }
+                    log.next_slot += 1;
+                }
+            }
+            None => log.start_sector(0, 1)?,
+        }
+
+        Ok(log)
+    }
+
+    /// Buffer a record, writing the batch out once it's full or reaches the end of the sector
+    pub fn append(&mut self, record: &LogRecord) -> Result<(), F::Error> {
+        if self.pending.is_empty() && self.next_slot >= self.slots_per_sector() {
+            self.start_sector((self.sector + 1) % self.sectors, self.sequence + 1)?;
+        }
+
+        // Flushed below as soon as it fills, so there's always room
+        let _ = self.pending.push(record.encode());
+
+        let sector_full = self.next_slot + self.pending.len() as u32 >= self.slots_per_sector();
+        if self.pending.is_full() || sector_full {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Write out whatever is buffered, even if it's less than a batch
+    pub fn flush(&mut self) -> Result<(), F::Error> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut page = [0u8; PAGE_SIZE];
+        for (chunk, record) in page.chunks_exact_mut(RECORD_SIZE).zip(&self.pending) {
+            chunk.copy_from_slice(record);
+        }
+
+        let len = self.pending.len() * RECORD_SIZE;
+        let offset = self.slot_offset(self.sector, self.next_slot);
+        self.flash.write(offset, &page[..len])?;
+
+        self.next_slot += self.pending.len() as u32;
+        self.pending.clear();
+
+        Ok(())
+    }
+
+    /// Every record in the log, oldest first, after flushing anything still buffered
+    ///
+    /// Records that fail their checksum, left by a reset in the middle of a write, are skipped.
+    pub fn records(&mut self) -> Result<Records<'_, F>, F::Error> {
+        self.flush()?;
+
+        // The sector after the current one is the oldest, once the log has wrapped around
+        let first = (self.sector + 1) % self.sectors;
+
+        Ok(Records {
+            log: self,
+            visited: 0,
+            sector: first,
+            slot: 0,
+        })
+    }
+
+    /// The newest record in the log, after flushing anything still buffered
+    ///
+    /// Looks back from the end of the current sector, then through the one before it if the
+    /// current one has no records yet.
+    pub fn last_record(&mut self) -> Result<Option<LogRecord>, F::Error> {
+        self.flush()?;
+
+        let previous = (self.sector + self.sectors - 1) % self.sectors;
+        let mut sectors = [(self.sector, self.next_slot), (previous, 0)];
+        if previous != self.sector
+            && Self::read_header(&mut self.flash, previous)? == Some(self.sequence.wrapping_sub(1))
+        {
+            sectors[1].1 = self.slots_per_sector();
+        }
+
+        let mut bytes = [0u8; RECORD_SIZE];
+        for (sector, end) in sectors {
+            for slot in (1..end).rev() {
+                self.flash
+                    .read(self.slot_offset(sector, slot), &mut bytes)?;
+                if let Some(record) = LogRecord::decode(&bytes) {
+                    return Ok(Some(record));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn slots_per_sector(&self) -> u32 {
+        F::SECTOR_SIZE / RECORD_SIZE as u32
+    }
+
+    fn slot_offset(&self, sector: u32, slot: u32) -> u32 {
+        sector * F::SECTOR_SIZE + slot * RECORD_SIZE as u32
+    }
+
+    /// Sequence number of `sector`, or `None` if it isn't part of the log
+    fn read_header(flash: &mut F, sector: u32) -> Result<Option<u32>, F::Error> {
+        let mut header = [0u8; 8];
+        flash.read(sector * F::SECTOR_SIZE, &mut header)?;
+
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let sequence = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+        Ok((magic == SECTOR_MAGIC).then_some(sequence))
+    }
+
+    fn start_sector(&mut self, sector: u32, sequence: u32) -> Result<(), F::Error> {
+        self.flash.erase(sector)?;
+
+        let mut header = [0u8; 8];
+        header[0..4].copy_from_slice(&SECTOR_MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&sequence.to_le_bytes());
+        self.flash.write(sector * F::SECTOR_SIZE, &header)?;
+
+        self.sector = sector;
+        self.sequence = sequence;
+        self.next_slot = 1;
+
+        Ok(())
+    }
+}
+
+/// Iterator returned by `FlashLog::records`
+pub struct Records<'a, F: Flash> {
+    log: &'a mut FlashLog<F>,
+
+    /// Sectors finished so far, out of `log.sectors`
+    visited: u32,
+    sector: u32,
+
+    /// Last slot read in `sector`; 0 before the first record
+    slot: u32,
+}
+
+impl<F: Flash> Iterator for Records<'_, F> {
+    type Item = Result<LogRecord, F::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.visited < self.log.sectors {
+            if self.slot == 0 {
+                match FlashLog::read_header(&mut self.log.flash, self.sector) {
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        self.next_sector();
+                        continue;
+                    }
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            let end = if self.sector == self.log.sector {
+                self.log.next_slot
+            } else {
+                self.log.slots_per_sector()
+            };
+
+            self.slot += 1;
+            if self.slot >= end {
+                self.next_sector();
+                continue;
+            }
+
+            let mut bytes = [0u8; RECORD_SIZE];
+            let offset = self.log.slot_offset(self.sector, self.slot);
+            if let Err(e) = self.log.flash.read(offset, &mut bytes) {
+                return Some(Err(e));
+            }
+
+            // An erased slot ends a sector that was being written when the device reset
+            if is_erased(&bytes) {
+                self.next_sector();
+                continue;
+            }
+
+            if let Some(record) = LogRecord::decode(&bytes) {
+                return Some(Ok(record));
+            }
+        }
+
+        None
+    }
+}
+
+impl<F: Flash> Records<'_, F> {
+    fn next_sector(&mut self) {
+        self.visited += 1;
+        self.sector = (self.sector + 1) % self.log.sectors;
+        self.slot = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sectors small enough to wrap around quickly, 31 records each
+    const MOCK_SECTOR_SIZE: usize = 512;
+    const MOCK_SECTORS: u32 = 3;
+
+    /// NOR flash in RAM, refusing to set bits without an erase
+    struct MockFlash {
+        bytes: [u8; MOCK_SECTOR_SIZE * MOCK_SECTORS as usize],
+        erases: u32,
+    }
+
+    impl MockFlash {
+        fn new() -> Self {
+            Self {
+                bytes: [0xFF; MOCK_SECTOR_SIZE * MOCK_SECTORS as usize],
+                erases: 0,
+            }
+        }
+    }
+
+    impl Flash for MockFlash {
+        type Error = ();
+
+        const SECTOR_SIZE: u32 = MOCK_SECTOR_SIZE as u32;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), ()> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.bytes[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), ()> {
+            let offset = offset as usize;
+            for (stored, &byte) in self.bytes[offset..offset + bytes.len()]
+                .iter_mut()
+                .zip(bytes)
+            {
+                assert_eq!(*stored & byte, byte, "write without erase at {}", offset);
+                *stored = byte;
+            }
+            Ok(())
+        }
+
+        fn erase(&mut self, sector: u32) -> Result<(), ()> {
+            let start = sector as usize * MOCK_SECTOR_SIZE;
+            self.bytes[start..start + MOCK_SECTOR_SIZE].fill(0xFF);
+            self.erases += 1;
+            Ok(())
+        }
+    }
+
+    fn record(timestamp: u32) -> LogRecord {
+        LogRecord {
+            timestamp,
+            latitude: 40.17764,
+            longitude: 44.51255,
+            altitude: Some(-86),
+            satellites: None,
+        }
+    }
+
+    fn timestamps(log: &mut FlashLog<&mut MockFlash>) -> std::vec::Vec<u32> {
+        log.records()
+            .unwrap()
+            .map(|record| record.unwrap().timestamp)
+            .collect()
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let record = record(1_740_830_400);
+        let decoded = LogRecord::decode(&record.encode()).unwrap();
+
+        assert_eq!(decoded.timestamp, record.timestamp);
+        assert!((decoded.latitude - record.latitude).abs() < 1e-7);
+        assert!((decoded.longitude - record.longitude).abs() < 1e-7);
+        assert_eq!(decoded.altitude, Some(-86));
+        assert_eq!(decoded.satellites, None);
+        assert_eq!(LogRecord::decode(&[0xFF; RECORD_SIZE]), None);
+    }
+
+    #[test]
+    fn test_wraparound_keeps_newest_in_order() {
+        let mut flash = MockFlash::new();
+        let mut log = FlashLog::open(&mut flash, MOCK_SECTORS).unwrap();
+
+        // 31 records per sector, so the 94th starts over in the first sector
+        for timestamp in 0..100 {
+            log.append(&record(timestamp)).unwrap();
+        }
+
+        // The first sector's 31 records went when it was erased for the 94th
+        assert_eq!(
+            timestamps(&mut log),
+            (31..100).collect::<std::vec::Vec<_>>()
+        );
+        assert_eq!(flash.erases, 4);
+    }
+
+    #[test]
+    fn test_reopen_continues_the_log() {
+        let mut flash = MockFlash::new();
+
+        let mut log = FlashLog::open(&mut flash, MOCK_SECTORS).unwrap();
+        for timestamp in 0..40 {
+            log.append(&record(timestamp)).unwrap();
+        }
+        // The last 9 records are still buffered when the device resets
+        drop(log);
+
+        let mut log = FlashLog::open(&mut flash, MOCK_SECTORS).unwrap();
+        for timestamp in 100..150 {
+            log.append(&record(timestamp)).unwrap();
+        }
+
+        let expected: std::vec::Vec<u32> = (0..31).chain(100..150).collect();
+        assert_eq!(timestamps(&mut log), expected);
+    }
+
+    #[test]
+    fn test_last_record() {
+        let mut flash = MockFlash::new();
+
+        let mut log = FlashLog::open(&mut flash, MOCK_SECTORS).unwrap();
+        assert_eq!(log.last_record().unwrap(), None);
+
+        // Still buffered
+        log.append(&record(0)).unwrap();
+        assert_eq!(log.last_record().unwrap().map(|r| r.timestamp), Some(0));
+
+        // The sector fills up at 31; the 32nd starts the next one, still in the buffer when the
+        // device resets
+        for timestamp in 1..32 {
+            log.append(&record(timestamp)).unwrap();
+        }
+        drop(log);
+
+        let mut log = FlashLog::open(&mut flash, MOCK_SECTORS).unwrap();
+        assert_eq!(log.last_record().unwrap().map(|r| r.timestamp), Some(30));
+    }
+
+    #[test]
+    fn test_torn_record_is_skipped() {
+        let mut flash = MockFlash::new();
+
+        let mut log = FlashLog::open(&mut flash, MOCK_SECTORS).unwrap();
+        for timestamp in 0..3 {
+            log.append(&record(timestamp)).unwrap();
+        }
+        log.flush().unwrap();
+        drop(log);
+
+        // Corrupt the second record, as an interrupted write would
+        flash.bytes[2 * RECORD_SIZE + 4] ^= 0x01;
+
+        let mut log = FlashLog::open(&mut flash, MOCK_SECTORS).unwrap();
+        log.append(&record(3)).unwrap();
+
+        assert_eq!(timestamps(&mut log), [0, 2, 3]);
+    }
+}
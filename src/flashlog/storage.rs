@@ -0,0 +1,137 @@
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant};
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+use esp_storage::{FlashStorage, FlashStorageError};
+
+use super::{Flash, FlashLog, LogRecord};
+use crate::gnss::positioning::GnssPositioning;
+use crate::gnss::watch::{restore_fix, GNSS_WATCH};
+
+/// Start of the flash region the log lives in
+///
+/// The default espflash partition table ends the app partition at 4 MB, leaving the upper half
+/// of the Heltec V3's 8 MB flash unused. A custom partition table has to keep this range free.
+pub const REGION_OFFSET: u32 = 0x40_0000;
+
+/// Sectors given to the log, 1 MB; a little over 65,000 records
+pub const REGION_SECTORS: u32 = 256;
+
+/// The log, once `record` has opened it; lock it to read records back
+pub static FLASH_LOG: Mutex<CriticalSectionRawMutex, RefCell<Option<FlashLog<FlashRegion>>>> =
+    Mutex::new(RefCell::new(None));
+
+/// `REGION_SECTORS` sectors of the on-board flash from `REGION_OFFSET`
+pub struct FlashRegion {
+    storage: FlashStorage,
+}
+
+impl Flash for FlashRegion {
+    type Error = FlashStorageError;
+
+    const SECTOR_SIZE: u32 = FlashStorage::ERASE_SIZE as u32;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        ReadNorFlash::read(&mut self.storage, REGION_OFFSET + offset, bytes)
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        NorFlash::write(&mut self.storage, REGION_OFFSET + offset, bytes)
+    }
+
+    fn erase(&mut self, sector: u32) -> Result<(), Self::Error> {
+        let start = REGION_OFFSET + sector * Self::SECTOR_SIZE;
+
+        NorFlash::erase(&mut self.storage, start, start + Self::SECTOR_SIZE)
+    }
+}
+
+/// How often fixes are written to the log
+pub struct Config {
+    /// Shortest time between records; at 10 s the region holds about a week of breadcrumbs
+    pub interval: Duration,
+
+    /// Longest a record stays buffered before it's written out, bounding how far behind the
+    /// position restored after a reset can be
+    pub flush_interval: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            flush_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Open the log and append a record of the latest fix every `config.interval`
+///
+/// The newest record in the log is published as a stale fix on opening, for the display to show
+/// until the receiver has a fix of its own; see `GnssPositioning::is_stale`.
+///
+/// Flash writes stall the CPU while the cache is off, for tens of milliseconds when a sector is
+/// erased, which is why records are batched and rate limited rather than written per fix.
+#[embassy_executor::task]
+pub async fn record(config: Config) {
+    let region = FlashRegion {
+        storage: FlashStorage::new(),
+    };
+    match FlashLog::open(region, REGION_SECTORS) {
+        Ok(mut log) => {
+            match log.last_record() {
+                Ok(Some(record)) => restore_fix(GnssPositioning::from(&record)),
+                Ok(None) => {}
+                Err(e) => defmt::warn!(
+                    "Failed to read the last logged position: {:?}",
+                    defmt::Debug2Format(&e)
+                ),
+            }
+
+            FLASH_LOG.lock(|cell| cell.replace(Some(log)));
+        }
+        Err(e) => {
+            defmt::error!(
+                "Failed to open the flash log: {:?}",
+                defmt::Debug2Format(&e)
+            );
+            return;
+        }
+    }
+
+    let Some(mut gnss_rx) = GNSS_WATCH.receiver() else {
+        defmt::error!("No GNSS receiver left for the flash log");
+        return;
+    };
+
+    let mut last_record: Option<Instant> = None;
+    let mut last_flush = Instant::now();
+
+    loop {
+        // The restored fix is already the newest record
+        let Some(positioning) = gnss_rx.changed().await.filter(|p| !p.is_stale) else {
+            continue;
+        };
+        if last_record.is_some_and(|instant| instant.elapsed() < config.interval) {
+            continue;
+        }
+
+        let record = LogRecord::from(&positioning);
+        let flush = last_flush.elapsed() >= config.flush_interval;
+        let result = FLASH_LOG.lock(|cell| match cell.borrow_mut().as_mut() {
+            Some(log) => log
+                .append(&record)
+                .and_then(|()| if flush { log.flush() } else { Ok(()) }),
+            None => Ok(()),
+        });
+        if let Err(e) = result {
+            defmt::error!("Failed to log position: {:?}", defmt::Debug2Format(&e));
+        }
+
+        last_record = Some(Instant::now());
+        if flush {
+            last_flush = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use heapless::String;
+
+use super::error::GnssError;
+use super::sentence::MAX_NMEA_SENTENCE_SIZE;
+
+/// A complete outgoing sentence, `$` through `\r\n`
+pub type CommandFrame = String<MAX_NMEA_SENTENCE_SIZE>;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Sentence output rates in fixes per sentence, sent by `Gnss::configure`
+///
+/// The fields are GLL, RMC, VTG, GGA, GSA and GSV, then 13 more mostly reserved: RMC and GGA
+/// on every fix, GSV on every fifth for `satellites::SatelliteAccumulator`, the rest off.
+pub const PMTK_SENTENCE_OUTPUT: &str = "PMTK314,0,1,0,1,0,5,0,0,0,0,0,0,0,0,0,0,0,0,0";
+
+/// One fix a second, sent by `Gnss::configure`
+pub const PMTK_FIX_RATE_1HZ: &str = "PMTK220,1000";
+
+/// What `Gnss::configure` sends, in order
+pub const CONFIGURATION_COMMANDS: [&str; 2] = [PMTK_SENTENCE_OUTPUT, PMTK_FIX_RATE_1HZ];
+
+/// Checksum of an NMEA payload as two uppercase hex digits
+///
+/// `payload` is everything between `$` and `*`, exclusive.
+pub fn nmea_checksum(payload: &[u8]) -> [u8; 2] {
+    let checksum = payload.iter().fold(0u8, |checksum, &byte| checksum ^ byte);
+
+    [
+        HEX_DIGITS[(checksum >> 4) as usize],
+        HEX_DIGITS[(checksum & 0x0f) as usize],
+    ]
+}
+
+/// Wrap a command body such as `PMTK220,1000` into a full `$...*HH\r\n` frame
+pub fn nmea_frame(body: &str) -> Result<CommandFrame, GnssError> {
+    let mut frame = CommandFrame::new();
+    let checksum = nmea_checksum(body.as_bytes());
+
+    let pushed = frame
+        .push('$')
+        .and_then(|_| frame.push_str(body))
+        .and_then(|_| frame.push('*'))
+        .and_then(|_| frame.push(checksum[0] as char))
+        .and_then(|_| frame.push(checksum[1] as char))
+        .and_then(|_| frame.push_str("\r\n"));
+
+    pushed.map_err(|_| GnssError::CommandTooLong)?;
+
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum_of_known_pmtk_commands() {
+        assert_eq!(&nmea_checksum(b"PMTK220,1000"), b"1F");
+        assert_eq!(&nmea_checksum(b"PMTK101"), b"32");
+        assert_eq!(&nmea_checksum(b"PMTK251,115200"), b"1F");
+        assert_eq!(
+            &nmea_checksum(b"PMTK314,0,1,0,1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0"),
+            b"28"
+        );
+    }
+
+    #[test]
+    fn test_configuration_commands() {
+        assert_eq!(
+            nmea_frame(PMTK_SENTENCE_OUTPUT).unwrap().as_str(),
+            "$PMTK314,0,1,0,1,0,5,0,0,0,0,0,0,0,0,0,0,0,0,0*2D\r\n"
+        );
+        assert_eq!(
+            nmea_frame(PMTK_FIX_RATE_1HZ).unwrap().as_str(),
+            "$PMTK220,1000*1F\r\n"
+        );
+    }
+
+    #[test]
+    fn test_checksum_pads_to_two_digits() {
+        // 'A' ^ 'C' == 0x02
+        assert_eq!(&nmea_checksum(b"AC"), b"02");
+        assert_eq!(&nmea_checksum(b""), b"00");
+    }
+
+    #[test]
+    fn test_frame_wraps_body() {
+        assert_eq!(
+            nmea_frame("PMTK220,200").unwrap().as_str(),
+            "$PMTK220,200*2C\r\n"
+        );
+    }
+
+    #[test]
+    fn test_frame_of_receiver_sentence() {
+        // The usual RMC example, as a receiver would send it
+        assert_eq!(
+            nmea_frame("GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W")
+                .unwrap()
+                .as_str(),
+            "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A\r\n"
+        );
+    }
+
+    #[test]
+    fn test_frame_rejects_oversized_body() {
+        let body = [b'A'; MAX_NMEA_SENTENCE_SIZE];
+        let body = core::str::from_utf8(&body).unwrap();
+
+        assert!(matches!(nmea_frame(body), Err(GnssError::CommandTooLong)));
+    }
+}
@@ -1,48 +1,390 @@
+use super::command::{nmea_frame, CONFIGURATION_COMMANDS};
 use super::error::GnssError;
+use super::fallback;
+use super::filter::{PositionFilter, DEFAULT_SMOOTHING_MAX_SPEED};
+use super::plausibility::PlausibilityGate;
 use super::positioning::GnssPositioning;
-use super::sentence::SentenceBuffer;
-use super::watch::{GnssStateTx, GNSS_WATCH};
+use super::satellites::SatelliteAccumulator;
+use super::sentence::{SentenceBuffer, MAX_NMEA_SENTENCE_SIZE};
+use super::watch::{
+    forward_raw_sentence, has_fresh_fix, record_fix, GnssStateTx, GNSS_SATELLITES, GNSS_WATCH,
+    UART_OVERFLOW_COUNT,
+};
+use crate::dock::is_docked;
+use crate::errorlog::{self, ErrorCode};
+use crate::watchdog;
 use core::str;
+use core::sync::atomic::Ordering;
+use embassy_time::{with_timeout, Duration, Timer};
 use esp_hal::{
     gpio::AnyPin,
     peripherals::UART1,
-    uart::{self, RxConfig, RxError, UartRx},
+    uart::{self, RxConfig, RxError, Uart, UartRx, UartTx},
     Async,
 };
-use nmea::parse_str;
+use nmea::{parse_str, sentences::GgaData, ParseResult};
 
 pub const GNSS_BAUD_RATE: u32 = 9600;
 
+/// Accept any fix the receiver reports as valid, regardless of how many satellites it uses
+pub const GNSS_MIN_SATELLITES: u32 = 0;
+
+/// Fastest believable speed over ground (m/s), ~540 km/h; covers anything short of an airliner
+pub const GNSS_MAX_SPEED: f32 = 150.0;
+
+/// How long a published fix stands without a valid one replacing it, see `Config::fix_timeout`
+pub const DEFAULT_FIX_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Baud rates probed during auto-detection, most common factory defaults first
+pub const GNSS_BAUD_RATE_CANDIDATES: [u32; 5] = [9600, 38400, 115200, 57600, 4800];
+
+/// How long to listen at each candidate baud rate; RMC is emitted once per second
+const BAUD_RATE_PROBE_WINDOW: Duration = Duration::from_millis(1500);
+
+/// Longest sentence accepted; raise for receivers emitting longer proprietary or GSV sentences
+///
+/// Raw passthrough only fits `MAX_NMEA_SENTENCE_SIZE`, so longer sentences aren't forwarded.
+const SENTENCE_BUFFER_SIZE: usize = MAX_NMEA_SENTENCE_SIZE;
+
+/// Size of the UART RX FIFO in hardware; thresholds above it can never trigger
+pub const UART_FIFO_SIZE: u16 = 128;
+
+/// RX FIFO fill level that wakes the reader, see `Config::fifo_full_threshold`
+pub const DEFAULT_FIFO_FULL_THRESHOLD: u16 = 64;
+
+/// Bytes taken from the UART per read; a whole FIFO, so one wake-up always empties it
+const READ_BUFFER_SIZE: usize = UART_FIFO_SIZE as usize;
+
+/// Bytes `Gnss::send_command` writes before taking in what's arrived meanwhile; at any baud
+/// rate, about as many bytes come in during a chunk as go out, well short of filling the FIFO
+const SEND_CHUNK_SIZE: usize = 32;
+
+/// How long to keep reading after waking up docked; enough for a full 1 Hz RMC and GGA burst
+const DOCKED_READ_WINDOW: Duration = Duration::from_secs(2);
+
+/// Longest the read loop may take to come round before it counts as hung, well over the default
+/// `fix_timeout` and `RetryConfig::docked_interval`
+const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Pacing of the `start` task's read loop, trading responsiveness for CPU time and power
+pub struct RetryConfig {
+    /// Pause after each successful read; anything long enough for more than a FIFO's worth of
+    /// bytes to arrive (128 bytes, ~130 ms at 9600 baud) overflows it
+    pub poll_interval: Duration,
+
+    /// Backoff after a UART error, growing by this much with each further error in a row
+    pub backoff_step: Duration,
+
+    /// Longest backoff, however many errors in a row
+    pub max_backoff: Duration,
+
+    /// Time between bursts of reading while docked, see `dock::DOCKED`
+    pub docked_interval: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(0),
+            backoff_step: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            docked_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// How long to back off after `consecutive_errors` errors in a row
+    pub fn backoff(&self, consecutive_errors: u32) -> Duration {
+        let backoff = self
+            .backoff_step
+            .as_millis()
+            .saturating_mul(consecutive_errors as u64);
+
+        Duration::from_millis(backoff.min(self.max_backoff.as_millis()))
+    }
+}
+
 pub struct Config {
     pub baud_rate: u32,
     pub rx_pin: AnyPin,
+
+    /// Line to the receiver's RX, for `Gnss::configure`; `None` where it isn't wired, leaving the
+    /// receiver on its factory settings
+    pub tx_pin: Option<AnyPin>,
+
+    /// Probe `GNSS_BAUD_RATE_CANDIDATES` on startup instead of trusting `baud_rate`
+    pub auto_baud: bool,
+
+    /// Satellites (per the latest GGA) required before a fix is published as valid
+    ///
+    /// A receiver will happily report a valid RMC off three satellites, but that's a 2D fix with
+    /// no altitude and often tens of metres of error. Four is the minimum for a 3D fix; six or
+    /// more usually keeps the error down to a few metres. Until a GGA has been seen, any
+    /// non-zero minimum counts as unmet.
+    pub min_satellites: u32,
+
+    /// Fixes implying a faster move than this (m/s) from the last one are held back as glitches
+    /// until a second fix corroborates them, see `PlausibilityGate`
+    pub max_speed: f32,
+
+    /// Bytes in the RX FIFO before the reader is woken up; clamped to `UART_FIFO_SIZE`
+    ///
+    /// Whatever the FIFO has left above the threshold is the slack for the task to get
+    /// scheduled before bytes are lost: with 64 of 128 that's ~65 ms at 9600 baud, but only
+    /// ~5 ms at 115200. A lower threshold buys more slack at the cost of more wake-ups; a
+    /// sentence ending below it is still delivered once the line goes idle. Overflows are
+    /// counted in `watch::UART_OVERFLOW_COUNT`.
+    pub fifo_full_threshold: u16,
+
+    /// Withdraw the published fix after this long without a valid one
+    ///
+    /// Receivers that lose the sky usually say so with an invalid RMC, but one that goes quiet
+    /// or keeps sending sentences without a fix would otherwise leave the last position standing
+    /// forever. Not applied while docked, where reads are spaced out by design.
+    pub fix_timeout: Duration,
+
+    /// Read RMC sentences the `nmea` crate rejects with `fallback::parse_rmc`, so that one
+    /// malformed field that a fix doesn't need doesn't cost the fix
+    pub robust_parse: bool,
+
+    /// Publish fixes averaged over the last few seconds while stationary or slow, see
+    /// `PositionFilter`; `false` publishes them raw
+    pub smoothing: bool,
+
+    pub retry: RetryConfig,
 }
 
 pub struct Gnss {
     uart: UartRx<'static, Async>,
+    tx: Option<UartTx<'static, Async>>,
     sender: GnssStateTx,
 
-    nmea_buffer: SentenceBuffer,
+    nmea_buffer: SentenceBuffer<SENTENCE_BUFFER_SIZE>,
+
+    baud_rate: u32,
+    auto_baud: bool,
+    fifo_full_threshold: u16,
+
+    min_satellites: u32,
+    satellites: Option<u32>,
+
+    fix_timeout: Duration,
+
+    plausibility: PlausibilityGate,
+
+    robust_parse: bool,
+
+    /// Only with `Config::smoothing`
+    filter: Option<PositionFilter>,
+
+    /// Latest GGA, merged into each RMC-based positioning before it's published
+    last_gga: Option<GgaData>,
+
+    /// GSV groups collected for `GNSS_SATELLITES`
+    satellites_in_view: SatelliteAccumulator,
+
+    retry: RetryConfig,
 }
 
 impl Gnss {
     pub fn new<'a>(uart1: UART1, config: Config) -> Result<Self, GnssError> {
-        let uart_config = uart::Config::default()
-            .with_baudrate(config.baud_rate)
-            .with_rx(RxConfig::default().with_fifo_full_threshold(1024));
+        let fifo_full_threshold = config.fifo_full_threshold.min(UART_FIFO_SIZE);
+        if fifo_full_threshold != config.fifo_full_threshold {
+            defmt::warn!(
+                "UART FIFO threshold {} exceeds the {}-byte FIFO, using {}",
+                config.fifo_full_threshold,
+                UART_FIFO_SIZE,
+                fifo_full_threshold
+            );
+        }
 
-        let uart = UartRx::new(uart1, uart_config)
-            .map_err(|_| GnssError::UartError)?
-            .with_rx(config.rx_pin)
-            .into_async();
+        let uart = Uart::new(
+            uart1,
+            Self::uart_config(config.baud_rate, fifo_full_threshold),
+        )
+        .map_err(|_| GnssError::UartError)?
+        .with_rx(config.rx_pin);
+        let has_tx = config.tx_pin.is_some();
+        let uart = match config.tx_pin {
+            Some(tx_pin) => uart.with_tx(tx_pin),
+            None => uart,
+        };
+        let (uart, tx) = uart.into_async().split();
 
         Ok(Self {
             uart,
+            tx: has_tx.then_some(tx),
             sender: GNSS_WATCH.sender(),
             nmea_buffer: SentenceBuffer::new(),
+            baud_rate: config.baud_rate,
+            auto_baud: config.auto_baud,
+            fifo_full_threshold,
+            min_satellites: config.min_satellites,
+            satellites: None,
+            fix_timeout: config.fix_timeout,
+            plausibility: PlausibilityGate::new(config.max_speed),
+            robust_parse: config.robust_parse,
+            filter: config
+                .smoothing
+                .then(|| PositionFilter::new(DEFAULT_SMOOTHING_MAX_SPEED)),
+            last_gga: None,
+            satellites_in_view: SatelliteAccumulator::new(),
+            retry: config.retry,
         })
     }
 
+    /// Like `new`, then find the receiver's baud rate before returning
+    ///
+    /// Unlike `Config::auto_baud`, which leaves detection to `start` and falls back to
+    /// `Config::baud_rate`, this fails with `GnssError::UartError` if none of
+    /// `GNSS_BAUD_RATE_CANDIDATES` produce a valid sentence. Takes up to
+    /// `BAUD_RATE_PROBE_WINDOW` per candidate.
+    pub async fn new_autobaud(uart1: UART1, config: Config) -> Result<Self, GnssError> {
+        let mut gnss = Self::new(uart1, config)?;
+        gnss.detect_baud_rate().await.ok_or(GnssError::UartError)?;
+
+        // Already done, `start` needn't probe again
+        gnss.auto_baud = false;
+
+        Ok(gnss)
+    }
+
+    /// Send `CONFIGURATION_COMMANDS`, cutting the receiver's output down to the sentences the
+    /// driver reads at one fix a second
+    ///
+    /// Fewer sentences means fewer bytes to keep up with, and fewer FIFO overflows. The receiver's
+    /// `PMTK001` acknowledgements aren't waited for; they're ignored like any other unsupported
+    /// sentence. Fails with `GnssError::UartError` when there's no `Config::tx_pin`.
+    pub async fn configure(&mut self) -> Result<(), GnssError> {
+        for command in CONFIGURATION_COMMANDS {
+            self.send_command(nmea_frame(command)?.as_bytes()).await?;
+            defmt::info!("Sent GNSS command: {}", command);
+        }
+
+        Ok(())
+    }
+
+    /// Write `command` to the receiver as it is: a framed sentence from `command::nmea_frame`,
+    /// such as `PMTK101` for a hot start, or binary assistance data
+    ///
+    /// Goes out `SEND_CHUNK_SIZE` bytes at a time, and whatever the receiver sent meanwhile is
+    /// parsed between chunks, so even a long upload doesn't leave the RX FIFO to overflow. Fails
+    /// with `GnssError::UartError` when there's no `Config::tx_pin`.
+    pub async fn send_command(&mut self, command: &[u8]) -> Result<(), GnssError> {
+        for chunk in command.chunks(SEND_CHUNK_SIZE) {
+            let tx = self.tx.as_mut().ok_or(GnssError::UartError)?;
+            let mut bytes = chunk;
+            while !bytes.is_empty() {
+                let written = tx
+                    .write_async(bytes)
+                    .await
+                    .map_err(|_| GnssError::UartError)?;
+                bytes = &bytes[written..];
+            }
+
+            self.read_pending();
+        }
+
+        Ok(())
+    }
+
+    /// Parse whatever is already in the RX FIFO, without waiting for more
+    fn read_pending(&mut self) {
+        let mut read_buffer = [0u8; READ_BUFFER_SIZE];
+
+        match self.uart.read_buffered(&mut read_buffer) {
+            Ok(bytes_read) => self.feed(&read_buffer[..bytes_read]),
+            Err(e) => self.handle_uart_error(e),
+        }
+    }
+
+    fn uart_config(baud_rate: u32, fifo_full_threshold: u16) -> uart::Config {
+        uart::Config::default()
+            .with_baudrate(baud_rate)
+            .with_rx(RxConfig::default().with_fifo_full_threshold(fifo_full_threshold))
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), GnssError> {
+        self.uart
+            .apply_config(&Self::uart_config(baud_rate, self.fifo_full_threshold))
+            .map_err(|_| GnssError::UartError)?;
+
+        // Whatever was received at the previous rate is garbage now
+        self.drain_uart_buffer();
+        self.nmea_buffer.reset("Baud rate changed");
+
+        Ok(())
+    }
+
+    /// Try each of `GNSS_BAUD_RATE_CANDIDATES` until one yields a parseable NMEA sentence
+    ///
+    /// Falls back to the configured baud rate if none of the candidates produce valid data.
+    async fn detect_baud_rate(&mut self) -> Option<u32> {
+        for &baud_rate in GNSS_BAUD_RATE_CANDIDATES.iter() {
+            defmt::debug!("Probing GNSS at {} baud", baud_rate);
+
+            if let Err(e) = self.set_baud_rate(baud_rate) {
+                defmt::warn!("Unable to switch UART to {} baud: {}", baud_rate, e);
+                continue;
+            }
+
+            if self.probe_baud_rate().await {
+                defmt::info!("Detected GNSS baud rate: {}", baud_rate);
+                self.baud_rate = baud_rate;
+
+                return Some(baud_rate);
+            }
+        }
+
+        defmt::warn!(
+            "No valid NMEA at any candidate baud rate, falling back to {}",
+            self.baud_rate
+        );
+        let _ = self.set_baud_rate(self.baud_rate);
+
+        None
+    }
+
+    /// Whether the receiver sends anything within `timeout`, even at the wrong baud rate
+    ///
+    /// For the boot self-test; what's read is discarded.
+    pub async fn is_sending(&mut self, timeout: Duration) -> bool {
+        let mut read_buffer = [0u8; READ_BUFFER_SIZE];
+
+        // Framing errors still mean something arrived
+        matches!(
+            with_timeout(timeout, self.uart.read_async(&mut read_buffer)).await,
+            Ok(Ok(1..) | Err(_))
+        )
+    }
+
+    /// Listen for `BAUD_RATE_PROBE_WINDOW` and report whether a valid sentence came through
+    async fn probe_baud_rate(&mut self) -> bool {
+        let probe = async {
+            let mut read_buffer = [0u8; READ_BUFFER_SIZE];
+
+            loop {
+                match self.uart.read_async(&mut read_buffer).await {
+                    Ok(bytes_read) => {
+                        for &byte in &read_buffer[..bytes_read] {
+                            if let Some(sentence) = self.nmea_buffer.feed(byte) {
+                                if parse_str(sentence).is_ok() {
+                                    return;
+                                }
+                            }
+                        }
+                    }
+
+                    // Framing errors are expected while the baud rate is wrong
+                    Err(e) => defmt::debug!("UART error while probing: {}", e),
+                }
+            }
+        };
+
+        with_timeout(BAUD_RATE_PROBE_WINDOW, probe).await.is_ok()
+    }
+
     fn drain_uart_buffer(&mut self) {
         defmt::debug!("Draining UART buffer");
 
@@ -52,6 +394,7 @@ impl Gnss {
                 Ok(0) => break,    // Stop when no more bytes are available
                 Ok(_) => continue, // Keep reading if bytes were read
                 Err(err) => {
+                    errorlog::recent::record(ErrorCode::GnssUart);
                     defmt::error!("UART read error while draining: {}", err);
                     break; // Stop draining on another error
                 }
@@ -59,53 +402,201 @@ impl Gnss {
         }
     }
 
+    /// Read whatever the UART has and publish any sentences it completes
+    ///
+    /// FIFO overflows are recovered from right away, since backing off would only overflow it
+    /// again; any other UART error is returned for the caller to back off from.
     async fn read_positioning(&mut self) -> Result<(), GnssError> {
-        let mut read_buffer = [0u8; 64]; // UART read buffer
+        let mut read_buffer = [0u8; READ_BUFFER_SIZE];
 
-        loop {
-            match self.uart.read_async(&mut read_buffer).await {
-                Ok(bytes_read) if bytes_read > 0 => {
-                    for &byte in &read_buffer[..bytes_read] {
-                        if let Some(sentence) = self.nmea_buffer.feed(byte) {
-                            defmt::info!("nmea: {}", sentence);
-
-                            match Self::parse(sentence) {
-                                Ok(positioning) => {
-                                    defmt::info!("Positioning: {}", positioning);
-                                    self.sender.send(Some(positioning));
-                                }
-                                Err(GnssError::NoFix) => self.sender.send(None),
-                                Err(e) => {
-                                    defmt::warn!("NMEA parse error: {:?}", defmt::Debug2Format(&e))
-                                }
-                            }
-                        }
+        match self.uart.read_async(&mut read_buffer).await {
+            Ok(bytes_read) => {
+                self.feed(&read_buffer[..bytes_read]);
+
+                Ok(())
+            }
+
+            Err(RxError::FifoOverflowed) => {
+                self.handle_uart_error(RxError::FifoOverflowed);
+
+                Ok(())
+            }
+
+            Err(e) => {
+                self.handle_uart_error(e);
+
+                Err(GnssError::UartError)
+            }
+        }
+    }
+
+    /// Pass `bytes` from the UART through the sentence buffer, publishing what they complete
+    fn feed(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if let Some(sentence) = self.nmea_buffer.feed(byte) {
+                defmt::info!("nmea: {}", sentence);
+                forward_raw_sentence(sentence);
+
+                match Self::parse(sentence) {
+                    Err(_) if self.robust_parse => {
+                        let positioning = fallback::parse_rmc(sentence);
+                        self.handle_positioning(positioning);
                     }
+                    parsed => self.handle_parsed(parsed),
+                }
+            }
+        }
+
+        if !bytes.is_empty() {
+            defmt::info!(
+                "{}",
+                self.nmea_buffer.as_string().unwrap_or("<invalid UTF-8>")
+            );
+        }
+    }
+
+    /// Sleep for `docked_interval`, then read for `DOCKED_READ_WINDOW` to refresh the fix
+    ///
+    /// The receiver can't be told to power down, so it keeps sending and the FIFO overflows while
+    /// the task sleeps. Those bytes are stale anyway; they're discarded without being counted.
+    async fn read_docked(&mut self) {
+        Timer::after(self.retry.docked_interval).await;
+
+        let mut discard = [0u8; READ_BUFFER_SIZE];
+        loop {
+            match self.uart.read_buffered(&mut discard) {
+                Ok(0) => break,
+                Ok(_) | Err(RxError::FifoOverflowed) => continue,
+                Err(e) => {
+                    defmt::debug!("UART error while discarding: {}", e);
+                    break;
+                }
+            }
+        }
+        self.nmea_buffer.reset("woke up docked");
+
+        let read = async {
+            loop {
+                if self.read_positioning().await.is_err() {
+                    return;
+                }
+            }
+        };
+        let _ = with_timeout(DOCKED_READ_WINDOW, read).await;
+    }
+
+    fn parse(sentence: &str) -> Result<ParseResult, GnssError> {
+        parse_str(sentence).map_err(|e| {
+            defmt::warn!("NMEA parse error: {:?}", defmt::Debug2Format(&e));
+
+            GnssError::ParseError
+        })
+    }
+
+    fn handle_parsed(&mut self, parsed: Result<ParseResult, GnssError>) {
+        let positioning = match parsed {
+            // GGA carries no date, so it only feeds the fix-quality checks
+            Ok(ParseResult::GGA(gga)) => {
+                self.satellites = gga.fix_satellites;
+                self.last_gga = Some(gga);
+                return;
+            }
+            // GSV carries no position, only what's in the sky
+            Ok(ParseResult::GSV(gsv)) => {
+                if let Some(sky) = self.satellites_in_view.feed(&gsv) {
+                    defmt::debug!(
+                        "Satellites: {} in view, {} tracked",
+                        sky.in_view(),
+                        sky.tracked()
+                    );
+                    GNSS_SATELLITES.sender().send(sky.clone());
+                }
+                return;
+            }
+            Ok(parsed_data) => GnssPositioning::try_from(parsed_data),
+            Err(e) => Err(e),
+        };
 
-                    defmt::info!("{}", self.nmea_buffer.as_string().unwrap());
+        self.handle_positioning(positioning);
+    }
+
+    /// Publish a fix read from RMC, or withdraw the published one if the receiver has none
+    fn handle_positioning(&mut self, positioning: Result<GnssPositioning, GnssError>) {
+        match positioning {
+            Ok(positioning) if !self.has_enough_satellites() => {
+                defmt::info!(
+                    "Ignoring fix from {:?} satellites, {} required: {}",
+                    self.satellites,
+                    self.min_satellites,
+                    positioning
+                );
+                self.withdraw_fix();
+            }
+            Ok(mut positioning) => {
+                // The watch keeps the previous fix until a plausible one replaces it
+                if !self.plausibility.check(&positioning) {
+                    defmt::warn!("Ignoring implausible jump to {}", positioning);
+                    return;
                 }
 
-                Ok(_) => continue, // No bytes read; continue to next iteration
+                if let Some(gga) = &self.last_gga {
+                    positioning.merge_gga(gga);
+                }
+                if let Some(filter) = &mut self.filter {
+                    positioning = filter.apply(positioning);
+                }
 
-                Err(e) => self.handle_uart_error(e),
+                defmt::info!("Positioning: {}", positioning);
+                record_fix(&positioning);
+                self.sender.send(Some(positioning));
             }
+            Err(GnssError::NoFix) => self.withdraw_fix(),
+            Err(e) => {
+                defmt::warn!("NMEA parse error: {:?}", defmt::Debug2Format(&e))
+            }
+        }
+    }
+
+    /// Publish `None`, unless what's published is the fix restored at boot; that one stays up
+    /// until a live fix replaces it
+    fn withdraw_fix(&mut self) {
+        if GNSS_WATCH
+            .try_get()
+            .flatten()
+            .is_none_or(|positioning| !positioning.is_stale)
+        {
+            self.sender.send(None);
         }
     }
 
-    fn parse(sentence: &str) -> Result<GnssPositioning, GnssError> {
-        return parse_str(sentence)
-            .map_err(|e| {
-                defmt::warn!("NMEA parse error: {:?}", defmt::Debug2Format(&e));
+    /// Publish `None` if the fix on `GNSS_WATCH` is older than `fix_timeout`
+    fn expire_stale_fix(&mut self) {
+        let has_live_fix = GNSS_WATCH
+            .try_get()
+            .flatten()
+            .is_some_and(|positioning| !positioning.is_stale);
+        if has_live_fix && !has_fresh_fix(self.fix_timeout) {
+            defmt::warn!(
+                "No valid fix for {} s, withdrawing the last one",
+                self.fix_timeout.as_secs()
+            );
+            self.sender.send(None);
+        }
+    }
 
-                GnssError::ParseError
-            })
-            .and_then(|parsed_data| GnssPositioning::try_from(parsed_data));
+    fn has_enough_satellites(&self) -> bool {
+        self.min_satellites == 0
+            || self
+                .satellites
+                .is_some_and(|satellites| satellites >= self.min_satellites)
     }
 
     fn handle_uart_error(&mut self, e: RxError) {
         defmt::warn!("UART error: {}", e);
+        errorlog::recent::record(ErrorCode::GnssUart);
 
         if let RxError::FifoOverflowed = e {
+            UART_OVERFLOW_COUNT.fetch_add(1, Ordering::Relaxed);
             self.drain_uart_buffer();
             self.nmea_buffer.reset("FIFO overflowed");
         }
@@ -116,16 +607,51 @@ impl Gnss {
 pub async fn start(mut gnss: Gnss) {
     defmt::info!("Starting GNSS task");
 
+    if gnss.auto_baud {
+        gnss.detect_baud_rate().await;
+    }
+
+    if gnss.tx.is_some() {
+        if let Err(e) = gnss.configure().await {
+            defmt::warn!("Failed to configure the GNSS receiver: {}", e);
+        }
+    }
+
+    let mut consecutive_errors: u32 = 0;
+    let watchdog = watchdog::supervisor::register("GNSS", WATCHDOG_TIMEOUT);
+
     loop {
-        let result = gnss.read_positioning().await;
+        watchdog.feed();
+
+        if is_docked() {
+            gnss.read_docked().await;
+            continue;
+        }
 
-        match result {
-            Ok(_) => {
-                // Success - position found
-                defmt::warn!("SUCCESS");
+        // Bounded so that a receiver gone silent still has its fix expire
+        let read = with_timeout(gnss.fix_timeout, gnss.read_positioning()).await;
+        gnss.expire_stale_fix();
+
+        match read {
+            Err(_) => defmt::warn!("Nothing from the GNSS receiver"),
+            Ok(Ok(())) => {
+                consecutive_errors = 0;
+
+                if gnss.retry.poll_interval.as_ticks() > 0 {
+                    Timer::after(gnss.retry.poll_interval).await;
+                }
             }
-            Err(e) => {
-                defmt::warn!("FAILURE: {}", defmt::Debug2Format(&e));
+            Ok(Err(e)) => {
+                consecutive_errors = consecutive_errors.saturating_add(1);
+
+                let backoff = gnss.retry.backoff(consecutive_errors);
+                defmt::debug!(
+                    "GNSS error {} ({} in a row), backing off for {} ms",
+                    e,
+                    consecutive_errors,
+                    backoff.as_millis()
+                );
+                Timer::after(backoff).await;
             }
         }
     }
@@ -4,8 +4,9 @@ use defmt::Format;
 pub enum GnssError {
     NoFix,
     MissingField(&'static str), // Specify which field is missing
-    UnsupportedSentence,        // We only need to support GPRMC for now
+    UnsupportedSentence,        // Only RMC carries a position; GGA and GSV are handled apart
     UartError,
     InvalidUtf8,
     ParseError,
+    CommandTooLong, // Outgoing command doesn't fit in a sentence buffer
 }
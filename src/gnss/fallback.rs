@@ -0,0 +1,219 @@
+use chrono::{NaiveDate, NaiveTime};
+
+use super::command::nmea_checksum;
+use super::error::GnssError;
+use super::positioning::GnssPositioning;
+
+/// Read an RMC sentence field by field, for when `nmea::parse_str` rejects it
+///
+/// Only what a fix can't do without is checked strictly: the checksum, status, time, date and
+/// coordinates. Speed and course that don't parse are left out rather than failing the whole
+/// sentence, and the fields after the date aren't read at all. Any talker ID is accepted, so
+/// `GNRMC` from a multi-constellation receiver reads the same as `GPRMC`.
+///
+/// | Field | Content                                  |
+/// |-------|------------------------------------------|
+/// | 0     | Talker ID and `RMC`                      |
+/// | 1     | UTC time, `hhmmss` with optional decimals |
+/// | 2     | Status, `A` valid or `V` void            |
+/// | 3, 4  | Latitude, `ddmm.mmmm`, `N` or `S`        |
+/// | 5, 6  | Longitude, `dddmm.mmmm`, `E` or `W`      |
+/// | 7     | Speed over ground, knots                 |
+/// | 8     | Course over ground, degrees true         |
+/// | 9     | UTC date, `ddmmyy`                       |
+pub fn parse_rmc(sentence: &str) -> Result<GnssPositioning, GnssError> {
+    let body = sentence
+        .trim_end()
+        .strip_prefix('$')
+        .ok_or(GnssError::ParseError)?;
+    let (payload, checksum) = body.rsplit_once('*').ok_or(GnssError::ParseError)?;
+    if !checksum
+        .as_bytes()
+        .eq_ignore_ascii_case(&nmea_checksum(payload.as_bytes()))
+    {
+        return Err(GnssError::ParseError);
+    }
+
+    let mut fields = [""; 10];
+    for (slot, field) in fields.iter_mut().zip(payload.split(',')) {
+        *slot = field;
+    }
+
+    if fields[0].len() != 5 || !fields[0].ends_with("RMC") {
+        return Err(GnssError::UnsupportedSentence);
+    }
+
+    match fields[2] {
+        "A" => {}
+        "V" => return Err(GnssError::NoFix),
+        _ => return Err(GnssError::ParseError),
+    }
+
+    let latitude = coordinate(fields[3], fields[4], 2, ("N", "S"), "latitude")?;
+    let longitude = coordinate(fields[5], fields[6], 3, ("E", "W"), "longitude")?;
+    let time = required(fields[1], "fix_time")
+        .and_then(|field| parse_time(field).ok_or(GnssError::ParseError))?;
+    let date = required(fields[9], "fix_date")
+        .and_then(|field| parse_date(field).ok_or(GnssError::ParseError))?;
+
+    Ok(GnssPositioning {
+        datetime: date.and_time(time),
+        latitude,
+        longitude,
+        speed: fields[7].parse().ok(),
+        heading: fields[8].parse().ok(),
+        altitude: None,
+        satellites: None,
+        fix_quality: None,
+        is_stale: false,
+    })
+}
+
+fn required<'a>(field: &'a str, name: &'static str) -> Result<&'a str, GnssError> {
+    if field.is_empty() {
+        Err(GnssError::MissingField(name))
+    } else {
+        Ok(field)
+    }
+}
+
+/// Signed decimal degrees from `degree_digits` digits of degrees followed by decimal minutes
+fn coordinate(
+    value: &str,
+    hemisphere: &str,
+    degree_digits: usize,
+    (positive, negative): (&str, &str),
+    name: &'static str,
+) -> Result<f64, GnssError> {
+    let value = required(value, name)?;
+    if !value.is_ascii() || value.len() <= degree_digits {
+        return Err(GnssError::ParseError);
+    }
+
+    let degrees: f64 = value[..degree_digits]
+        .parse()
+        .map_err(|_| GnssError::ParseError)?;
+    let minutes: f64 = value[degree_digits..]
+        .parse()
+        .map_err(|_| GnssError::ParseError)?;
+    let magnitude = degrees + minutes / 60.0;
+
+    if hemisphere == positive {
+        Ok(magnitude)
+    } else if hemisphere == negative {
+        Ok(-magnitude)
+    } else if hemisphere.is_empty() {
+        Err(GnssError::MissingField(name))
+    } else {
+        Err(GnssError::ParseError)
+    }
+}
+
+/// `hhmmss` with up to three decimals of seconds; more are ignored
+fn parse_time(field: &str) -> Option<NaiveTime> {
+    let (whole, fraction) = field.split_once('.').unwrap_or((field, ""));
+    let [hour, minute, second] = two_digit_fields(whole)?;
+
+    let millis = fraction
+        .bytes()
+        .chain(core::iter::repeat(b'0'))
+        .take(3)
+        .try_fold(0, |millis, digit| {
+            digit
+                .is_ascii_digit()
+                .then(|| millis * 10 + (digit - b'0') as u32)
+        })?;
+
+    NaiveTime::from_hms_milli_opt(hour, minute, second, millis)
+}
+
+/// `ddmmyy`, in this century
+fn parse_date(field: &str) -> Option<NaiveDate> {
+    let [day, month, year] = two_digit_fields(field)?;
+
+    NaiveDate::from_ymd_opt(2000 + year as i32, month, day)
+}
+
+/// Three two-digit numbers from exactly six digits
+fn two_digit_fields(digits: &str) -> Option<[u32; 3]> {
+    let digits = digits.as_bytes();
+    if digits.len() != 6 || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    let pair =
+        |index: usize| (digits[index] - b'0') as u32 * 10 + (digits[index + 1] - b'0') as u32;
+
+    Some([pair(0), pair(2), pair(4)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::positioning::assert_close;
+
+    #[test]
+    fn test_parses_receiver_sentence() {
+        let positioning =
+            parse_rmc("$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230324,003.1,W*61\r\n")
+                .unwrap();
+
+        assert_eq!(
+            positioning.datetime,
+            NaiveDate::from_ymd_opt(2024, 3, 23)
+                .unwrap()
+                .and_hms_opt(12, 35, 19)
+                .unwrap()
+        );
+        assert_close(positioning.latitude, 48.1173, 1e-6);
+        assert_close(positioning.longitude, 11.516_666_666, 1e-6);
+        assert_eq!(positioning.speed, Some(22.4));
+        assert_eq!(positioning.heading, Some(84.4));
+    }
+
+    #[test]
+    fn test_accepts_any_talker() {
+        let positioning =
+            parse_rmc("$GNRMC,083559.00,A,4717.11437,N,00833.91522,E,0.004,77.52,091202,,,A*49")
+                .unwrap();
+
+        assert_close(positioning.latitude, 47.285_239_5, 1e-6);
+        assert_eq!(
+            positioning.datetime.time(),
+            NaiveTime::from_hms_opt(8, 35, 59).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_malformed_optional_fields_keep_the_fix() {
+        let positioning =
+            parse_rmc("$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,08#.4,230324,003.1,Q*70")
+                .unwrap();
+
+        assert_eq!(positioning.speed, Some(22.4));
+        assert_eq!(positioning.heading, None);
+
+        // Southern and western hemispheres, without speed or course at all
+        let positioning =
+            parse_rmc("$GPRMC,123519,A,4807.038,S,01131.000,W,,,230324,,*19").unwrap();
+        assert_close(positioning.latitude, -48.1173, 1e-6);
+        assert_close(positioning.longitude, -11.516_666_666, 1e-6);
+        assert_eq!(positioning.speed, None);
+    }
+
+    #[test]
+    fn test_rejects_void_and_corrupt_sentences() {
+        assert!(matches!(
+            parse_rmc("$GPRMC,123519,V,,,,,,,230324,,,N*5A"),
+            Err(GnssError::NoFix)
+        ));
+        assert!(matches!(
+            parse_rmc("$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230324,003.1,W*60"),
+            Err(GnssError::ParseError)
+        ));
+        assert!(matches!(
+            parse_rmc("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47"),
+            Err(GnssError::UnsupportedSentence)
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+use heapless::Deque;
+
+use super::positioning::GnssPositioning;
+
+/// Fixes averaged together; five seconds' worth at 1 Hz
+pub const FILTER_WINDOW: usize = 5;
+
+/// Fastest speed (knots) at which fixes are still smoothed, ~4 km/h; a brisk walk
+pub const DEFAULT_SMOOTHING_MAX_SPEED: f32 = 2.0;
+
+/// Longest gap between fixes that still continues the average, e.g. over a dropped sentence
+const MAX_GAP_MS: i64 = 3_000;
+
+/// A fix's coordinates and time, all the filter keeps of it
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    latitude: f64,
+    longitude: f64,
+    time_ms: i64,
+}
+
+/// Moving average over the last `FILTER_WINDOW` fixes, so a stationary position stops twitching
+///
+/// Only latitude and longitude are averaged; everything else is the latest fix's. Above
+/// `max_speed` fixes pass through as they are and the average starts over: averaging a moving
+/// position would drag it behind along the track. A gap of more than `MAX_GAP_MS` since the last
+/// fix starts it over too, so a position from before the fix was lost doesn't linger.
+#[derive(Debug)]
+pub struct PositionFilter {
+    /// Speed over ground in knots above which nothing is smoothed
+    max_speed: f32,
+
+    samples: Deque<Sample, FILTER_WINDOW>,
+}
+
+impl PositionFilter {
+    pub const fn new(max_speed: f32) -> Self {
+        Self {
+            max_speed,
+            samples: Deque::new(),
+        }
+    }
+
+    /// `positioning` with its coordinates averaged over the recent fixes
+    pub fn apply(&mut self, positioning: GnssPositioning) -> GnssPositioning {
+        let sample = Sample {
+            latitude: positioning.latitude,
+            longitude: positioning.longitude,
+            time_ms: positioning.datetime.and_utc().timestamp_millis(),
+        };
+
+        let is_moving = positioning
+            .speed
+            .is_some_and(|speed| speed > self.max_speed);
+        let is_stale = self
+            .samples
+            .back()
+            .is_some_and(|last| (sample.time_ms - last.time_ms).abs() > MAX_GAP_MS);
+        if is_moving || is_stale {
+            self.samples.clear();
+        }
+        if is_moving {
+            return positioning;
+        }
+
+        if self.samples.is_full() {
+            self.samples.pop_front();
+        }
+        let _ = self.samples.push_back(sample);
+
+        // Longitudes are averaged as offsets from the latest, so fixes either side of the
+        // antimeridian don't average out to the other side of the world
+        let count = self.samples.len() as f64;
+        let (latitude_sum, offset_sum) =
+            self.samples
+                .iter()
+                .fold((0.0, 0.0), |(latitude_sum, offset_sum), previous| {
+                    (
+                        latitude_sum + previous.latitude,
+                        offset_sum + wrap_longitude(previous.longitude - sample.longitude),
+                    )
+                });
+
+        GnssPositioning {
+            latitude: latitude_sum / count,
+            longitude: wrap_longitude(sample.longitude + offset_sum / count),
+            ..positioning
+        }
+    }
+}
+
+/// `longitude` brought into -180..=180
+fn wrap_longitude(longitude: f64) -> f64 {
+    if longitude > 180.0 {
+        longitude - 360.0
+    } else if longitude < -180.0 {
+        longitude + 360.0
+    } else {
+        longitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::positioning::{assert_close, positioning_at};
+
+    #[test]
+    fn test_averages_recent_fixes() {
+        let mut filter = PositionFilter::new(DEFAULT_SMOOTHING_MAX_SPEED);
+
+        let first = filter.apply(positioning_at(0, 52.0, 13.0));
+        assert_close(first.latitude, 52.0, 1e-9);
+
+        let second = filter.apply(positioning_at(1, 52.0002, 13.0004));
+        assert_close(second.latitude, 52.0001, 1e-9);
+        assert_close(second.longitude, 13.0002, 1e-9);
+        assert_eq!(second.datetime, positioning_at(1, 0.0, 0.0).datetime);
+
+        // Only the last `FILTER_WINDOW` count
+        for second in 2..2 + FILTER_WINDOW as u32 {
+            filter.apply(positioning_at(second, 53.0, 14.0));
+        }
+        let latest = filter.apply(positioning_at(7, 53.0, 14.0));
+        assert_close(latest.latitude, 53.0, 1e-9);
+        assert_close(latest.longitude, 14.0, 1e-9);
+    }
+
+    #[test]
+    fn test_moving_fixes_pass_through() {
+        let mut filter = PositionFilter::new(DEFAULT_SMOOTHING_MAX_SPEED);
+        filter.apply(positioning_at(0, 52.0, 13.0));
+
+        let moving = GnssPositioning {
+            speed: Some(30.0),
+            ..positioning_at(1, 52.001, 13.001)
+        };
+        assert_eq!(filter.apply(moving.clone()), moving);
+
+        // Stopping starts a new average rather than going back to the old one
+        let stopped = filter.apply(positioning_at(2, 52.002, 13.002));
+        assert_close(stopped.latitude, 52.002, 1e-9);
+    }
+
+    #[test]
+    fn test_gap_starts_over() {
+        let mut filter = PositionFilter::new(DEFAULT_SMOOTHING_MAX_SPEED);
+        filter.apply(positioning_at(0, 52.0, 13.0));
+
+        let after_gap = filter.apply(positioning_at(10, 52.001, 13.001));
+        assert_close(after_gap.latitude, 52.001, 1e-9);
+    }
+
+    #[test]
+    fn test_averages_across_antimeridian() {
+        let mut filter = PositionFilter::new(DEFAULT_SMOOTHING_MAX_SPEED);
+        filter.apply(positioning_at(0, -17.0, 179.9998));
+
+        let averaged = filter.apply(positioning_at(1, -17.0, -179.9998));
+        assert!(averaged.longitude.abs() > 179.9999);
+    }
+}
@@ -0,0 +1,88 @@
+use heapless::HistoryBuffer;
+
+use super::positioning::GnssPositioning;
+
+/// Number of fixes kept; at the usual 1 Hz update rate that's the last half minute or so
+pub const HISTORY_SIZE: usize = 32;
+
+/// Ring buffer of the most recent valid fixes
+///
+/// Once full, each new fix overwrites the oldest one, so memory use is fixed regardless of how
+/// long the device runs.
+pub struct PositionHistory {
+    fixes: HistoryBuffer<GnssPositioning, HISTORY_SIZE>,
+}
+
+impl PositionHistory {
+    pub const fn new() -> Self {
+        Self {
+            fixes: HistoryBuffer::new(),
+        }
+    }
+
+    pub fn record(&mut self, positioning: GnssPositioning) {
+        self.fixes.write(positioning);
+    }
+
+    pub fn latest(&self) -> Option<&GnssPositioning> {
+        self.fixes.recent()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fixes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fixes.len() == 0
+    }
+
+    /// Recorded fixes, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &GnssPositioning> {
+        self.fixes.oldest_ordered()
+    }
+}
+
+impl Default for PositionHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::positioning::positioning_at;
+
+    /// A fix `second`s into the recording, a little further east each time
+    fn fix(second: u32) -> GnssPositioning {
+        positioning_at(second % 60, 40.17764, 44.51255 + second as f64 * 1e-5)
+    }
+
+    #[test]
+    fn test_history_keeps_order() {
+        let mut history = PositionHistory::new();
+        assert!(history.is_empty());
+        assert_eq!(history.latest(), None);
+
+        for second in 0..3 {
+            history.record(fix(second));
+        }
+
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.latest(), Some(&fix(2)));
+        assert!(history.iter().eq([0, 1, 2].map(fix).iter()));
+    }
+
+    #[test]
+    fn test_history_overwrites_oldest_when_full() {
+        let mut history = PositionHistory::new();
+
+        for second in 0..(HISTORY_SIZE as u32 + 5) {
+            history.record(fix(second));
+        }
+
+        assert_eq!(history.len(), HISTORY_SIZE);
+        assert_eq!(history.iter().next(), Some(&fix(5)));
+        assert_eq!(history.latest(), Some(&fix(HISTORY_SIZE as u32 + 4)));
+    }
+}
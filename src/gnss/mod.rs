@@ -1,6 +1,13 @@
+pub mod command;
 mod error;
+pub mod fallback;
+pub mod filter;
+pub mod history;
+pub mod plausibility;
 pub mod positioning;
-mod sentence;
+pub mod precision;
+pub mod satellites;
+pub mod sentence;
 
 // ESP32-specific modules
 #[cfg(feature = "esp32")]
@@ -0,0 +1,176 @@
+use libm::cos;
+
+use super::positioning::GnssPositioning;
+
+/// Mean Earth radius, metres
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// Shortest time assumed between two fixes, so fixes with the same timestamp don't divide by zero
+const MIN_INTERVAL_MS: i64 = 1_000;
+
+/// Where and when a fix was taken, all the gate needs to remember of it
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Fix {
+    latitude: f64,
+    longitude: f64,
+    time_ms: i64,
+}
+
+impl From<&GnssPositioning> for Fix {
+    fn from(positioning: &GnssPositioning) -> Self {
+        Self {
+            latitude: positioning.latitude,
+            longitude: positioning.longitude,
+            time_ms: positioning.datetime.and_utc().timestamp_millis(),
+        }
+    }
+}
+
+impl Fix {
+    /// Whether getting from `self` to `other` stays within `max_speed` (m/s)
+    fn reaches(&self, other: &Fix, max_speed: f32) -> bool {
+        let seconds = (other.time_ms - self.time_ms).abs().max(MIN_INTERVAL_MS) as f64 / 1_000.0;
+        let max_distance = max_speed as f64 * seconds;
+
+        distance_squared(self, other) <= max_distance * max_distance
+    }
+}
+
+/// Squared distance in metres², by the equirectangular approximation
+///
+/// Off by well under a percent at the distances that matter here, and the comparison doesn't
+/// need a square root.
+fn distance_squared(from: &Fix, to: &Fix) -> f64 {
+    let mut longitude_delta = to.longitude - from.longitude;
+    if longitude_delta > 180.0 {
+        longitude_delta -= 360.0;
+    } else if longitude_delta < -180.0 {
+        longitude_delta += 360.0;
+    }
+
+    let mean_latitude = ((from.latitude + to.latitude) / 2.0).to_radians();
+    let x = longitude_delta.to_radians() * cos(mean_latitude) * EARTH_RADIUS_M;
+    let y = (to.latitude - from.latitude).to_radians() * EARTH_RADIUS_M;
+
+    x * x + y * y
+}
+
+/// Rejects single fixes that jump further than the tracker could have moved
+///
+/// Receivers occasionally emit one fix hundreds of kilometres off. A fix that would need more
+/// than `max_speed` to reach from the last accepted one is held back; if the next fix lies
+/// within reach of it, the jump was real (e.g. the receiver caught up after a restart) and that
+/// next fix is accepted. Otherwise the outlier is forgotten.
+#[derive(Debug)]
+pub struct PlausibilityGate {
+    /// Fastest believable speed over ground, m/s
+    max_speed: f32,
+
+    accepted: Option<Fix>,
+
+    /// Implausible fix waiting for a second one to corroborate it
+    candidate: Option<Fix>,
+}
+
+impl PlausibilityGate {
+    pub const fn new(max_speed: f32) -> Self {
+        Self {
+            max_speed,
+            accepted: None,
+            candidate: None,
+        }
+    }
+
+    /// Whether `positioning` should be published, remembering it if so
+    pub fn check(&mut self, positioning: &GnssPositioning) -> bool {
+        let fix = Fix::from(positioning);
+
+        let plausible = match (&self.accepted, &self.candidate) {
+            (None, _) => true,
+            (Some(accepted), _) if accepted.reaches(&fix, self.max_speed) => true,
+            (_, Some(candidate)) => candidate.reaches(&fix, self.max_speed),
+            (_, None) => false,
+        };
+
+        if plausible {
+            self.accepted = Some(fix);
+            self.candidate = None;
+        } else {
+            self.candidate = Some(fix);
+        }
+
+        plausible
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::positioning::positioning_at;
+
+    /// ~50 km/h
+    const MAX_SPEED: f32 = 14.0;
+
+    #[test]
+    fn test_single_outlier_rejected() {
+        let mut gate = PlausibilityGate::new(MAX_SPEED);
+
+        // Walking pace, then one fix ~500 km away, then back on track
+        assert!(gate.check(&positioning_at(0, 40.17764, 44.51255)));
+        assert!(gate.check(&positioning_at(1, 40.17765, 44.51256)));
+        assert!(!gate.check(&positioning_at(2, 44.67, 44.51)));
+        assert!(gate.check(&positioning_at(3, 40.17767, 44.51258)));
+        assert!(gate.check(&positioning_at(4, 40.17768, 44.51259)));
+    }
+
+    #[test]
+    fn test_corroborated_jump_accepted() {
+        let mut gate = PlausibilityGate::new(MAX_SPEED);
+
+        assert!(gate.check(&positioning_at(0, 40.17764, 44.51255)));
+
+        // The receiver relocks ~20 km away; the second fix there confirms it
+        assert!(!gate.check(&positioning_at(1, 40.35, 44.51)));
+        assert!(gate.check(&positioning_at(2, 40.35001, 44.51)));
+        assert!(gate.check(&positioning_at(3, 40.35002, 44.51)));
+    }
+
+    #[test]
+    fn test_fast_but_possible_movement_accepted() {
+        let mut gate = PlausibilityGate::new(MAX_SPEED);
+
+        // ~11 m/s north, just under the limit, for a minute
+        for second in 0..60 {
+            let latitude = 40.17764 + second as f64 * 0.0001;
+            assert!(
+                gate.check(&positioning_at(second, latitude, 44.51255)),
+                "{second}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_distance_across_antimeridian() {
+        let east = Fix {
+            latitude: 0.0,
+            longitude: 179.9999,
+            time_ms: 0,
+        };
+        let west = Fix {
+            latitude: 0.0,
+            longitude: -179.9999,
+            ..east
+        };
+
+        // 0.0002° of longitude at the equator is ~22 m
+        let distance = distance_squared(&east, &west);
+        assert!((distance - 22.24 * 22.24).abs() < 1.0, "{distance}");
+    }
+
+    #[test]
+    fn test_cos() {
+        assert!((cos(0.0) - 1.0).abs() < 1e-12);
+        assert!((cos(60f64.to_radians()) - 0.5).abs() < 1e-9);
+        assert!(cos(90f64.to_radians()).abs() < 1e-8);
+    }
+}
@@ -1,9 +1,27 @@
 use crate::gnss::error::GnssError;
-use chrono::NaiveDateTime;
+use crate::lora::packet::{decode_coordinate, encode_coordinate, round};
+use crate::lora::report::CM_PER_S_PER_KNOT;
+use chrono::{DateTime, NaiveDateTime};
 use defmt::Format;
 use nmea::sentences::rmc::RmcStatusOfFix;
+use nmea::sentences::{FixType, GgaData};
 use nmea::ParseResult;
 
+/// Speeds below this are jitter from a stationary receiver rather than real motion
+pub const SPEED_NOISE_FLOOR_KNOTS: f32 = 0.5;
+
+const KMH_PER_KNOT: f32 = 1.852;
+
+/// Size of the BLE telemetry characteristic, see `GnssPositioning::to_ble_bytes`
+pub const BLE_TELEMETRY_SIZE: usize = 24;
+
+/// Telemetry flag bits, one per field that may be missing
+const BLE_FLAG_FIX: u8 = 1 << 0;
+const BLE_FLAG_SPEED: u8 = 1 << 1;
+const BLE_FLAG_HEADING: u8 = 1 << 2;
+const BLE_FLAG_ALTITUDE: u8 = 1 << 3;
+const BLE_FLAG_SATELLITES: u8 = 1 << 4;
+
 #[derive(Debug, Clone, PartialEq, Format)]
 pub struct GnssPositioning {
     #[defmt(Debug2Format)]
@@ -12,6 +30,154 @@ pub struct GnssPositioning {
     pub longitude: f64,
     pub speed: Option<f32>,
     pub heading: Option<f32>,
+
+    /// Metres above mean sea level, from the latest GGA; negative below it
+    pub altitude: Option<f32>,
+
+    /// Satellites used for the fix, from the latest GGA
+    pub satellites: Option<u32>,
+
+    /// GGA fix quality indicator: 1 GPS, 2 DGPS, 4 RTK fixed, 5 RTK float, 6 dead reckoning, ...
+    pub fix_quality: Option<u8>,
+
+    /// Restored from the flash log at boot rather than received; shown until a live fix replaces
+    /// it, but never reported
+    pub is_stale: bool,
+}
+
+impl GnssPositioning {
+    /// Speed over ground for presentation, with drift below the noise floor reported as zero
+    ///
+    /// The stored `speed` stays as the receiver reported it.
+    pub fn speed_knots(&self) -> Option<f32> {
+        self.speed.map(|speed| {
+            if speed < SPEED_NOISE_FLOOR_KNOTS {
+                0.0
+            } else {
+                speed
+            }
+        })
+    }
+
+    /// Same as `speed_knots`, in km/h
+    pub fn speed_kmh(&self) -> Option<f32> {
+        self.speed_knots().map(|speed| speed * KMH_PER_KNOT)
+    }
+
+    /// Fill in what RMC doesn't carry from the latest GGA
+    ///
+    /// Missing fields stay `None` rather than defaulting to zero, which would be a plausible
+    /// altitude.
+    pub fn merge_gga(&mut self, gga: &GgaData) {
+        self.altitude = gga.altitude;
+        self.satellites = gga.fix_satellites;
+        self.fix_quality = gga.fix_type.map(fix_quality);
+    }
+
+    /// Altitude rounded to whole metres for display, keeping the sign below sea level
+    ///
+    /// Rounding first means values just under zero show as `0` rather than `-0`.
+    pub fn altitude_metres(&self) -> Option<i32> {
+        self.altitude.map(|altitude| {
+            if altitude >= 0.0 {
+                (altitude + 0.5) as i32
+            } else {
+                (altitude - 0.5) as i32
+            }
+        })
+    }
+
+    /// Pack the fix for the BLE `telemetry` characteristic
+    ///
+    /// All multi-byte fields are little-endian. A field whose flag is clear is zeroed.
+    ///
+    /// | Offset | Type  | Field                                                 |
+    /// |--------|-------|-------------------------------------------------------|
+    /// | 0      | `i32` | Latitude, 1e-7 degree                                 |
+    /// | 4      | `i32` | Longitude, 1e-7 degree                                |
+    /// | 8      | `u16` | Speed over ground, cm/s (bit 1)                       |
+    /// | 10     | `u16` | Heading, 0.01 degree (bit 2)                          |
+    /// | 12     | `i16` | Altitude above sea level, m (bit 3)                   |
+    /// | 14     | `u8`  | Satellites in use, 255 meaning 255 or more (bit 4)    |
+    /// | 15     | `u8`  | Flags: bit 0 valid fix, bits 1-4 as noted, rest zero  |
+    /// | 16     | `u32` | Fix time, Unix seconds                                |
+    /// | 20     | -     | Reserved, zero                                        |
+    ///
+    /// Bit 0 is always set here; a characteristic still all zeroes means no fix yet.
+    pub fn to_ble_bytes(&self) -> [u8; BLE_TELEMETRY_SIZE] {
+        let mut bytes = [0u8; BLE_TELEMETRY_SIZE];
+        let mut flags = BLE_FLAG_FIX;
+
+        bytes[0..4].copy_from_slice(&encode_coordinate(self.latitude));
+        bytes[4..8].copy_from_slice(&encode_coordinate(self.longitude));
+
+        if let Some(speed) = self.speed {
+            let speed = round(speed as f64 * CM_PER_S_PER_KNOT).clamp(0.0, u16::MAX as f64);
+            bytes[8..10].copy_from_slice(&(speed as u16).to_le_bytes());
+            flags |= BLE_FLAG_SPEED;
+        }
+        if let Some(heading) = self.heading {
+            let heading = round(heading as f64 * 100.0).clamp(0.0, u16::MAX as f64);
+            bytes[10..12].copy_from_slice(&(heading as u16).to_le_bytes());
+            flags |= BLE_FLAG_HEADING;
+        }
+        if let Some(altitude) = self.altitude {
+            let altitude = round(altitude as f64).clamp(i16::MIN as f64, i16::MAX as f64);
+            bytes[12..14].copy_from_slice(&(altitude as i16).to_le_bytes());
+            flags |= BLE_FLAG_ALTITUDE;
+        }
+        if let Some(satellites) = self.satellites {
+            bytes[14] = satellites.min(u8::MAX as u32) as u8;
+            flags |= BLE_FLAG_SATELLITES;
+        }
+        bytes[15] = flags;
+
+        let timestamp = u32::try_from(self.datetime.and_utc().timestamp()).unwrap_or_default();
+        bytes[16..20].copy_from_slice(&timestamp.to_le_bytes());
+
+        bytes
+    }
+
+    /// Inverse of `to_ble_bytes`, to the precision of the wire format; `None` without a fix
+    pub fn from_ble_bytes(bytes: &[u8; BLE_TELEMETRY_SIZE]) -> Option<Self> {
+        let flags = bytes[15];
+        if flags & BLE_FLAG_FIX == 0 {
+            return None;
+        }
+
+        let field = |flag: u8| flags & flag != 0;
+        let speed = u16::from_le_bytes([bytes[8], bytes[9]]);
+        let heading = u16::from_le_bytes([bytes[10], bytes[11]]);
+        let altitude = i16::from_le_bytes([bytes[12], bytes[13]]);
+        let timestamp = u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
+
+        Some(Self {
+            datetime: DateTime::from_timestamp(timestamp as i64, 0)?.naive_utc(),
+            latitude: decode_coordinate([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            longitude: decode_coordinate([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            speed: field(BLE_FLAG_SPEED).then(|| (speed as f64 / CM_PER_S_PER_KNOT) as f32),
+            heading: field(BLE_FLAG_HEADING).then(|| heading as f32 / 100.0),
+            altitude: field(BLE_FLAG_ALTITUDE).then_some(altitude as f32),
+            satellites: field(BLE_FLAG_SATELLITES).then_some(bytes[14] as u32),
+            fix_quality: None,
+            is_stale: false,
+        })
+    }
+}
+
+/// The indicator digit GGA carries for `fix_type`
+fn fix_quality(fix_type: FixType) -> u8 {
+    match fix_type {
+        FixType::Invalid => 0,
+        FixType::Gps => 1,
+        FixType::DGps => 2,
+        FixType::Pps => 3,
+        FixType::Rtk => 4,
+        FixType::FloatRtk => 5,
+        FixType::Estimated => 6,
+        FixType::Manual => 7,
+        FixType::Simulation => 8,
+    }
 }
 
 impl TryFrom<ParseResult> for GnssPositioning {
@@ -46,6 +212,176 @@ impl TryFrom<ParseResult> for GnssPositioning {
             longitude,
             speed: rmc.speed_over_ground,
             heading: rmc.true_course,
+            altitude: None,
+            satellites: None,
+            fix_quality: None,
+            is_stale: false,
         })
     }
 }
+
+/// A fix `second`s past noon on 2025-03-01 at the given coordinates, and nothing else known
+#[cfg(test)]
+pub(crate) fn positioning_at(second: u32, latitude: f64, longitude: f64) -> GnssPositioning {
+    GnssPositioning {
+        datetime: chrono::NaiveDate::from_ymd_opt(2025, 3, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, second)
+            .unwrap(),
+        latitude,
+        longitude,
+        speed: None,
+        heading: None,
+        altitude: None,
+        satellites: None,
+        fix_quality: None,
+        is_stale: false,
+    }
+}
+
+/// Fail unless `actual` is within `tolerance` of `expected`
+#[cfg(test)]
+pub(crate) fn assert_close<T: Into<f64>>(actual: T, expected: T, tolerance: T) {
+    let (actual, expected) = (actual.into(), expected.into());
+    assert!(
+        (actual - expected).abs() < tolerance.into(),
+        "{} != {}",
+        actual,
+        expected
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn positioning_with_speed(speed: Option<f32>) -> GnssPositioning {
+        GnssPositioning {
+            speed,
+            ..positioning_at(0, 40.17764, 44.51255)
+        }
+    }
+
+    #[test]
+    fn test_speed_below_noise_floor_is_zero() {
+        assert_eq!(positioning_with_speed(Some(0.3)).speed_knots(), Some(0.0));
+        assert_eq!(positioning_with_speed(Some(0.49)).speed_kmh(), Some(0.0));
+    }
+
+    #[test]
+    fn test_speed_at_and_above_noise_floor_is_kept() {
+        assert_eq!(
+            positioning_with_speed(Some(SPEED_NOISE_FLOOR_KNOTS)).speed_knots(),
+            Some(SPEED_NOISE_FLOOR_KNOTS)
+        );
+        assert_eq!(positioning_with_speed(Some(0.51)).speed_knots(), Some(0.51));
+        assert_eq!(positioning_with_speed(Some(10.0)).speed_kmh(), Some(18.52));
+    }
+
+    #[test]
+    fn test_noise_floor_leaves_stored_speed_untouched() {
+        let positioning = positioning_with_speed(Some(0.3));
+
+        assert_eq!(positioning.speed, Some(0.3));
+        assert_eq!(positioning_with_speed(None).speed_kmh(), None);
+    }
+
+    #[test]
+    fn test_negative_altitude_survives_gga_merge() {
+        // Badwater Basin, Death Valley
+        let parsed = nmea::parse_str(
+            "$GPGGA,120000.00,3613.8820,N,11649.2770,W,1,08,0.9,-86.0,M,-31.0,M,,*4C",
+        )
+        .unwrap();
+        let nmea::ParseResult::GGA(gga) = parsed else {
+            panic!("expected a GGA sentence");
+        };
+
+        let mut positioning = positioning_with_speed(None);
+        positioning.merge_gga(&gga);
+
+        assert_eq!(positioning.altitude, Some(-86.0));
+        assert_eq!(positioning.altitude_metres(), Some(-86));
+        assert_eq!(positioning.fix_quality, Some(1));
+    }
+
+    #[test]
+    fn test_multi_constellation_rmc() {
+        // As a u-blox receiver tracking GPS and GLONASS sends it
+        let parsed = nmea::parse_str(
+            "$GNRMC,083559.00,A,4717.11437,N,00833.91522,E,0.004,77.52,091202,,,A*49",
+        )
+        .unwrap();
+
+        let positioning = GnssPositioning::try_from(parsed).unwrap();
+        assert_eq!(
+            positioning.datetime,
+            NaiveDate::from_ymd_opt(2002, 12, 9)
+                .unwrap()
+                .and_hms_opt(8, 35, 59)
+                .unwrap()
+        );
+        assert!((positioning.latitude - 47.285_239_5).abs() < 1e-6);
+        assert!((positioning.longitude - 8.565_253_7).abs() < 1e-6);
+        assert_eq!(positioning.heading, Some(77.52));
+    }
+
+    #[test]
+    fn test_ble_bytes_round_trip() {
+        let mut positioning = positioning_with_speed(Some(12.5));
+        positioning.heading = Some(271.35);
+        positioning.altitude = Some(-86.0);
+        positioning.satellites = Some(9);
+
+        let bytes = positioning.to_ble_bytes();
+        assert_eq!(bytes[15], 0b1_1111);
+        assert_eq!(&bytes[20..], &[0; 4]);
+
+        let decoded = GnssPositioning::from_ble_bytes(&bytes).unwrap();
+        assert_eq!(decoded.datetime, positioning.datetime);
+        assert!((decoded.latitude - positioning.latitude).abs() < 1e-7);
+        assert!((decoded.longitude - positioning.longitude).abs() < 1e-7);
+        assert!((decoded.speed.unwrap() - 12.5).abs() < 0.01);
+        assert!((decoded.heading.unwrap() - 271.35).abs() < 0.01);
+        assert_eq!(decoded.altitude, Some(-86.0));
+        assert_eq!(decoded.satellites, Some(9));
+    }
+
+    #[test]
+    fn test_ble_bytes_missing_fields() {
+        let positioning = positioning_with_speed(None);
+
+        let bytes = positioning.to_ble_bytes();
+        assert_eq!(bytes[15], 0b1);
+        assert_eq!(&bytes[8..15], &[0; 7]);
+
+        let decoded = GnssPositioning::from_ble_bytes(&bytes).unwrap();
+        assert_eq!(decoded.speed, None);
+        assert_eq!(decoded.heading, None);
+        assert_eq!(decoded.altitude, None);
+        assert_eq!(decoded.satellites, None);
+    }
+
+    #[test]
+    fn test_ble_bytes_without_fix() {
+        assert_eq!(
+            GnssPositioning::from_ble_bytes(&[0; BLE_TELEMETRY_SIZE]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_altitude_rounding_keeps_sign() {
+        let mut positioning = positioning_with_speed(None);
+
+        positioning.altitude = Some(-0.4);
+        assert_eq!(positioning.altitude_metres(), Some(0));
+
+        positioning.altitude = Some(-12.6);
+        assert_eq!(positioning.altitude_metres(), Some(-13));
+
+        positioning.altitude = None;
+        assert_eq!(positioning.altitude_metres(), None);
+    }
+}
@@ -0,0 +1,119 @@
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::lora::packet::round;
+
+/// Decimal places used unless configured otherwise; 1e-5 degree is ~1.1 m of latitude
+pub const DEFAULT_DECIMALS: u8 = 5;
+
+/// Finest precision worth asking for, that of the 4-byte fixed-point coordinates
+pub const MAX_DECIMALS: u8 = 7;
+
+/// Decimal places of the coordinate precision in use, see `CoordinatePrecision::current`
+///
+/// Set over BLE through `LoraCommand::Reconfigure`, and kept across resets along with the rest
+/// of the `RadioSettings`.
+pub static COORDINATE_PRECISION: AtomicU8 = AtomicU8::new(DEFAULT_DECIMALS);
+
+/// How many decimal places of a degree coordinates are shown and sent with
+///
+/// Only presentation and serialization are affected; fixes keep full precision. The compact
+/// report is a fixed 3 bytes per coordinate at ~1.2 m resolution, so precision beyond 4 decimals
+/// is lost there anyway, and a coarser setting makes the reported position coarser without
+/// shortening the packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoordinatePrecision(u8);
+
+impl Default for CoordinatePrecision {
+    fn default() -> Self {
+        Self(DEFAULT_DECIMALS)
+    }
+}
+
+impl CoordinatePrecision {
+    /// `None` for more than `MAX_DECIMALS` places
+    pub const fn new(decimals: u8) -> Option<Self> {
+        if decimals > MAX_DECIMALS {
+            return None;
+        }
+
+        Some(Self(decimals))
+    }
+
+    /// The precision set in `COORDINATE_PRECISION`
+    pub fn current() -> Self {
+        Self::new(COORDINATE_PRECISION.load(Ordering::Relaxed)).unwrap_or_default()
+    }
+
+    pub const fn decimals(self) -> u8 {
+        self.0
+    }
+
+    /// `degrees` rounded, half away from zero, to this many decimal places
+    pub fn quantize(self, degrees: f64) -> f64 {
+        let scale = 10u32.pow(self.0 as u32) as f64;
+
+        round(degrees * scale) / scale
+    }
+
+    /// `degrees` formatted with exactly this many decimal places, agreeing with `quantize`
+    pub fn display(self, degrees: f64) -> Coordinate {
+        Coordinate {
+            degrees,
+            precision: self,
+        }
+    }
+}
+
+/// A coordinate formatted to a `CoordinatePrecision`, see `CoordinatePrecision::display`
+pub struct Coordinate {
+    degrees: f64,
+    precision: CoordinatePrecision,
+}
+
+impl fmt::Display for Coordinate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Quantizing first keeps the formatter's own rounding out of it
+        write!(
+            f,
+            "{:.*}",
+            self.precision.0 as usize,
+            self.precision.quantize(self.degrees)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    fn precision(decimals: u8) -> CoordinatePrecision {
+        CoordinatePrecision::new(decimals).unwrap()
+    }
+
+    #[test]
+    fn test_quantize_rounds_rather_than_truncates() {
+        assert!((precision(5).quantize(40.177_649) - 40.177_65).abs() < 1e-9);
+        assert!((precision(5).quantize(40.177_641) - 40.177_64).abs() < 1e-9);
+        assert!((precision(3).quantize(-44.512_6) - -44.513).abs() < 1e-9);
+
+        // Half away from zero on both sides of the equator and meridian
+        assert_eq!(precision(0).quantize(-44.5), -45.0);
+        assert_eq!(precision(0).quantize(44.5), 45.0);
+    }
+
+    #[test]
+    fn test_display_matches_precision() {
+        assert_eq!(precision(5).display(40.177_649).to_string(), "40.17765");
+        assert_eq!(precision(3).display(-44.512_6).to_string(), "-44.513");
+        assert_eq!(precision(0).display(44.6).to_string(), "45");
+        assert_eq!(precision(7).display(1.0).to_string(), "1.0000000");
+    }
+
+    #[test]
+    fn test_precision_is_bounded() {
+        assert_eq!(CoordinatePrecision::new(MAX_DECIMALS + 1), None);
+        assert_eq!(CoordinatePrecision::default().decimals(), DEFAULT_DECIMALS);
+    }
+}
@@ -0,0 +1,172 @@
+use heapless::Vec;
+use nmea::sentences::GsvData;
+use nmea::GnssType;
+
+/// Satellites kept across all constellations; a multi-GNSS receiver rarely has more in view
+pub const MAX_SATELLITES: usize = 32;
+
+/// One satellite as the latest GSV group described it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SatelliteInfo {
+    pub constellation: GnssType,
+    pub prn: u32,
+
+    /// Degrees above the horizon
+    pub elevation: Option<f32>,
+
+    /// Degrees from true north
+    pub azimuth: Option<f32>,
+
+    /// Carrier-to-noise density in dB-Hz; `None` while the satellite is in view but not tracked
+    pub snr: Option<f32>,
+}
+
+/// The sky as the receiver last reported it, one entry per satellite in view
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GnssSatellites {
+    pub satellites: Vec<SatelliteInfo, MAX_SATELLITES>,
+}
+
+impl GnssSatellites {
+    pub fn in_view(&self) -> usize {
+        self.satellites.len()
+    }
+
+    /// Satellites the receiver has a signal from, whether or not it uses them for the fix
+    pub fn tracked(&self) -> usize {
+        self.satellites
+            .iter()
+            .filter(|satellite| satellite.snr.is_some())
+            .count()
+    }
+}
+
+/// Collects GSV sentences into `GnssSatellites`
+///
+/// A receiver describes the sky in a group of GSV sentences per constellation, four satellites
+/// to a sentence. Each complete group replaces what the previous one said about that
+/// constellation; a group with a sentence missing or out of order is dropped, leaving the
+/// previous one in place.
+#[derive(Default)]
+pub struct SatelliteAccumulator {
+    sky: GnssSatellites,
+
+    /// Satellites of the group in progress, and the sentence number expected next
+    pending: Vec<SatelliteInfo, MAX_SATELLITES>,
+    pending_group: Option<(GnssType, u16)>,
+}
+
+impl SatelliteAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add one GSV sentence, returning the updated sky when it completes a group
+    pub fn feed(&mut self, gsv: &GsvData) -> Option<&GnssSatellites> {
+        if gsv.sentence_num == 1 {
+            self.pending.clear();
+            self.pending_group = Some((gsv.gnss_type, 1));
+        }
+
+        if self.pending_group != Some((gsv.gnss_type, gsv.sentence_num)) {
+            self.pending_group = None;
+            return None;
+        }
+
+        for satellite in gsv.sats_info.iter().flatten() {
+            // Beyond `MAX_SATELLITES` the rest of the group is left out
+            let _ = self.pending.push(SatelliteInfo {
+                constellation: satellite.gnss_type(),
+                prn: satellite.prn(),
+                elevation: satellite.elevation(),
+                azimuth: satellite.azimuth(),
+                snr: satellite.snr(),
+            });
+        }
+
+        if gsv.sentence_num < gsv.number_of_sentences {
+            self.pending_group = Some((gsv.gnss_type, gsv.sentence_num + 1));
+            return None;
+        }
+        self.pending_group = None;
+
+        self.sky
+            .satellites
+            .retain(|satellite| satellite.constellation != gsv.gnss_type);
+        for &satellite in &self.pending {
+            if self.sky.satellites.push(satellite).is_err() {
+                break;
+            }
+        }
+
+        Some(&self.sky)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nmea::ParseResult;
+
+    fn gsv(sentence: &str) -> GsvData {
+        let ParseResult::GSV(gsv) = nmea::parse_str(sentence).unwrap() else {
+            panic!("expected a GSV sentence");
+        };
+
+        gsv
+    }
+
+    const GPS_GROUP: [&str; 2] = [
+        "$GPGSV,2,1,06,02,38,090,42,05,12,300,,12,71,045,38,15,22,210,31*75",
+        "$GPGSV,2,2,06,24,05,160,,25,47,270,29*71",
+    ];
+
+    #[test]
+    fn test_group_completes_on_last_sentence() {
+        let mut accumulator = SatelliteAccumulator::new();
+
+        assert!(accumulator.feed(&gsv(GPS_GROUP[0])).is_none());
+        let sky = accumulator.feed(&gsv(GPS_GROUP[1])).unwrap();
+
+        assert_eq!(sky.in_view(), 6);
+        assert_eq!(sky.tracked(), 4);
+        assert_eq!(sky.satellites[0].prn, 2);
+        assert_eq!(sky.satellites[0].snr, Some(42.0));
+        assert_eq!(sky.satellites[1].snr, None);
+    }
+
+    #[test]
+    fn test_constellations_are_kept_separately() {
+        let mut accumulator = SatelliteAccumulator::new();
+        accumulator.feed(&gsv(GPS_GROUP[0]));
+        accumulator.feed(&gsv(GPS_GROUP[1]));
+
+        let sky = accumulator
+            .feed(&gsv("$GLGSV,1,1,02,65,30,100,35,66,10,200,*63"))
+            .unwrap();
+        assert_eq!(sky.in_view(), 8);
+        assert_eq!(sky.tracked(), 5);
+
+        // A new GPS group replaces only the GPS satellites
+        let sky = accumulator
+            .feed(&gsv("$GPGSV,1,1,01,02,38,090,44*48"))
+            .unwrap();
+        assert_eq!(sky.in_view(), 3);
+        assert_eq!(sky.satellites[2].snr, Some(44.0));
+    }
+
+    #[test]
+    fn test_incomplete_group_is_dropped() {
+        let mut accumulator = SatelliteAccumulator::new();
+
+        // Second sentence without the first
+        assert!(accumulator.feed(&gsv(GPS_GROUP[1])).is_none());
+
+        // First sentence followed by another constellation's
+        assert!(accumulator.feed(&gsv(GPS_GROUP[0])).is_none());
+        assert!(accumulator
+            .feed(&gsv("$GLGSV,2,2,05,65,30,100,35*57"))
+            .is_none());
+        assert!(accumulator.feed(&gsv(GPS_GROUP[1])).is_none());
+    }
+}
@@ -1,19 +1,29 @@
 use core::str;
 use core::str::Utf8Error;
 
-const MAX_NMEA_SENTENCE_SIZE: usize = 128;
+use super::command::nmea_checksum;
 
-type Buffer = [u8; MAX_NMEA_SENTENCE_SIZE];
+/// Default sentence capacity; the NMEA 0183 limit is 82 characters, proprietary sentences and
+/// GSV from receivers tracking many satellites can run longer
+pub const MAX_NMEA_SENTENCE_SIZE: usize = 128;
 
+/// Assembles sentences of up to `N` bytes, from `$` through the checksum, excluding CR LF
+///
+/// A longer sentence is dropped whole, and collecting starts over at the next `$`, so it can't
+/// take the sentences after it down with it. A sentence whose checksum doesn't match its
+/// contents is dropped as well.
 #[derive(Debug)]
-pub struct SentenceBuffer {
+pub struct SentenceBuffer<const N: usize = MAX_NMEA_SENTENCE_SIZE> {
     cursor: usize,
-    buffer: Buffer,
+    buffer: [u8; N],
 
     state: ParseState,
+
+    /// Whether a sentence ending before a `*` is dropped rather than handed out as it is
+    require_checksum: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 enum ParseState {
     /// Waiting for a start-of-sentence marker
     Waiting,
@@ -31,24 +41,36 @@ enum ParseState {
     Complete,
 }
 
-impl SentenceBuffer {
+impl<const N: usize> SentenceBuffer<N> {
     pub fn new() -> Self {
         Self {
             cursor: 0,
-            buffer: [0; MAX_NMEA_SENTENCE_SIZE],
+            buffer: [0; N],
 
             state: ParseState::Waiting,
+
+            require_checksum: true,
         }
     }
 
-    fn push_byte(&mut self, byte: u8) {
+    /// Also hand out sentences that end without a checksum, as some receivers send their
+    /// proprietary ones; they're only checked for a `$` start and a CR or LF end
+    pub fn set_require_checksum(&mut self, require_checksum: bool) {
+        self.require_checksum = require_checksum;
+    }
+
+    /// Append `byte`, or drop the whole sentence if it doesn't fit; `false` if dropped
+    fn push_byte(&mut self, byte: u8) -> bool {
         if self.cursor >= self.buffer.len() {
-            // When overflown, reset the the buffer
+            // Too long to ever complete; drop it and wait for the next sentence
             self.reset("Buffer overflow");
+            return false;
         }
 
         self.buffer[self.cursor] = byte;
         self.cursor += 1;
+
+        true
     }
 
     pub fn as_string(&self) -> Result<&str, Utf8Error> {
@@ -70,15 +92,21 @@ impl SentenceBuffer {
                 match byte {
                     b'*' => {
                         // Transition to checksum state
-                        self.push_byte(byte);
-                        self.state = ParseState::InChecksum { count: 0 };
+                        if self.push_byte(byte) {
+                            self.state = ParseState::InChecksum { count: 0 };
+                        }
+                    }
+
+                    b'\r' | b'\n' if !self.require_checksum => {
+                        // Sentence termination without a checksum, which is allowed
+                        return self.complete();
                     }
 
                     b'\r' => {
                         // Sentence termination without a checksum
                         defmt::warn!(
                             "Sentence terminated without checksum: {}",
-                            self.as_string().unwrap()
+                            self.as_string().unwrap_or("<invalid UTF-8>")
                         );
                         self.reset("Sentence terminated without checksum");
                     }
@@ -94,10 +122,21 @@ impl SentenceBuffer {
 
             ParseState::InChecksum { count } => {
                 // Expecting exactly two hexadecimal digits
-                self.push_byte(byte);
+                if !self.push_byte(byte) {
+                    return None;
+                }
 
                 let new_count = count + 1;
                 if new_count == 2 {
+                    if !self.checksum_matches() {
+                        defmt::warn!(
+                            "Sentence checksum mismatch: {}",
+                            self.as_string().unwrap_or("<invalid UTF-8>")
+                        );
+                        self.reset("Checksum mismatch");
+                        return None;
+                    }
+
                     // After two hex digits, transition to termination state
                     self.state = ParseState::Terminating;
                 } else {
@@ -108,21 +147,7 @@ impl SentenceBuffer {
             ParseState::Terminating => {
                 match byte {
                     // NL finishes the sentence
-                    b'\n' => {
-                        // Check if this is an RMC sentence
-                        // if self.buffer.starts_with(b"$GPRMC") {
-                        // Transition to Complete state and return the sentence
-                        self.state = ParseState::Complete;
-
-                        if let Ok(sentence_str) = self.as_string() {
-                            return Some(sentence_str);
-                        } else {
-                            defmt::warn!("Invalid UTF-8 in NMEA sentence");
-                        }
-                        // } else {
-                        //     self.reset("Not an RMC sentence");
-                        // }
-                    }
+                    b'\n' => return self.complete(),
 
                     // Ignore CR
                     b'\r' => {}
@@ -133,13 +158,34 @@ impl SentenceBuffer {
             }
 
             ParseState::Complete => {
-                // This should never be reached due to the reset at the start of the function
-                self.reset("ParseState::Complete state reached");
+                // The last sentence has been handed out; this byte may already start the next one
+                self.reset("Previous sentence complete");
+                return self.feed(byte);
             }
         }
         None
     }
 
+    /// Whether the two hex digits just collected match the XOR of the bytes between `$` and `*`
+    fn checksum_matches(&self) -> bool {
+        let (payload, digits) = self.buffer[1..self.cursor].split_at(self.cursor - 4);
+
+        digits[1..].eq_ignore_ascii_case(&nmea_checksum(payload))
+    }
+
+    /// Mark the sentence collected so far complete and hand it out
+    fn complete(&mut self) -> Option<&str> {
+        self.state = ParseState::Complete;
+
+        match self.as_string() {
+            Ok(sentence_str) => Some(sentence_str),
+            Err(_) => {
+                defmt::warn!("Invalid UTF-8 in NMEA sentence");
+                None
+            }
+        }
+    }
+
     pub fn reset(&mut self, reason: &str) {
         self.cursor = 0;
         self.buffer.fill(0);
@@ -154,8 +200,161 @@ impl SentenceBuffer {
 mod tests {
     use super::*;
 
+    fn feed_all<const N: usize>(buffer: &mut SentenceBuffer<N>, bytes: &[u8]) -> Option<String> {
+        bytes
+            .iter()
+            .filter_map(|&byte| buffer.feed(byte).map(String::from))
+            .last()
+    }
+
+    #[test]
+    fn test_sentence_at_capacity() {
+        let mut buffer = SentenceBuffer::<16>::new();
+
+        assert_eq!(
+            feed_all(&mut buffer, b"$ABCDEFGHIJKL*0C\r\n").as_deref(),
+            Some("$ABCDEFGHIJKL*0C")
+        );
+    }
+
+    #[test]
+    fn test_sentence_over_capacity_dropped() {
+        let mut buffer = SentenceBuffer::<16>::new();
+
+        assert_eq!(feed_all(&mut buffer, b"$ABCDEFGHIJKLM*00\r\n"), None);
+
+        // The next sentence is unaffected
+        assert_eq!(
+            feed_all(&mut buffer, b"$GPTXT,01*62\r\n").as_deref(),
+            Some("$GPTXT,01*62")
+        );
+    }
+
+    #[test]
+    fn test_unterminated_run_never_overflows() {
+        let mut buffer = SentenceBuffer::<16>::new();
+
+        assert_eq!(buffer.feed(b'$'), None);
+        for _ in 0..200 {
+            assert_eq!(buffer.feed(b'A'), None);
+            assert!(buffer.cursor <= buffer.buffer.len());
+        }
+
+        // Dropped whole, with none of the run carried into the next sentence
+        assert_eq!(buffer.state, ParseState::Waiting);
+        assert_eq!(buffer.as_string(), Ok(""));
+        assert_eq!(
+            feed_all(&mut buffer, b"$GPTXT,01*62\r\n").as_deref(),
+            Some("$GPTXT,01*62")
+        );
+    }
+
     #[test]
-    fn test_gps_sentence_validity() {
-        assert!(true);
+    fn test_state_transitions() {
+        let mut buffer: SentenceBuffer = SentenceBuffer::new();
+        assert_eq!(buffer.state, ParseState::Waiting);
+
+        let expected = [
+            (b'x', ParseState::Waiting),
+            (b'$', ParseState::Collecting),
+            (b'G', ParseState::Collecting),
+            (b'*', ParseState::InChecksum { count: 0 }),
+            (b'4', ParseState::InChecksum { count: 1 }),
+            (b'7', ParseState::Terminating),
+            (b'\r', ParseState::Terminating),
+        ];
+        for (byte, state) in expected {
+            assert_eq!(buffer.feed(byte), None);
+            assert_eq!(buffer.state, state, "after {:?}", byte as char);
+        }
+
+        assert_eq!(buffer.feed(b'\n'), Some("$G*47"));
+        assert_eq!(buffer.state, ParseState::Complete);
+    }
+
+    #[test]
+    fn test_back_to_back_sentences() {
+        let mut buffer: SentenceBuffer = SentenceBuffer::new();
+
+        assert_eq!(
+            feed_all(&mut buffer, b"$GPTXT,01*62\r\n").as_deref(),
+            Some("$GPTXT,01*62")
+        );
+
+        // The `$` right after a completed sentence starts the next one
+        assert_eq!(buffer.feed(b'$'), None);
+        assert_eq!(buffer.state, ParseState::Collecting);
+        assert_eq!(
+            feed_all(&mut buffer, b"GPTXT,02*61\r\n").as_deref(),
+            Some("$GPTXT,02*61")
+        );
+    }
+
+    #[test]
+    fn test_terminated_without_checksum_resets() {
+        let mut buffer: SentenceBuffer = SentenceBuffer::new();
+
+        assert_eq!(feed_all(&mut buffer, b"$GPTXT,01\r"), None);
+        assert_eq!(buffer.state, ParseState::Waiting);
+        assert_eq!(buffer.as_string(), Ok(""));
+
+        // The LF that follows doesn't resurrect it
+        assert_eq!(buffer.feed(b'\n'), None);
+        assert_eq!(buffer.state, ParseState::Waiting);
+    }
+
+    #[test]
+    fn test_sentence_without_checksum_when_allowed() {
+        let mut buffer: SentenceBuffer = SentenceBuffer::new();
+        buffer.set_require_checksum(false);
+
+        assert_eq!(
+            feed_all(&mut buffer, b"$PUBX,00\r\n").as_deref(),
+            Some("$PUBX,00")
+        );
+
+        // LF alone ends it too, and sentences with a checksum are unaffected
+        assert_eq!(
+            feed_all(&mut buffer, b"$PUBX,00\n").as_deref(),
+            Some("$PUBX,00")
+        );
+        assert_eq!(
+            feed_all(&mut buffer, b"$GPTXT,01*62\r\n").as_deref(),
+            Some("$GPTXT,01*62")
+        );
+    }
+
+    #[test]
+    fn test_checksum_mismatch_dropped() {
+        let mut buffer: SentenceBuffer = SentenceBuffer::new();
+
+        assert_eq!(feed_all(&mut buffer, b"$GPTXT,01*63\r\n"), None);
+        assert_eq!(buffer.state, ParseState::Waiting);
+
+        // Lowercase hex digits are as good as uppercase
+        assert_eq!(
+            feed_all(&mut buffer, b"$GPTXT,09*6a\r\n").as_deref(),
+            Some("$GPTXT,09*6a")
+        );
+    }
+
+    #[test]
+    fn test_unexpected_byte_while_terminating_resets() {
+        let mut buffer: SentenceBuffer = SentenceBuffer::new();
+
+        assert_eq!(feed_all(&mut buffer, b"$GPTXT,01*62"), None);
+        assert_eq!(buffer.state, ParseState::Terminating);
+
+        assert_eq!(buffer.feed(b'X'), None);
+        assert_eq!(buffer.state, ParseState::Waiting);
+        assert_eq!(buffer.feed(b'\n'), None);
+        assert_eq!(buffer.as_string(), Ok(""));
+    }
+
+    #[test]
+    fn test_default_capacity() {
+        let buffer: SentenceBuffer = SentenceBuffer::new();
+
+        assert_eq!(buffer.buffer.len(), MAX_NMEA_SENTENCE_SIZE);
     }
 }
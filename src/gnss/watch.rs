@@ -1,9 +1,60 @@
+use crate::gnss::history::PositionHistory;
 use crate::gnss::positioning::GnssPositioning;
+use crate::gnss::satellites::GnssSatellites;
+use crate::gnss::sentence::MAX_NMEA_SENTENCE_SIZE;
+use core::cell::{Cell, RefCell};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::channel::Channel;
 use embassy_sync::watch::Watch;
+use embassy_time::{Duration, Instant};
 
+/// Consumers of `GNSS_WATCH`: the app state aggregator, `record_history`, the flash log and BLE
+/// telemetry
 pub const WATCH_BUFFER_SIZE: usize = 4;
 
+/// Sentences buffered for the raw passthrough before new ones get dropped
+pub const PASSTHROUGH_QUEUE_SIZE: usize = 4;
+
+/// A complete NMEA sentence as received, including the trailing CR LF
+pub type RawSentence = heapless::Vec<u8, { MAX_NMEA_SENTENCE_SIZE + 2 }>;
+
+/// Whether raw sentences should be forwarded at all; off by default to keep the link quiet
+pub static NMEA_PASSTHROUGH_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Raw sentences on their way to whoever streams them out (BLE)
+pub static NMEA_PASSTHROUGH: Channel<CriticalSectionRawMutex, RawSentence, PASSTHROUGH_QUEUE_SIZE> =
+    Channel::new();
+
+/// Times the UART receive FIFO overflowed and buffered bytes had to be thrown away
+pub static UART_OVERFLOW_COUNT: AtomicU32 = AtomicU32::new(0);
+
+/// Sentences dropped from the passthrough because the consumer fell behind
+pub static DROPPED_SENTENCE_COUNT: AtomicU32 = AtomicU32::new(0);
+
+/// Queue a raw sentence for passthrough if it's enabled
+///
+/// Never blocks: when the consumer falls behind the sentence is dropped, so a slow link can't
+/// stall the GNSS task.
+pub fn forward_raw_sentence(sentence: &str) {
+    if !NMEA_PASSTHROUGH_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let mut raw = RawSentence::new();
+    if raw.extend_from_slice(sentence.as_bytes()).is_err()
+        || raw.extend_from_slice(b"\r\n").is_err()
+    {
+        return;
+    }
+
+    if NMEA_PASSTHROUGH.try_send(raw).is_err() {
+        DROPPED_SENTENCE_COUNT.fetch_add(1, Ordering::Relaxed);
+        defmt::debug!("Passthrough queue full, dropping sentence");
+    }
+}
+
 // Static channel for latest positioning data
 pub static GNSS_WATCH: Watch<CriticalSectionRawMutex, Option<GnssPositioning>, WATCH_BUFFER_SIZE> =
     Watch::new();
@@ -21,3 +72,92 @@ pub type GnssStateTx = embassy_sync::watch::Sender<
     Option<GnssPositioning>,
     WATCH_BUFFER_SIZE,
 >;
+
+/// Consumers of `GNSS_SATELLITES`, such as a signal-quality screen
+const SATELLITES_RECEIVERS: usize = 1;
+
+/// Satellites in view, republished whenever the receiver finishes a GSV group
+///
+/// Arrives independently of `GNSS_WATCH`, and keeps updating without a fix.
+pub static GNSS_SATELLITES: Watch<CriticalSectionRawMutex, GnssSatellites, SATELLITES_RECEIVERS> =
+    Watch::new();
+
+/// When the driver last published a valid fix
+static LAST_FIX_AT: Mutex<CriticalSectionRawMutex, Cell<Option<Instant>>> =
+    Mutex::new(Cell::new(None));
+
+/// UTC time of the latest valid fix in milliseconds since the Unix epoch, and when it arrived
+static GPS_TIME_REFERENCE: Mutex<CriticalSectionRawMutex, Cell<Option<(u64, Instant)>>> =
+    Mutex::new(Cell::new(None));
+
+/// Note that `positioning` has just been published on `GNSS_WATCH` as a valid fix
+pub fn record_fix(positioning: &GnssPositioning) {
+    let now = Instant::now();
+    let gps_time_ms = positioning.datetime.and_utc().timestamp_millis();
+
+    LAST_FIX_AT.lock(|last_fix_at| last_fix_at.set(Some(now)));
+    if let Ok(gps_time_ms) = u64::try_from(gps_time_ms) {
+        GPS_TIME_REFERENCE.lock(|reference| reference.set(Some((gps_time_ms, now))));
+    }
+}
+
+/// Current UTC time in milliseconds since the Unix epoch, as far as GPS has told us
+///
+/// Extrapolated from the latest fix with the local monotonic clock, so it's only as accurate as
+/// the delay between the receiver timestamping a fix and the driver parsing it, typically a few
+/// hundred milliseconds. `None` until the first valid fix.
+pub fn gps_time_ms() -> Option<u64> {
+    GPS_TIME_REFERENCE
+        .lock(|reference| reference.get())
+        .map(|(gps_time_ms, at)| gps_time_ms + at.elapsed().as_millis())
+}
+
+/// Publish `positioning`, restored from flash, unless a live fix has come in already
+pub fn restore_fix(positioning: GnssPositioning) {
+    if GNSS_WATCH.try_get().flatten().is_none() {
+        defmt::info!("Restored last known position: {}", positioning);
+        GNSS_WATCH.sender().send(Some(positioning));
+    }
+}
+
+/// Time since the driver last published a valid fix, `None` before the first one
+///
+/// A fix restored from flash doesn't count; it was never published by the driver.
+pub fn fix_age() -> Option<Duration> {
+    LAST_FIX_AT
+        .lock(|last_fix_at| last_fix_at.get())
+        .map(|last_fix_at| last_fix_at.elapsed())
+}
+
+/// Whether `GNSS_WATCH` holds a valid fix that is no older than `max_age`
+///
+/// The age is measured from when the driver published the fix, not from the fix's own
+/// `datetime`: comparing against that needs a clock disciplined to GPS time, which the firmware
+/// doesn't keep yet. Until it does, a receiver that keeps replaying an old fix looks fresh here.
+/// A fix restored from flash never counts, however recent it is.
+pub fn has_fresh_fix(max_age: Duration) -> bool {
+    GNSS_WATCH
+        .try_get()
+        .flatten()
+        .is_some_and(|positioning| !positioning.is_stale)
+        && fix_age().is_some_and(|age| age <= max_age)
+}
+
+/// Recent valid fixes, filled by `record_history`
+pub static POSITION_HISTORY: Mutex<CriticalSectionRawMutex, RefCell<PositionHistory>> =
+    Mutex::new(RefCell::new(PositionHistory::new()));
+
+/// Copy every live fix published on `GNSS_WATCH` into `POSITION_HISTORY`
+#[embassy_executor::task]
+pub async fn record_history() {
+    let Some(mut gnss_rx) = GNSS_WATCH.receiver() else {
+        defmt::error!("No GNSS receiver left for the position history");
+        return;
+    };
+
+    loop {
+        if let Some(positioning) = gnss_rx.changed().await.filter(|p| !p.is_stale) {
+            POSITION_HISTORY.lock(|history| history.borrow_mut().record(positioning));
+        }
+    }
+}
@@ -1,6 +0,0 @@
-#[derive(Debug)]
-pub enum GpsError {
-    NoFix,
-    UartError,
-    Timeout,
-}
@@ -0,0 +1,46 @@
+use embassy_time::{Duration, Timer};
+use esp_hal::gpio::Output;
+
+use crate::lora::driver::TX_CONFIRMED;
+
+/// How long the display stays inverted after a position report goes out
+pub const FLASH_DURATION: Duration = Duration::from_millis(150);
+
+/// Length of the buzzer chirp after a position report goes out
+const BUZZER_PULSE: Duration = Duration::from_millis(50);
+
+/// Field feedback that a position report was transmitted, each indicator enabled separately
+pub struct Config {
+    /// Briefly invert the display
+    pub flash_display: bool,
+
+    /// Pulse an active buzzer wired to GPIO47; the stock board doesn't have one
+    pub buzzer: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            flash_display: true,
+            buzzer: false,
+        }
+    }
+}
+
+/// Drive the buzzer high for `BUZZER_PULSE` on every confirmed transmission
+#[embassy_executor::task]
+pub async fn buzzer(mut pin: Output<'static>) {
+    let Some(mut tx_confirmed) = TX_CONFIRMED.receiver() else {
+        defmt::error!("No TX confirmation receiver left for the buzzer");
+        return;
+    };
+
+    loop {
+        let reports = tx_confirmed.changed().await;
+        defmt::debug!("Buzzing for report {}", reports);
+
+        pin.set_high();
+        Timer::after(BUZZER_PULSE).await;
+        pin.set_low();
+    }
+}
@@ -4,4 +4,14 @@
 #[cfg(feature = "native-testing")]
 extern crate std;
 
+mod battery;
+#[cfg(feature = "esp32")]
+mod dock;
+mod errorlog;
+mod flashlog;
 mod gnss;
+mod lora;
+mod nav;
+#[cfg(feature = "esp32")]
+mod power;
+mod watchdog;
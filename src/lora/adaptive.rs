@@ -0,0 +1,211 @@
+/// Lowest spreading factor the SX1262 supports in LoRa mode (that we use)
+pub const MIN_SPREADING_FACTOR: u8 = 7;
+
+/// Highest spreading factor the SX1262 supports
+pub const MAX_SPREADING_FACTOR: u8 = 12;
+
+/// Demodulation floor at SF7 in dB of SNR, per the SX1262 datasheet
+const SF7_SNR_FLOOR_DB: f32 = -7.5;
+
+/// Each step up in spreading factor lowers the demodulation floor by this much
+const SNR_FLOOR_STEP_DB: f32 = 2.5;
+
+/// Lowest SNR at which a packet at `spreading_factor` can still be demodulated
+pub fn snr_floor(spreading_factor: u8) -> f32 {
+    SF7_SNR_FLOOR_DB
+        - (spreading_factor.saturating_sub(MIN_SPREADING_FACTOR)) as f32 * SNR_FLOOR_STEP_DB
+}
+
+/// Tuning for `AdaptiveSf`
+///
+/// Decisions are based on the margin between the smoothed SNR and the current spreading factor's
+/// demodulation floor. Stepping down one SF costs `SNR_FLOOR_STEP_DB` of margin, so
+/// `lower_margin_db` has to exceed `raise_margin_db` by more than that or a change in one
+/// direction immediately qualifies for a change back.
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveSfConfig {
+    /// Step the SF up (slower, more robust) once the margin drops below this
+    pub raise_margin_db: f32,
+
+    /// Step the SF down (faster, less airtime) once the margin exceeds this
+    pub lower_margin_db: f32,
+
+    /// Minimum time between two changes, in milliseconds
+    pub dwell_ms: u64,
+
+    /// Weight of each new sample in the rolling SNR average, between 0 and 1
+    pub smoothing: f32,
+
+    pub min_spreading_factor: u8,
+    pub max_spreading_factor: u8,
+}
+
+impl Default for AdaptiveSfConfig {
+    fn default() -> Self {
+        Self {
+            raise_margin_db: 3.0,
+            lower_margin_db: 10.0,
+            dwell_ms: 30_000,
+            smoothing: 0.25,
+            min_spreading_factor: MIN_SPREADING_FACTOR,
+            max_spreading_factor: MAX_SPREADING_FACTOR,
+        }
+    }
+}
+
+/// An accepted spreading factor change
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SfChange {
+    pub from: u8,
+    pub to: u8,
+
+    /// Smoothed SNR that triggered the change
+    pub snr: f32,
+}
+
+/// Picks a spreading factor from the SNR of received packets without thrashing
+///
+/// Three things keep it from oscillating around a threshold: an exponential moving average
+/// instead of the instantaneous SNR, separate thresholds for stepping up and down, and a dwell
+/// time after each change.
+#[derive(Debug, Clone)]
+pub struct AdaptiveSf {
+    config: AdaptiveSfConfig,
+    spreading_factor: u8,
+    average_snr: Option<f32>,
+    last_change_ms: Option<u64>,
+}
+
+impl AdaptiveSf {
+    pub fn new(config: AdaptiveSfConfig, spreading_factor: u8) -> Self {
+        Self {
+            config,
+            spreading_factor,
+            average_snr: None,
+            last_change_ms: None,
+        }
+    }
+
+    pub fn spreading_factor(&self) -> u8 {
+        self.spreading_factor
+    }
+
+    pub fn average_snr(&self) -> Option<f32> {
+        self.average_snr
+    }
+
+    /// Feed the SNR of a received packet, returning the change to apply, if any
+    ///
+    /// `now_ms` only needs to be monotonic.
+    pub fn on_snr(&mut self, snr: f32, now_ms: u64) -> Option<SfChange> {
+        let average = match self.average_snr {
+            Some(average) => average + self.config.smoothing * (snr - average),
+            None => snr,
+        };
+        self.average_snr = Some(average);
+
+        let dwelling = self
+            .last_change_ms
+            .is_some_and(|last_change| now_ms.saturating_sub(last_change) < self.config.dwell_ms);
+        if dwelling {
+            return None;
+        }
+
+        let margin = average - snr_floor(self.spreading_factor);
+        let target = if margin < self.config.raise_margin_db {
+            self.spreading_factor + 1
+        } else if margin > self.config.lower_margin_db {
+            self.spreading_factor.saturating_sub(1)
+        } else {
+            return None;
+        };
+
+        if !(self.config.min_spreading_factor..=self.config.max_spreading_factor).contains(&target)
+        {
+            return None;
+        }
+
+        let change = SfChange {
+            from: self.spreading_factor,
+            to: target,
+            snr: average,
+        };
+        self.spreading_factor = target;
+        self.last_change_ms = Some(now_ms);
+
+        Some(change)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snr_floor_per_spreading_factor() {
+        assert_eq!(snr_floor(7), -7.5);
+        assert_eq!(snr_floor(10), -15.0);
+        assert_eq!(snr_floor(12), -20.0);
+    }
+
+    #[test]
+    fn test_weak_signal_raises_spreading_factor() {
+        let mut adaptive = AdaptiveSf::new(AdaptiveSfConfig::default(), 7);
+
+        // Margin of 1.5 dB at SF7
+        assert_eq!(
+            adaptive.on_snr(-6.0, 0),
+            Some(SfChange {
+                from: 7,
+                to: 8,
+                snr: -6.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_dwell_time_blocks_back_to_back_changes() {
+        let config = AdaptiveSfConfig::default();
+        let mut adaptive = AdaptiveSf::new(config, 7);
+
+        assert!(adaptive.on_snr(-9.0, 0).is_some());
+        assert_eq!(adaptive.on_snr(-9.0, config.dwell_ms - 1), None);
+        assert!(adaptive.on_snr(-9.0, config.dwell_ms).is_some());
+        assert_eq!(adaptive.spreading_factor(), 9);
+    }
+
+    #[test]
+    fn test_hysteresis_band_holds_steady() {
+        let config = AdaptiveSfConfig::default();
+        let mut adaptive = AdaptiveSf::new(config, 7);
+
+        // 1.5 dB of margin at SF7 is 4 dB at SF8: inside the band, so no step back down
+        assert!(adaptive.on_snr(-6.0, 0).is_some());
+        for n in 1..10 {
+            assert_eq!(adaptive.on_snr(-6.0, n * config.dwell_ms), None);
+        }
+        assert_eq!(adaptive.spreading_factor(), 8);
+    }
+
+    #[test]
+    fn test_single_outlier_is_dampened() {
+        let mut adaptive = AdaptiveSf::new(AdaptiveSfConfig::default(), 10);
+
+        for now_ms in 0..10 {
+            assert_eq!(adaptive.on_snr(-9.0, now_ms), None);
+        }
+
+        // One deep fade moves the average by only a quarter of the dip
+        assert_eq!(adaptive.on_snr(-21.0, 10), None);
+        assert_eq!(adaptive.average_snr(), Some(-12.0));
+    }
+
+    #[test]
+    fn test_stays_within_bounds() {
+        let mut adaptive = AdaptiveSf::new(AdaptiveSfConfig::default(), MAX_SPREADING_FACTOR);
+        assert_eq!(adaptive.on_snr(-30.0, 0), None);
+
+        let mut adaptive = AdaptiveSf::new(AdaptiveSfConfig::default(), MIN_SPREADING_FACTOR);
+        assert_eq!(adaptive.on_snr(20.0, 0), None);
+    }
+}
@@ -0,0 +1,81 @@
+use embassy_time::Duration;
+
+use super::relay::RelayConfig;
+use super::slot::TimeSlots;
+
+/// Controls how often the position broadcaster transmits
+///
+/// The interval shrinks linearly from `max_interval` at `stationary_speed` down to `min_interval`
+/// at `fast_speed`. A stationary tracker still transmits once per `max_interval` as a "still here"
+/// heartbeat. Nothing is sent without a fix younger than `max_fix_age`. With `time_slots` set, a
+/// due broadcast is further held back until this node's slot comes around.
+pub struct BroadcastConfig {
+    /// Interval used at or above `fast_speed`
+    pub min_interval: Duration,
+
+    /// Heartbeat interval used while stopped or without a speed reading
+    pub max_interval: Duration,
+
+    /// Speed over ground (knots) at or below which the tracker is considered stopped
+    pub stationary_speed: f32,
+
+    /// Speed over ground (knots) at or above which the shortest interval is used
+    pub fast_speed: f32,
+
+    /// Oldest fix still worth broadcasting
+    pub max_fix_age: Duration,
+
+    /// Only transmit within this node's slot of a GPS-timed frame; off when `None`
+    ///
+    /// Every node sharing the channel needs the same frame period and slot width, and a distinct
+    /// slot. Without GPS time the broadcast is instead delayed by a random part of the frame.
+    pub time_slots: Option<TimeSlots>,
+
+    /// Wrap position reports for relaying, and forward peers' relayed packets; off when `None`
+    ///
+    /// Nodes without it still log relayed packets they hear, but don't forward them, and their
+    /// own reports go out unwrapped for relays to ignore. Forwarding a packet costs the same
+    /// airtime as sending it, so keep `max_hops` as low as the deployment allows.
+    pub relay: Option<RelayConfig>,
+
+    /// This node's ID in the header of every frame it sends; frames addressed to another node
+    /// are ignored
+    pub node_id: u32,
+}
+
+impl Default for BroadcastConfig {
+    fn default() -> Self {
+        Self {
+            min_interval: Duration::from_secs(5),
+            max_interval: Duration::from_secs(60),
+            stationary_speed: 1.0,
+            fast_speed: 30.0,
+            max_fix_age: Duration::from_secs(10),
+            time_slots: None,
+            relay: None,
+            node_id: 0,
+        }
+    }
+}
+
+impl BroadcastConfig {
+    /// Broadcast interval for the given speed over ground, always within the configured bounds
+    pub fn interval_for(&self, speed: Option<f32>) -> Duration {
+        let speed = match speed {
+            Some(speed) if speed > self.stationary_speed => speed,
+            _ => return self.max_interval,
+        };
+
+        if speed >= self.fast_speed {
+            return self.min_interval;
+        }
+
+        let ratio = (speed - self.stationary_speed) / (self.fast_speed - self.stationary_speed);
+        let span = self
+            .max_interval
+            .as_millis()
+            .saturating_sub(self.min_interval.as_millis());
+
+        Duration::from_millis(self.max_interval.as_millis() - (span as f32 * ratio) as u64)
+    }
+}
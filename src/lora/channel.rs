@@ -0,0 +1,145 @@
+use heapless::Vec;
+
+use super::error::LoraError;
+
+/// Upper bound on the number of channels in a plan
+///
+/// Every peer has to be configured with the same list, so keep it short enough to coordinate by
+/// hand.
+pub const MAX_CHANNELS: usize = 8;
+
+/// List of frequencies (Hz) that transmissions rotate through, round-robin
+///
+/// An empty plan means "don't rotate" and leaves the radio on its configured frequency.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelPlan {
+    channels: Vec<u32, MAX_CHANNELS>,
+    cursor: usize,
+}
+
+impl ChannelPlan {
+    pub fn new(channels: &[u32]) -> Result<Self, LoraError> {
+        Ok(Self {
+            channels: Vec::from_slice(channels).map_err(|_| LoraError::InvalidConfig)?,
+            cursor: 0,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    pub fn channels(&self) -> &[u32] {
+        &self.channels
+    }
+
+    /// Frequency to use for the next transmission, moving the rotation along
+    pub fn advance(&mut self) -> Option<u32> {
+        let frequency = *self.channels.get(self.cursor)?;
+        self.cursor = (self.cursor + 1) % self.channels.len();
+
+        Some(frequency)
+    }
+}
+
+/// Both receiving and transmitting hop through `channels`, spending `period_ms` on each
+///
+/// Every node works out the channel from GPS time, the same clock as `slot::TimeSlots`, so
+/// nodes with the same plan and period are on the same channel at the same time without ever
+/// exchanging a schedule. Clocks only agree to within a fix's latency, so frames sent right at
+/// a hop may go unheard; keep the period long against the airtime of a frame.
+#[derive(Debug, Clone)]
+pub struct FrequencyHopping {
+    pub channels: ChannelPlan,
+    pub period_ms: u64,
+}
+
+impl FrequencyHopping {
+    /// Channel for GPS time `now_ms`, `None` with no channels to hop through
+    pub fn channel_at(&self, now_ms: u64) -> Option<u32> {
+        let channels = self.channels.channels();
+        if channels.is_empty() {
+            return None;
+        }
+        let hop = now_ms / self.period_ms.max(1);
+
+        Some(channels[(hop % channels.len() as u64) as usize])
+    }
+
+    /// Milliseconds from GPS time `now_ms` until the next hop
+    pub fn until_next_hop(&self, now_ms: u64) -> u64 {
+        let period = self.period_ms.max(1);
+        period - now_ms % period
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_order() {
+        let mut plan = ChannelPlan::new(&[903_900_000, 904_100_000, 904_300_000]).unwrap();
+
+        let rotation: [Option<u32>; 7] = core::array::from_fn(|_| plan.advance());
+
+        assert_eq!(
+            rotation,
+            [
+                Some(903_900_000),
+                Some(904_100_000),
+                Some(904_300_000),
+                Some(903_900_000),
+                Some(904_100_000),
+                Some(904_300_000),
+                Some(903_900_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_single_channel_repeats() {
+        let mut plan = ChannelPlan::new(&[868_100_000]).unwrap();
+
+        assert_eq!(plan.advance(), Some(868_100_000));
+        assert_eq!(plan.advance(), Some(868_100_000));
+    }
+
+    #[test]
+    fn test_empty_plan_does_not_rotate() {
+        let mut plan = ChannelPlan::default();
+
+        assert!(plan.is_empty());
+        assert_eq!(plan.advance(), None);
+    }
+
+    #[test]
+    fn test_too_many_channels_rejected() {
+        let channels = [915_000_000; MAX_CHANNELS + 1];
+
+        assert!(matches!(
+            ChannelPlan::new(&channels),
+            Err(LoraError::InvalidConfig)
+        ));
+    }
+
+    #[test]
+    fn test_hops_on_gps_time() {
+        let hopping = FrequencyHopping {
+            channels: ChannelPlan::new(&[868_100_000, 868_300_000, 868_500_000]).unwrap(),
+            period_ms: 10_000,
+        };
+
+        assert_eq!(hopping.channel_at(0), Some(868_100_000));
+        assert_eq!(hopping.channel_at(9_999), Some(868_100_000));
+        assert_eq!(hopping.channel_at(10_000), Some(868_300_000));
+        assert_eq!(hopping.channel_at(30_000), Some(868_100_000));
+        assert_eq!(hopping.until_next_hop(12_500), 7_500);
+
+        let empty = FrequencyHopping {
+            channels: ChannelPlan::default(),
+            period_ms: 10_000,
+        };
+        assert_eq!(empty.channel_at(0), None);
+    }
+}
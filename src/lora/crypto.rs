@@ -0,0 +1,218 @@
+use aes::Aes128;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+
+use super::error::LoraError;
+use super::packet::PacketType;
+
+/// AES-128 with a 32-bit big-endian block counter in the last four bytes of the IV
+type Aes128Ctr = ctr::Ctr32BE<Aes128>;
+
+pub const KEY_SIZE: usize = 16;
+
+/// Bytes in front of the ciphertext, including the type byte
+pub const ENCRYPTED_HEADER_LEN: usize = 1 + PacketType::Encrypted.payload_len();
+
+/// Bytes of a provisioned key, see `key_from_bytes`
+pub const KEY_RECORD_SIZE: usize = 21;
+
+/// Marks a provisioned key, so that erased flash isn't taken for one
+const KEY_MAGIC: [u8; 4] = *b"SBLK";
+
+/// The network key in a provisioned record, `None` for anything else
+///
+/// | Byte | Field                                 |
+/// |------|---------------------------------------|
+/// | 0    | `SBLK`                                |
+/// | 4    | Key                                   |
+/// | 20   | Inverted byte sum of bytes 0-19       |
+pub fn key_from_bytes(bytes: &[u8; KEY_RECORD_SIZE]) -> Option<[u8; KEY_SIZE]> {
+    if bytes[..4] != KEY_MAGIC || bytes[20] != !checksum(&bytes[..20]) {
+        return None;
+    }
+
+    let mut key = [0u8; KEY_SIZE];
+    key.copy_from_slice(&bytes[4..20]);
+
+    Some(key)
+}
+
+/// Encrypts packets with a key shared by every node in the network, AES-128 in CTR mode
+///
+/// An encrypted packet wraps the original, type byte included:
+///
+/// | Byte | Field                                               |
+/// |------|-----------------------------------------------------|
+/// | 0    | `PacketType::Encrypted` (0x0C)                      |
+/// | 1    | Packet counter, `u32` little-endian                 |
+/// | 5    | The original packet, encrypted                      |
+///
+/// The IV is the sender's node ID as `u24` LE, then the packet counter as `u32` LE, five zero
+/// bytes and the block counter. The frame header's own sequence number would wrap after 256
+/// frames, hence the separate counter.
+///
+/// CTR mode falls apart if a keystream is ever used twice, which happens whenever two packets
+/// under the same key share a node ID and packet counter. So:
+///
+/// - Every node sharing a key needs its own node ID. Two nodes set to the same ID reuse each
+///   other's keystreams.
+/// - The counter starts from a random value each boot and is never stored, so two boots of a
+///   node only collide if the stretches of counters they used overlap. At one packet a second,
+///   two day-long boots overlap with a chance of about 1 in 25,000; change the key long before
+///   a node has been through that many.
+/// - Wrapping takes 2^32 packets, well beyond any boot.
+///
+/// There is no authentication. The frame CRC only catches corruption, so anyone in range can
+/// flip bits in the plaintext, or replay old packets, without the receiver noticing. A wrong key
+/// decrypts to noise that the packet decoders usually, but not always, reject.
+pub struct LoraCrypto {
+    key: [u8; KEY_SIZE],
+    counter: u32,
+}
+
+impl LoraCrypto {
+    /// Encrypt with `key`, starting from packet counter `counter`, ideally random
+    pub fn new(key: [u8; KEY_SIZE], counter: u32) -> Self {
+        Self { key, counter }
+    }
+
+    /// Encrypt `packet` from `source` into `buffer`, returning the number of bytes written
+    pub fn encrypt(
+        &mut self,
+        source: u32,
+        packet: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<usize, LoraError> {
+        let len = ENCRYPTED_HEADER_LEN + packet.len();
+        let buffer = buffer.get_mut(..len).ok_or(LoraError::BufferError)?;
+
+        let counter = self.counter;
+        self.counter = self.counter.wrapping_add(1);
+
+        buffer[0] = PacketType::Encrypted as u8;
+        buffer[1..ENCRYPTED_HEADER_LEN].copy_from_slice(&counter.to_le_bytes());
+        let ciphertext = &mut buffer[ENCRYPTED_HEADER_LEN..];
+        ciphertext.copy_from_slice(packet);
+        self.cipher(source, counter).apply_keystream(ciphertext);
+
+        Ok(len)
+    }
+
+    /// Decrypt a packet `encrypt` wrote on node `source` into `buffer`, returning the original
+    pub fn decrypt<'a>(
+        &self,
+        source: u32,
+        bytes: &[u8],
+        buffer: &'a mut [u8],
+    ) -> Result<&'a [u8], LoraError> {
+        let packet_type = PacketType::try_from(*bytes.first().ok_or(LoraError::NoData)?)?;
+        if packet_type != PacketType::Encrypted {
+            return Err(LoraError::UnexpectedPacketType(packet_type as u8));
+        }
+
+        let header = bytes
+            .get(..ENCRYPTED_HEADER_LEN)
+            .ok_or(LoraError::BufferError)?;
+        let ciphertext = &bytes[ENCRYPTED_HEADER_LEN..];
+        if ciphertext.is_empty() {
+            return Err(LoraError::NoData);
+        }
+        let counter = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+
+        let packet = buffer
+            .get_mut(..ciphertext.len())
+            .ok_or(LoraError::BufferError)?;
+        packet.copy_from_slice(ciphertext);
+        self.cipher(source, counter).apply_keystream(packet);
+
+        Ok(packet)
+    }
+
+    fn cipher(&self, source: u32, counter: u32) -> Aes128Ctr {
+        let mut iv = [0u8; 16];
+        iv[..3].copy_from_slice(&source.to_le_bytes()[..3]);
+        iv[3..7].copy_from_slice(&counter.to_le_bytes());
+
+        Aes128Ctr::new(&self.key.into(), &iv.into())
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; KEY_SIZE] = *b"0123456789abcdef";
+    const PACKET: &[u8] = &[0x0B, b'h', b'e', b'l', b'l', b'o'];
+
+    #[test]
+    fn test_round_trip() {
+        let mut crypto = LoraCrypto::new(KEY, 7);
+        let mut encrypted = [0u8; 32];
+        let len = crypto.encrypt(0x12_3456, PACKET, &mut encrypted).unwrap();
+
+        assert_eq!(len, ENCRYPTED_HEADER_LEN + PACKET.len());
+        assert_eq!(encrypted[0], PacketType::Encrypted as u8);
+        assert_eq!(&encrypted[1..5], &7u32.to_le_bytes());
+        assert_ne!(&encrypted[5..len], PACKET);
+
+        let mut decrypted = [0u8; 32];
+        assert_eq!(
+            crypto
+                .decrypt(0x12_3456, &encrypted[..len], &mut decrypted)
+                .unwrap(),
+            PACKET
+        );
+    }
+
+    #[test]
+    fn test_keystream_differs_per_packet_and_source() {
+        let mut crypto = LoraCrypto::new(KEY, 0);
+        let mut first = [0u8; 32];
+        let mut second = [0u8; 32];
+        let mut other_source = [0u8; 32];
+
+        crypto.encrypt(1, PACKET, &mut first).unwrap();
+        crypto.encrypt(1, PACKET, &mut second).unwrap();
+        let mut crypto = LoraCrypto::new(KEY, 0);
+        crypto.encrypt(2, PACKET, &mut other_source).unwrap();
+
+        assert_ne!(first[5..11], second[5..11]);
+        assert_ne!(first[5..11], other_source[5..11]);
+    }
+
+    #[test]
+    fn test_wrong_key_or_source_garbles() {
+        let mut crypto = LoraCrypto::new(KEY, 0);
+        let mut encrypted = [0u8; 32];
+        let len = crypto.encrypt(1, PACKET, &mut encrypted).unwrap();
+
+        let mut decrypted = [0u8; 32];
+        let other = LoraCrypto::new(*b"fedcba9876543210", 0);
+        assert_ne!(
+            other.decrypt(1, &encrypted[..len], &mut decrypted).unwrap(),
+            PACKET
+        );
+        assert_ne!(
+            crypto
+                .decrypt(2, &encrypted[..len], &mut decrypted)
+                .unwrap(),
+            PACKET
+        );
+    }
+
+    #[test]
+    fn test_key_record() {
+        let mut record = [0u8; KEY_RECORD_SIZE];
+        record[..4].copy_from_slice(b"SBLK");
+        record[4..20].copy_from_slice(&KEY);
+        record[20] = !checksum(&record[..20]);
+        assert_eq!(key_from_bytes(&record), Some(KEY));
+
+        record[10] ^= 1;
+        assert_eq!(key_from_bytes(&record), None);
+        assert_eq!(key_from_bytes(&[0xFF; KEY_RECORD_SIZE]), None);
+    }
+}
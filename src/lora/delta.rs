@@ -0,0 +1,303 @@
+use super::error::LoraError;
+use super::packet::{decode_coordinate, encode_coordinate, PacketType};
+
+/// Keyframes sent by default, as one in this many position packets
+pub const DEFAULT_KEYFRAME_EVERY: u8 = 10;
+
+/// Fixed-point (1e-7 degree) steps per delta step, making a delta step 1e-6 degree (~11 cm)
+const DELTA_STEP: i32 = 10;
+
+/// A full position that the deltas following it are relative to
+///
+/// | Byte | Field                                     |
+/// |------|-------------------------------------------|
+/// | 0    | `PacketType::PositionKeyframe` (0x06)     |
+/// | 1    | Keyframe ID, incremented every keyframe   |
+/// | 2    | Latitude, `i32` 1e-7 degree               |
+/// | 6    | Longitude, `i32` 1e-7 degree              |
+///
+/// Deltas (`PacketType::PositionDelta`, 0x07) carry the ID of their keyframe in byte 1, then
+/// latitude and longitude offsets from it as `i16` 1e-6 degree. That reaches ~3.6 km from the
+/// keyframe; anything further is sent as a new keyframe instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Keyframe {
+    id: u8,
+
+    /// Coordinates as fixed point, exactly as sent
+    latitude: i32,
+    longitude: i32,
+}
+
+/// Sends every `keyframe_every`-th position in full and the rest as offsets from the last one
+///
+/// Deltas are all relative to the keyframe rather than to each other, so a lost delta costs one
+/// position and nothing more; a lost keyframe costs the deltas up to the next one.
+#[derive(Debug)]
+pub struct DeltaEncoder {
+    keyframe_every: u8,
+    keyframe: Option<Keyframe>,
+    deltas_sent: u8,
+}
+
+impl DeltaEncoder {
+    pub const fn new(keyframe_every: u8) -> Self {
+        Self {
+            keyframe_every,
+            keyframe: None,
+            deltas_sent: 0,
+        }
+    }
+
+    /// Write the next packet for this position, returning the number of bytes written
+    pub fn encode(
+        &mut self,
+        latitude: f64,
+        longitude: f64,
+        buffer: &mut [u8],
+    ) -> Result<usize, LoraError> {
+        let latitude = i32::from_le_bytes(encode_coordinate(latitude));
+        let longitude = i32::from_le_bytes(encode_coordinate(longitude));
+
+        let delta = self
+            .keyframe
+            .filter(|_| self.deltas_sent + 1 < self.keyframe_every)
+            .and_then(|keyframe| {
+                Some((
+                    keyframe.id,
+                    delta(latitude, keyframe.latitude)?,
+                    delta(longitude, keyframe.longitude)?,
+                ))
+            });
+
+        if let Some((id, latitude, longitude)) = delta {
+            let len = 1 + PacketType::PositionDelta.payload_len();
+            let buffer = buffer.get_mut(..len).ok_or(LoraError::BufferError)?;
+
+            buffer[0] = PacketType::PositionDelta as u8;
+            buffer[1] = id;
+            buffer[2..4].copy_from_slice(&latitude.to_le_bytes());
+            buffer[4..6].copy_from_slice(&longitude.to_le_bytes());
+            self.deltas_sent += 1;
+
+            return Ok(len);
+        }
+
+        // First position, time for a keyframe, or too far from the last one
+        let keyframe = Keyframe {
+            id: self
+                .keyframe
+                .map_or(0, |keyframe| keyframe.id.wrapping_add(1)),
+            latitude,
+            longitude,
+        };
+
+        let len = 1 + PacketType::PositionKeyframe.payload_len();
+        let buffer = buffer.get_mut(..len).ok_or(LoraError::BufferError)?;
+
+        buffer[0] = PacketType::PositionKeyframe as u8;
+        buffer[1] = keyframe.id;
+        buffer[2..6].copy_from_slice(&latitude.to_le_bytes());
+        buffer[6..10].copy_from_slice(&longitude.to_le_bytes());
+        self.keyframe = Some(keyframe);
+        self.deltas_sent = 0;
+
+        Ok(len)
+    }
+}
+
+impl Default for DeltaEncoder {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEYFRAME_EVERY)
+    }
+}
+
+/// Offset of `value` from `base` in delta steps, `None` if it doesn't fit
+fn delta(value: i32, base: i32) -> Option<i16> {
+    let difference = value as i64 - base as i64;
+    let steps = (difference + (DELTA_STEP / 2) as i64 * difference.signum()) / DELTA_STEP as i64;
+
+    i16::try_from(steps).ok()
+}
+
+/// Turns keyframes and deltas from one sender back into absolute positions
+///
+/// Packets carry no sender address, so a receiver hearing several delta-encoding nodes at once
+/// will mix their keyframes up; the keyframe ID check catches most, not all, of that.
+#[derive(Debug, Default)]
+pub struct DeltaDecoder {
+    keyframe: Option<Keyframe>,
+}
+
+impl DeltaDecoder {
+    pub const fn new() -> Self {
+        Self { keyframe: None }
+    }
+
+    /// Decode a keyframe or delta into latitude and longitude
+    ///
+    /// Fails with `MissingKeyframe` for a delta whose keyframe wasn't received, such as when
+    /// joining mid-stream; positions resume with the sender's next keyframe.
+    pub fn decode(&mut self, bytes: &[u8]) -> Result<(f64, f64), LoraError> {
+        let packet_type = PacketType::try_from(*bytes.first().ok_or(LoraError::NoData)?)?;
+        let payload = bytes
+            .get(1..1 + packet_type.payload_len())
+            .ok_or(LoraError::BufferError)?;
+
+        let keyframe = match packet_type {
+            PacketType::PositionKeyframe => {
+                let keyframe = Keyframe {
+                    id: payload[0],
+                    latitude: i32::from_le_bytes([payload[1], payload[2], payload[3], payload[4]]),
+                    longitude: i32::from_le_bytes([payload[5], payload[6], payload[7], payload[8]]),
+                };
+                self.keyframe = Some(keyframe);
+
+                return Ok(coordinates(keyframe.latitude, keyframe.longitude));
+            }
+            PacketType::PositionDelta => self
+                .keyframe
+                .filter(|keyframe| keyframe.id == payload[0])
+                .ok_or(LoraError::MissingKeyframe(payload[0]))?,
+            _ => return Err(LoraError::UnexpectedPacketType(packet_type as u8)),
+        };
+
+        let latitude = i16::from_le_bytes([payload[1], payload[2]]) as i32 * DELTA_STEP;
+        let longitude = i16::from_le_bytes([payload[3], payload[4]]) as i32 * DELTA_STEP;
+
+        Ok(coordinates(
+            keyframe.latitude.saturating_add(latitude),
+            keyframe.longitude.saturating_add(longitude),
+        ))
+    }
+}
+
+fn coordinates(latitude: i32, longitude: i32) -> (f64, f64) {
+    (
+        decode_coordinate(latitude.to_le_bytes()),
+        decode_coordinate(longitude.to_le_bytes()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::positioning::assert_close;
+
+    const LATITUDE: f64 = 37.774_929_5;
+    const LONGITUDE: f64 = -122.419_415_5;
+
+    fn send(encoder: &mut DeltaEncoder, latitude: f64, longitude: f64) -> ([u8; 16], usize) {
+        let mut buffer = [0u8; 16];
+        let len = encoder.encode(latitude, longitude, &mut buffer).unwrap();
+
+        (buffer, len)
+    }
+
+    fn assert_decodes_to(decoded: (f64, f64), latitude: f64, longitude: f64) {
+        assert_close(decoded.0, latitude, 1e-6);
+        assert_close(decoded.1, longitude, 1e-6);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut encoder = DeltaEncoder::new(4);
+        let mut decoder = DeltaDecoder::new();
+
+        for step in 0..12 {
+            let latitude = LATITUDE + step as f64 * 0.000_37;
+            let longitude = LONGITUDE - step as f64 * 0.000_21;
+            let (buffer, len) = send(&mut encoder, latitude, longitude);
+
+            let expected = if step % 4 == 0 {
+                PacketType::PositionKeyframe
+            } else {
+                PacketType::PositionDelta
+            };
+            assert_eq!(buffer[0], expected as u8, "step {step}");
+            assert_eq!(len, 1 + expected.payload_len());
+
+            assert_decodes_to(decoder.decode(&buffer[..len]).unwrap(), latitude, longitude);
+        }
+    }
+
+    #[test]
+    fn test_keyframe_is_exact() {
+        let mut encoder = DeltaEncoder::default();
+        let (buffer, len) = send(&mut encoder, LATITUDE, LONGITUDE);
+
+        let decoded = DeltaDecoder::new().decode(&buffer[..len]).unwrap();
+
+        assert!((decoded.0 - LATITUDE).abs() < 1e-7);
+        assert!((decoded.1 - LONGITUDE).abs() < 1e-7);
+    }
+
+    #[test]
+    fn test_overflow_forces_keyframe() {
+        let mut encoder = DeltaEncoder::default();
+        let mut decoder = DeltaDecoder::new();
+
+        let (buffer, len) = send(&mut encoder, LATITUDE, LONGITUDE);
+        decoder.decode(&buffer[..len]).unwrap();
+
+        // ~3.3 km north still fits a delta, ~3.9 km doesn't
+        let (buffer, len) = send(&mut encoder, LATITUDE + 0.03, LONGITUDE);
+        assert_eq!(buffer[0], PacketType::PositionDelta as u8);
+        assert_decodes_to(
+            decoder.decode(&buffer[..len]).unwrap(),
+            LATITUDE + 0.03,
+            LONGITUDE,
+        );
+
+        let (buffer, len) = send(&mut encoder, LATITUDE + 0.035, LONGITUDE);
+        assert_eq!(buffer[0], PacketType::PositionKeyframe as u8);
+        assert_eq!(buffer[1], 1);
+        assert_decodes_to(
+            decoder.decode(&buffer[..len]).unwrap(),
+            LATITUDE + 0.035,
+            LONGITUDE,
+        );
+    }
+
+    #[test]
+    fn test_joining_mid_stream_waits_for_keyframe() {
+        let mut encoder = DeltaEncoder::new(3);
+        let mut decoder = DeltaDecoder::new();
+
+        send(&mut encoder, LATITUDE, LONGITUDE);
+        let (buffer, len) = send(&mut encoder, LATITUDE + 0.001, LONGITUDE);
+
+        assert!(matches!(
+            decoder.decode(&buffer[..len]),
+            Err(LoraError::MissingKeyframe(0))
+        ));
+
+        send(&mut encoder, LATITUDE + 0.002, LONGITUDE);
+        let (buffer, len) = send(&mut encoder, LATITUDE + 0.003, LONGITUDE);
+        assert_eq!(buffer[0], PacketType::PositionKeyframe as u8);
+        assert_decodes_to(
+            decoder.decode(&buffer[..len]).unwrap(),
+            LATITUDE + 0.003,
+            LONGITUDE,
+        );
+    }
+
+    #[test]
+    fn test_delta_against_stale_keyframe_rejected() {
+        let mut decoder = DeltaDecoder::new();
+        let mut encoder = DeltaEncoder::new(2);
+
+        let (buffer, len) = send(&mut encoder, LATITUDE, LONGITUDE);
+        decoder.decode(&buffer[..len]).unwrap();
+
+        // Keyframe 1 is lost, so its delta must not be applied to keyframe 0
+        send(&mut encoder, LATITUDE, LONGITUDE);
+        send(&mut encoder, LATITUDE, LONGITUDE);
+        let (buffer, len) = send(&mut encoder, LATITUDE, LONGITUDE);
+
+        assert_eq!(buffer[0], PacketType::PositionDelta as u8);
+        assert!(matches!(
+            decoder.decode(&buffer[..len]),
+            Err(LoraError::MissingKeyframe(1))
+        ));
+    }
+}
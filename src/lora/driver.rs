@@ -0,0 +1,1988 @@
+use core::cell::Cell;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use embassy_futures::select::{select, select3, Either, Either3};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::watch::Watch;
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::gpio::{Input, Output};
+use esp_hal::rng::Rng;
+use esp_hal::Async;
+use lora_phy::iv::GenericSx126xInterfaceVariant;
+use lora_phy::mod_params::{
+    Bandwidth, CodingRate, ModulationParams, PacketParams, PacketStatus, RadioError,
+    SpreadingFactor,
+};
+use lora_phy::sx126x::{self, Sx1262, Sx126x, TcxoCtrlVoltage};
+use lora_phy::{LoRa, RxMode};
+
+use super::adaptive::{AdaptiveSf, AdaptiveSfConfig};
+use super::broadcast::BroadcastConfig;
+use super::channel::{ChannelPlan, FrequencyHopping};
+use super::crypto::LoraCrypto;
+use super::delta::{DeltaDecoder, DeltaEncoder};
+use super::duty::{time_on_air_us, DutyCycleConfig, DutyCycleLimiter};
+use super::error::LoraError;
+use super::header::{decode_frame, encode_frame, FrameFlags, BROADCAST, CRC_LEN, HEADER_LEN};
+use super::health::HealthMonitor;
+use super::loss::SequenceTracker;
+use super::packet::{
+    decode_ack, decode_status, decode_text, encode_ack, encode_status, NodeStatus, PacketType,
+};
+use super::persist;
+use super::region::Region;
+use super::relay::{decode_relayed, Relay, RelayVerdict};
+use super::report::{decode_report, encode_report, PositionReport, ReportFormat};
+use super::settings::RadioSettings;
+use super::slot::{SlotWait, TimeSlots};
+use super::stats::RadioStats;
+use crate::battery::monitor::BATTERY_PERCENT;
+use crate::dock::is_docked;
+use crate::errorlog::{self, ErrorCode};
+use crate::gnss::positioning::GnssPositioning;
+use crate::gnss::precision::{CoordinatePrecision, COORDINATE_PRECISION};
+use crate::gnss::watch::{gps_time_ms, has_fresh_fix, GNSS_WATCH};
+use crate::power::{self, PowerMode};
+use crate::watchdog::{self, supervisor::TaskWatchdog};
+
+pub const RX_BUFFER_SIZE: usize = 128;
+
+/// Preamble length in symbols, sent and expected
+const PREAMBLE_LEN: u16 = 4;
+/// Frequency of the presets, which are all for `Region::Us915`
+const LORA_FREQUENCY: u32 = Region::Us915.default_frequency();
+
+/// Send a status beacon after every this many position reports
+const STATUS_BEACON_EVERY: u32 = 10;
+
+/// Channel activity checks `transmit_with_cad` makes before giving up
+const CAD_ATTEMPTS: u32 = 5;
+
+/// Backoff after finding the channel busy: at least `CAD_BACKOFF_MIN_MS`, plus up to
+/// `CAD_BACKOFF_SPREAD_MS` at random so that nodes waiting on the same transmission don't all
+/// try again at once
+const CAD_BACKOFF_MIN_MS: u64 = 50;
+const CAD_BACKOFF_SPREAD_MS: u64 = 200;
+
+/// Longest random wait before forwarding a relayed packet, so that relays which heard the same
+/// packet don't all transmit over each other
+const RELAY_JITTER_MS: u64 = 500;
+
+/// Longest the run loop may take to come round before it counts as hung; an iteration listens
+/// for up to `BroadcastConfig::min_interval`, may wait a frame for its slot and then transmits
+const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Sources whose lost frames are counted at once, see `SequenceTracker`
+const TRACKED_NODES: usize = 8;
+
+/// `ReportFormat` used for position broadcasts, as its `u8` discriminant
+///
+/// Set over BLE through `LoraCommand::Reconfigure`, and kept across resets along with the rest
+/// of the `RadioSettings`.
+pub static REPORT_FORMAT: AtomicU8 = AtomicU8::new(ReportFormat::Standard as u8);
+
+/// Consumers of `TX_CONFIRMED`: the app state aggregator, for the display flash, and the buzzer
+const TX_CONFIRMED_RECEIVERS: usize = 2;
+
+/// Changes every time a position report has gone out, carrying how many have so far
+///
+/// "Gone out" means the radio finished transmitting it; reports are broadcast without asking for
+/// an acknowledgement, so this says nothing about whether anyone heard it.
+pub static TX_CONFIRMED: Watch<CriticalSectionRawMutex, u32, TX_CONFIRMED_RECEIVERS> = Watch::new();
+
+pub type TxConfirmedRx =
+    embassy_sync::watch::Receiver<'static, CriticalSectionRawMutex, u32, TX_CONFIRMED_RECEIVERS>;
+
+/// Consumers of `LORA_RX`, such as the display and BLE
+const LORA_RX_RECEIVERS: usize = 2;
+
+/// A packet as received, with the signal it arrived on
+#[derive(Clone)]
+pub struct ReceivedPacket {
+    /// The packet's type byte and payload, without the frame header; see `header::decode_frame`
+    pub payload: heapless::Vec<u8, RX_BUFFER_SIZE>,
+    pub status: PacketStatus,
+}
+
+/// Every intact frame addressed to this node, as it's received
+///
+/// Relayed packets are published as received, envelope included. A consumer that falls behind
+/// only sees the latest packet.
+pub static LORA_RX: Watch<CriticalSectionRawMutex, ReceivedPacket, LORA_RX_RECEIVERS> =
+    Watch::new();
+
+pub type LoraRxRx = embassy_sync::watch::Receiver<
+    'static,
+    CriticalSectionRawMutex,
+    ReceivedPacket,
+    LORA_RX_RECEIVERS,
+>;
+
+/// Largest packet that still fits a frame of `RX_BUFFER_SIZE`; the type byte is part of the header
+pub const MAX_PACKET_SIZE: usize = RX_BUFFER_SIZE - HEADER_LEN - CRC_LEN + 1;
+
+/// Commands waiting in `LORA_TX_QUEUE` before further ones are turned away
+const TX_QUEUE_SIZE: usize = 4;
+
+/// A packet queued for transmission: its type byte and payload, framed when it's sent
+pub type TxPacket = heapless::Vec<u8, MAX_PACKET_SIZE>;
+
+/// Something another task wants the radio to do
+pub enum LoraCommand {
+    /// Transmit a packet
+    Send(TxPacket),
+
+    /// Switch transmitting and listening to another spreading factor, 5 to 12
+    ///
+    /// Refused if the region's dwell time limit doesn't allow it. With adaptive SF on, the next
+    /// received packet may step it again.
+    SetSpreadingFactor(u8),
+
+    /// Switch to other radio and report settings, and keep them across resets
+    ///
+    /// Refused unless they pass `RadioSettings::check` for the configured region and bandwidth.
+    Reconfigure(RadioSettings),
+}
+
+/// Packets other tasks want transmitted, and changes they want made, handled as soon as the
+/// radio is free
+///
+/// The LoRa task takes them between receives, so a command waits at most for a broadcast or
+/// forward already in progress. Tasks that can wait for room should `send().await` directly;
+/// `queue_packet` and `queue_command` are for those that can't.
+pub static LORA_TX_QUEUE: Channel<CriticalSectionRawMutex, LoraCommand, TX_QUEUE_SIZE> =
+    Channel::new();
+
+/// Queue `packet` for transmission without waiting, `LoraError::QueueFull` if there's no room
+pub fn queue_packet(packet: &[u8]) -> Result<(), LoraError> {
+    let packet = TxPacket::from_slice(packet).map_err(|_| LoraError::BufferError)?;
+
+    queue_command(LoraCommand::Send(packet))
+}
+
+/// Queue `command` without waiting, `LoraError::QueueFull` if there's no room
+///
+/// A full queue means the radio is behind on what it's been asked to do already, so the
+/// caller is told rather than the oldest command being dropped.
+pub fn queue_command(command: LoraCommand) -> Result<(), LoraError> {
+    LORA_TX_QUEUE
+        .try_send(command)
+        .map_err(|_| LoraError::QueueFull)
+}
+
+/// Radio counters, readable from any task
+pub static RADIO_STATS: BlockingMutex<CriticalSectionRawMutex, Cell<RadioStats>> =
+    BlockingMutex::new(Cell::new(RadioStats::new()));
+
+/// When the last frame was received intact
+static LAST_PACKET_AT: BlockingMutex<CriticalSectionRawMutex, Cell<Option<Instant>>> =
+    BlockingMutex::new(Cell::new(None));
+
+/// Radio settings in use, with the region and bandwidth in Hz that changes to them are checked
+/// against; `None` until the radio is up
+static LIVE_SETTINGS: BlockingMutex<
+    CriticalSectionRawMutex,
+    Cell<Option<(RadioSettings, Region, u32)>>,
+> = BlockingMutex::new(Cell::new(None));
+
+/// Radio and report settings in use, `None` until the radio is up
+pub fn radio_settings() -> Option<RadioSettings> {
+    LIVE_SETTINGS
+        .lock(|live| live.get())
+        .map(|(settings, _, _)| settings)
+}
+
+/// Check `settings` the way `LoraCommand::Reconfigure` will, so that a request can be turned
+/// down before it's queued
+pub fn check_settings(settings: &RadioSettings) -> Result<(), LoraError> {
+    let (_, region, bandwidth_hz) = LIVE_SETTINGS
+        .lock(|live| live.get())
+        .ok_or(LoraError::InvalidConfig)?;
+
+    settings.check(region, bandwidth_hz)
+}
+
+/// Send and show positions in the format and precision `settings` have
+fn set_reporting(settings: &RadioSettings) {
+    REPORT_FORMAT.store(settings.report_format as u8, Ordering::Relaxed);
+    COORDINATE_PRECISION.store(settings.coordinate_precision.decimals(), Ordering::Relaxed);
+}
+
+/// Whether a frame has been received intact within `window`
+pub fn heard_within(window: Duration) -> bool {
+    LAST_PACKET_AT
+        .lock(|last_packet_at| last_packet_at.get())
+        .is_some_and(|last_packet_at| last_packet_at.elapsed() <= window)
+}
+
+/// Apply `update` to the shared counters and return the result
+fn update_stats(update: impl FnOnce(&mut RadioStats)) -> RadioStats {
+    RADIO_STATS.lock(|cell| {
+        let mut stats = cell.get();
+        update(&mut stats);
+        cell.set(stats);
+
+        stats
+    })
+}
+
+// Configuration parameters for the LoRa interface
+pub struct LoraConfig {
+    /// Receive frequency, and the transmit frequency when `tx_channels` is empty; moved along
+    /// by `Lora::hop` when `hopping` is set
+    pub frequency: u32,
+    pub spreading_factor: SpreadingFactor,
+    pub bandwidth: Bandwidth,
+    pub coding_rate: CodingRate,
+
+    /// Output power in dBm
+    pub tx_power: i32,
+
+    /// Regulatory region; only checked by `LoraConfigBuilder::build`
+    pub region: Region,
+
+    /// Frequencies that broadcasts rotate through, one per transmission
+    ///
+    /// Spreads airtime across channels so a single congested one doesn't swallow every update.
+    /// The node itself keeps listening on `frequency` between broadcasts, so peers that want to
+    /// reach it must agree on that channel, and anything that wants to hear all of its
+    /// broadcasts has to either listen on every channel in the plan or scan them in the same
+    /// order.
+    pub tx_channels: ChannelPlan,
+
+    /// Hop through a list of channels, listening and transmitting alike; stays on `frequency`
+    /// when `None`
+    ///
+    /// Replaces `tx_channels`, which has to be left empty. Every peer needs the same channels
+    /// and period, and a GPS fix to go by: until a node has one it stays where it is, and only
+    /// hears peers that happen to hop past.
+    pub hopping: Option<FrequencyHopping>,
+
+    /// Step the spreading factor with the SNR of received packets; off when `None`
+    ///
+    /// Every peer has to follow the same changes to stay in contact, so only enable this on
+    /// links where both ends run it against each other.
+    pub adaptive_sf: Option<AdaptiveSfConfig>,
+
+    /// How the board wires the SX1262's oscillator and antenna switch
+    pub wiring: RadioWiring,
+
+    /// Listen between broadcasts in single-shot windows, see `Lora::receive_with_timeout`
+    pub single_shot_rx: bool,
+
+    /// Sleep between broadcasts instead of listening, whenever `LORA_TX_QUEUE` is empty
+    ///
+    /// Cuts the radio's draw from around 5 mA to under a microamp, but nothing is heard while it
+    /// sleeps: no peers' positions, no ACKs for them to wait on and nothing to relay. Only suits
+    /// a node that just reports.
+    pub sleep_when_idle: bool,
+
+    /// Retransmissions `Lora::send_reliable` makes before giving up on an acknowledgement
+    pub ack_retries: u8,
+
+    /// Have the radio append a CRC to transmitted packets and check it on received ones
+    ///
+    /// Only turn this off to talk to devices that don't use it. Corrupted packets then reach
+    /// the receive handler instead of being dropped by the radio; frames from this firmware
+    /// still carry their own CRC in the header, see `header::FrameFlags`, but anything else is
+    /// taken as it arrives.
+    pub crc_on: bool,
+
+    /// Encrypt every packet sent with the network key, see `crypto::LoraCrypto`
+    ///
+    /// The key is provisioned to flash separately, see `persist::load_key`; without one, packets
+    /// go out in the clear. Encrypted packets are 5 bytes longer, so the largest that still fit a
+    /// frame are that much shorter than `MAX_PACKET_SIZE`. Plaintext packets are accepted either
+    /// way, from nodes without the key.
+    pub crypto: bool,
+
+    /// Refuse to send, with `LoraError::DutyCycleExceeded`, once this much airtime is spent
+    ///
+    /// Set from `Region::duty_cycle` by `Region::to_config`, and `LoraConfigBuilder::build` won't
+    /// accept a looser limit than that. Everything sent counts, ACKs and relayed packets
+    /// included; what doesn't fit is dropped rather than queued, and the next position report
+    /// is sent once there's room again.
+    pub duty_cycle: Option<DutyCycleConfig>,
+}
+
+/// Board-specific SX1262 wiring that the driver can't detect
+///
+/// | Board                                | `tcxo_voltage` | `external_rf_switch` |
+/// |--------------------------------------|----------------|----------------------|
+/// | Heltec WiFi LoRa 32 V3               | 1.7-1.8 V      | `false` (DIO2)       |
+/// | RAK4631                              | 1.8 V          | `false` (DIO2)       |
+/// | Ebyte E22-xxxM30S, Waveshare HAT     | 1.8 V          | `true` (RXEN/TXEN)   |
+/// | Modules with a plain crystal         | `None`         | depends on module    |
+///
+/// Getting the TCXO wrong leaves the radio without a clock, so it never finishes calibrating;
+/// getting the switch wrong leaves the antenna connected to the wrong path, so transmissions go
+/// nowhere or nothing is heard. lora-phy always sets DIO2 up to drive the switch for the SX1262,
+/// which does no harm on boards where DIO2 isn't connected to anything.
+#[derive(Clone, Copy)]
+pub struct RadioWiring {
+    /// Voltage DIO3 supplies to the TCXO; `None` for boards with a plain crystal
+    pub tcxo_voltage: Option<TcxoCtrlVoltage>,
+
+    /// The antenna switch has its own RX/TX enable lines, passed to `Lora::new` as
+    /// `RfSwitchPins`, instead of (or as well as) DIO2
+    pub external_rf_switch: bool,
+}
+
+impl Default for RadioWiring {
+    /// Heltec WiFi LoRa 32 V3
+    fn default() -> Self {
+        Self {
+            tcxo_voltage: Some(TcxoCtrlVoltage::Ctrl1V7),
+            external_rf_switch: false,
+        }
+    }
+}
+
+/// GPIOs enabling the receive and transmit paths of an external antenna switch
+pub struct RfSwitchPins<'a> {
+    pub rx_enable: Output<'a>,
+    pub tx_enable: Output<'a>,
+}
+
+impl Default for LoraConfig {
+    fn default() -> Self {
+        Region::Us915.to_config()
+    }
+}
+
+impl LoraConfig {
+    /// This config on the frequency and spreading factor in `settings`, if they're legal in its
+    /// region
+    pub fn with_settings(self, settings: RadioSettings) -> Result<Self, LoraError> {
+        settings.check(self.region, bandwidth_hz(self.bandwidth))?;
+        let spreading_factor =
+            spreading_factor(settings.spreading_factor).ok_or(LoraError::InvalidConfig)?;
+
+        Ok(Self {
+            frequency: settings.frequency,
+            spreading_factor,
+            ..self
+        })
+    }
+}
+
+impl Region {
+    /// A configuration that's legal in this region as it is, to adjust from
+    ///
+    /// Uses the region's default frequency at the highest power it allows, up to 20 dBm, with
+    /// the `balanced` preset's modulation. Outside the Americas and Australia that's narrowed to
+    /// the 125 kHz channels the band plans there are laid out in, at coding rate 4/5 to keep the
+    /// airtime within duty cycle limits.
+    pub fn to_config(self) -> LoraConfig {
+        let config = match self {
+            Region::Us915 | Region::Au915 => LoraConfig::balanced(),
+            Region::Eu868 | Region::As923 => LoraConfig {
+                bandwidth: Bandwidth::_125KHz,
+                coding_rate: CodingRate::_4_5,
+                ..LoraConfig::balanced()
+            },
+        };
+
+        LoraConfig {
+            frequency: self.default_frequency(),
+            tx_power: self.max_tx_power().min(20),
+            region: self,
+            duty_cycle: self.duty_cycle(),
+            ..config
+        }
+    }
+}
+
+/// Presets by intent; set the fields directly for anything else
+///
+/// Bit rates are raw LoRa rates before packet overhead; sensitivities are SX1262 datasheet
+/// figures, each 3 dB of sensitivity roughly worth 40% more line-of-sight range.
+impl LoraConfig {
+    /// SF12 / 125 kHz / 4/8: ~180 bit/s, ~-137 dBm sensitivity
+    ///
+    /// Maximum range, but a 12-byte position packet spends over a second on air; too long for the
+    /// dwell time limit of `Region::Us915`, so `LoraConfigBuilder` won't accept it there.
+    pub fn long_range() -> Self {
+        Self {
+            frequency: LORA_FREQUENCY,
+            spreading_factor: SpreadingFactor::_12,
+            bandwidth: Bandwidth::_125KHz,
+            coding_rate: CodingRate::_4_8,
+            tx_power: 20,
+            region: Region::Us915,
+            tx_channels: ChannelPlan::default(),
+            hopping: None,
+            adaptive_sf: None,
+            wiring: RadioWiring::default(),
+            single_shot_rx: false,
+            sleep_when_idle: false,
+            ack_retries: 3,
+            crc_on: true,
+            crypto: false,
+            duty_cycle: None,
+        }
+    }
+
+    /// SF10 / 250 kHz / 4/8: ~1.2 kbit/s, ~-129 dBm sensitivity
+    ///
+    /// Several kilometres in the open at a fraction of the long-range airtime.
+    pub fn balanced() -> Self {
+        Self {
+            frequency: LORA_FREQUENCY,
+            spreading_factor: SpreadingFactor::_10,
+            bandwidth: Bandwidth::_250KHz,
+            coding_rate: CodingRate::_4_8,
+            tx_power: 20,
+            region: Region::Us915,
+            tx_channels: ChannelPlan::default(),
+            hopping: None,
+            adaptive_sf: None,
+            wiring: RadioWiring::default(),
+            single_shot_rx: false,
+            sleep_when_idle: false,
+            ack_retries: 3,
+            crc_on: true,
+            crypto: false,
+            duty_cycle: None,
+        }
+    }
+
+    /// SF7 / 250 kHz / 4/5: ~11 kbit/s, ~-121 dBm sensitivity
+    ///
+    /// For nodes within a kilometre or so; short airtime leaves room for frequent updates.
+    pub fn fast() -> Self {
+        Self {
+            frequency: LORA_FREQUENCY,
+            spreading_factor: SpreadingFactor::_7,
+            bandwidth: Bandwidth::_250KHz,
+            coding_rate: CodingRate::_4_5,
+            tx_power: 20,
+            region: Region::Us915,
+            tx_channels: ChannelPlan::default(),
+            hopping: None,
+            adaptive_sf: None,
+            wiring: RadioWiring::default(),
+            single_shot_rx: false,
+            sleep_when_idle: false,
+            ack_retries: 3,
+            crc_on: true,
+            crypto: false,
+            duty_cycle: None,
+        }
+    }
+}
+
+impl LoraConfig {
+    /// Start from the `balanced` preset and adjust
+    pub fn builder() -> LoraConfigBuilder {
+        LoraConfigBuilder {
+            config: Self::default(),
+        }
+    }
+}
+
+/// Chainable setters for `LoraConfig`, validating the combination as a whole in `build`
+pub struct LoraConfigBuilder {
+    config: LoraConfig,
+}
+
+impl LoraConfigBuilder {
+    pub fn frequency(mut self, frequency: u32) -> Self {
+        self.config.frequency = frequency;
+        self
+    }
+
+    pub fn spreading_factor(mut self, spreading_factor: SpreadingFactor) -> Self {
+        self.config.spreading_factor = spreading_factor;
+        self
+    }
+
+    pub fn bandwidth(mut self, bandwidth: Bandwidth) -> Self {
+        self.config.bandwidth = bandwidth;
+        self
+    }
+
+    pub fn coding_rate(mut self, coding_rate: CodingRate) -> Self {
+        self.config.coding_rate = coding_rate;
+        self
+    }
+
+    pub fn tx_power(mut self, tx_power: i32) -> Self {
+        self.config.tx_power = tx_power;
+        self
+    }
+
+    pub fn region(mut self, region: Region) -> Self {
+        self.config.region = region;
+        self
+    }
+
+    pub fn tx_channels(mut self, tx_channels: ChannelPlan) -> Self {
+        self.config.tx_channels = tx_channels;
+        self
+    }
+
+    pub fn hopping(mut self, hopping: FrequencyHopping) -> Self {
+        self.config.hopping = Some(hopping);
+        self
+    }
+
+    pub fn adaptive_sf(mut self, adaptive_sf: AdaptiveSfConfig) -> Self {
+        self.config.adaptive_sf = Some(adaptive_sf);
+        self
+    }
+
+    pub fn wiring(mut self, wiring: RadioWiring) -> Self {
+        self.config.wiring = wiring;
+        self
+    }
+
+    pub fn single_shot_rx(mut self, single_shot_rx: bool) -> Self {
+        self.config.single_shot_rx = single_shot_rx;
+        self
+    }
+
+    pub fn sleep_when_idle(mut self, sleep_when_idle: bool) -> Self {
+        self.config.sleep_when_idle = sleep_when_idle;
+        self
+    }
+
+    pub fn ack_retries(mut self, ack_retries: u8) -> Self {
+        self.config.ack_retries = ack_retries;
+        self
+    }
+
+    pub fn crc_on(mut self, crc_on: bool) -> Self {
+        self.config.crc_on = crc_on;
+        self
+    }
+
+    pub fn crypto(mut self, crypto: bool) -> Self {
+        self.config.crypto = crypto;
+        self
+    }
+
+    pub fn duty_cycle(mut self, duty_cycle: DutyCycleConfig) -> Self {
+        self.config.duty_cycle = Some(duty_cycle);
+        self
+    }
+
+    /// Check every frequency, the output power and the slowest spreading factor in use against
+    /// the region, returning `LoraError::InvalidConfig` on the first violation
+    pub fn build(self) -> Result<LoraConfig, LoraError> {
+        let config = self.config;
+        let region = config.region;
+        let bandwidth = bandwidth_hz(config.bandwidth);
+
+        region.check_frequency(config.frequency, bandwidth)?;
+        for &frequency in config.tx_channels.channels() {
+            region.check_frequency(frequency, bandwidth)?;
+        }
+        if let Some(hopping) = &config.hopping {
+            if !config.tx_channels.is_empty() {
+                return Err(LoraError::InvalidConfig);
+            }
+            for &frequency in hopping.channels.channels() {
+                region.check_frequency(frequency, bandwidth)?;
+            }
+        }
+
+        region.check_tx_power(config.tx_power)?;
+
+        if let Some(required) = region.duty_cycle() {
+            match &config.duty_cycle {
+                Some(duty_cycle) if duty_cycle.limit_permille <= required.limit_permille => {}
+                _ => return Err(LoraError::InvalidConfig),
+            }
+        }
+
+        // Adaptive SF may step up to its maximum, so that's the one that has to fit
+        let slowest = match &config.adaptive_sf {
+            Some(adaptive) => {
+                let current = sf_number(config.spreading_factor);
+                if !(adaptive.min_spreading_factor..=adaptive.max_spreading_factor)
+                    .contains(&current)
+                {
+                    return Err(LoraError::InvalidConfig);
+                }
+                adaptive.max_spreading_factor
+            }
+            None => sf_number(config.spreading_factor),
+        };
+        region.check_modulation(slowest, bandwidth)?;
+
+        Ok(config)
+    }
+}
+
+pub struct Lora<'a> {
+    lora: LoRa<
+        Sx126x<
+            embassy_embedded_hal::shared_bus::asynch::spi::SpiDevice<
+                'a,
+                CriticalSectionRawMutex,
+                esp_hal::spi::master::Spi<'a, Async>,
+                Output<'a>,
+            >,
+            GenericSx126xInterfaceVariant<Output<'a>, Input<'a>>,
+            Sx1262,
+        >,
+        embassy_time::Delay,
+    >,
+    config: LoraConfig,
+    adaptive_sf: Option<AdaptiveSf>,
+    delta_encoder: DeltaEncoder,
+    delta_decoder: DeltaDecoder,
+    health: HealthMonitor,
+    modulation_params: ModulationParams,
+    rx_packet_params: PacketParams,
+    tx_packet_params: PacketParams,
+    rx_buffer: [u8; RX_BUFFER_SIZE],
+
+    /// RSSI and SNR of the last frame received intact, see `last_rx_stats`
+    last_rx_stats: Option<(i16, i16)>,
+
+    /// Source ID of sent frames, set by `run` from `BroadcastConfig::node_id`
+    node_id: u32,
+
+    /// Sequence number of the next frame sent
+    sequence: u8,
+
+    /// Set by `run` when `BroadcastConfig::relay` enables relaying
+    relay: Option<Relay>,
+
+    /// Envelope of a received packet waiting to be forwarded, its length in `forward_len`
+    forward_buffer: [u8; RX_BUFFER_SIZE],
+    forward_len: Option<usize>,
+
+    /// Source and sequence number of a received frame that asked for an ACK not yet sent
+    pending_ack: Option<(u32, u8)>,
+
+    /// Sequence number in the last ACK addressed to this node
+    last_ack: Option<u8>,
+
+    /// Frames missed from each peer, by the gaps in their sequence numbers
+    sequence_tracker: SequenceTracker<TRACKED_NODES>,
+
+    /// Encrypts sent packets and decrypts received ones, set by `set_crypto`
+    crypto: Option<LoraCrypto>,
+
+    /// Airtime spent under `LoraConfig::duty_cycle`
+    duty_cycle: Option<DutyCycleLimiter>,
+}
+
+impl<'a> Lora<'a> {
+    /// Create a new LoRa instance with the Embassy SPI device
+    ///
+    /// TX and RX completion are awaited on `dio1` and command readiness on `busy`, both as GPIO
+    /// interrupts rather than by polling; neither pin needs any setup beyond being an `Input`.
+    pub async fn new(
+        spi_device: embassy_embedded_hal::shared_bus::asynch::spi::SpiDevice<
+            'a,
+            CriticalSectionRawMutex,
+            esp_hal::spi::master::Spi<'a, Async>,
+            Output<'a>,
+        >,
+        reset: Output<'a>,
+        dio1: Input<'a>,
+        busy: Input<'a>,
+        rf_switch: Option<RfSwitchPins<'a>>,
+        config: LoraConfig,
+    ) -> Result<Self, LoraError> {
+        // Pins without a switch to drive, or a switch without pins, is a board mix-up
+        if config.wiring.external_rf_switch != rf_switch.is_some() {
+            defmt::error!(
+                "External RF switch configured: {}, pins given: {}",
+                config.wiring.external_rf_switch,
+                rf_switch.is_some()
+            );
+            return Err(LoraError::InvalidConfig);
+        }
+
+        // Create the interface variant
+        let (rx_enable, tx_enable) = match rf_switch {
+            Some(pins) => (Some(pins.rx_enable), Some(pins.tx_enable)),
+            None => (None, None),
+        };
+        let iv = match GenericSx126xInterfaceVariant::new(reset, dio1, busy, rx_enable, tx_enable) {
+            Ok(iv) => iv,
+            Err(_) => return Err(LoraError::InvalidConfig),
+        };
+
+        // Create the SX126x configuration
+        let sx126x_config = sx126x::Config {
+            chip: Sx1262,
+            tcxo_ctrl: config.wiring.tcxo_voltage,
+            use_dcdc: false,
+            rx_boost: true,
+        };
+
+        // Create the radio instance
+        let radio = Sx126x::new(spi_device, iv, sx126x_config);
+        let mut lora = LoRa::new(radio, false, embassy_time::Delay).await?;
+
+        let modulation_params = lora.create_modulation_params(
+            config.spreading_factor,
+            config.bandwidth,
+            config.coding_rate,
+            config.frequency,
+        )?;
+
+        let rx_packet_params = lora.create_rx_packet_params(
+            PREAMBLE_LEN,
+            false,
+            RX_BUFFER_SIZE as u8,
+            config.crc_on,
+            false,
+            &modulation_params,
+        )?;
+        let tx_packet_params = lora.create_tx_packet_params(
+            PREAMBLE_LEN,
+            false,
+            config.crc_on,
+            false,
+            &modulation_params,
+        )?;
+
+        let duty_cycle = config.duty_cycle.map(DutyCycleLimiter::new);
+        let adaptive_sf = config.adaptive_sf.map(|adaptive_config| {
+            AdaptiveSf::new(adaptive_config, sf_number(config.spreading_factor))
+        });
+
+        Ok(Self {
+            lora,
+            config,
+            adaptive_sf,
+            delta_encoder: DeltaEncoder::default(),
+            delta_decoder: DeltaDecoder::new(),
+            health: HealthMonitor::new(),
+            modulation_params,
+            rx_packet_params,
+            tx_packet_params,
+            rx_buffer: [0; RX_BUFFER_SIZE],
+            last_rx_stats: None,
+            node_id: 0,
+            sequence: 0,
+            relay: None,
+            forward_buffer: [0; RX_BUFFER_SIZE],
+            forward_len: None,
+            pending_ack: None,
+            last_ack: None,
+            sequence_tracker: SequenceTracker::new(),
+            crypto: None,
+            duty_cycle,
+        })
+    }
+
+    /// Encrypt packets from now on, and decrypt encrypted ones received
+    pub fn set_crypto(&mut self, crypto: LoraCrypto) {
+        self.crypto = Some(crypto);
+    }
+
+    /// Time on air of `payload_len` bytes at the current modulation, in milliseconds rounded up
+    ///
+    /// `payload_len` is everything the radio sends, so a packet's frame header counts too:
+    /// `HEADER_LEN - 1` bytes more than the packet, plus `CRC_LEN` for frames that carry one and
+    /// `crypto::ENCRYPTED_HEADER_LEN` when encrypted. The longest is `RX_BUFFER_SIZE`.
+    pub fn time_on_air_ms(&self, payload_len: usize) -> u32 {
+        self.time_on_air_us(payload_len).div_ceil(1_000) as u32
+    }
+
+    fn time_on_air_us(&self, payload_len: usize) -> u64 {
+        time_on_air_us(
+            sf_number(self.config.spreading_factor),
+            bandwidth_hz(self.config.bandwidth),
+            coding_rate_number(self.config.coding_rate),
+            PREAMBLE_LEN,
+            payload_len,
+            self.config.crc_on,
+        )
+    }
+
+    /// RSSI in dBm and SNR in dB of the most recent frame received intact; `None` until one is
+    ///
+    /// Frames that fail the CRC don't count, lora-phy doesn't report their signal.
+    pub fn last_rx_stats(&self) -> Option<(i16, i16)> {
+        self.last_rx_stats
+    }
+
+    /// Rebuild the modulation parameters for another frequency, keeping SF/BW/CR
+    fn set_frequency(&mut self, frequency: u32) -> Result<(), LoraError> {
+        self.modulation_params = self.lora.create_modulation_params(
+            self.config.spreading_factor,
+            self.config.bandwidth,
+            self.config.coding_rate,
+            frequency,
+        )?;
+
+        Ok(())
+    }
+
+    /// Move to the channel `LoraConfig::hopping` has for the current GPS time
+    ///
+    /// Does nothing when not hopping, or without GPS time to go by.
+    pub fn hop(&mut self) -> Result<(), LoraError> {
+        let Some(frequency) = self
+            .config
+            .hopping
+            .as_ref()
+            .and_then(|hopping| hopping.channel_at(gps_time_ms()?))
+        else {
+            return Ok(());
+        };
+
+        if frequency != self.config.frequency {
+            defmt::debug!("Hopping to {} Hz", frequency);
+            self.config.frequency = frequency;
+            self.set_frequency(frequency)?;
+        }
+
+        Ok(())
+    }
+
+    /// Time left on the current channel while hopping, `None` otherwise
+    fn until_next_hop(&self) -> Option<Duration> {
+        let hopping = self.config.hopping.as_ref()?;
+
+        Some(Duration::from_millis(
+            hopping.until_next_hop(gps_time_ms()?),
+        ))
+    }
+
+    /// Switch to another spreading factor, rebuilding the parameters that depend on it
+    ///
+    /// Takes effect from the next transmission or receive window.
+    fn set_spreading_factor(&mut self, spreading_factor: SpreadingFactor) -> Result<(), LoraError> {
+        self.config.spreading_factor = spreading_factor;
+        self.set_frequency(self.config.frequency)?;
+        self.publish_settings();
+
+        self.rebuild_packet_params()
+    }
+
+    /// Switch to `settings`, and store them for the next boot, if they're legal in the region
+    ///
+    /// Takes effect from the next transmission or receive window, even if storing them fails.
+    fn apply_settings(&mut self, settings: RadioSettings) -> Result<(), LoraError> {
+        settings.check(self.config.region, bandwidth_hz(self.config.bandwidth))?;
+        let spreading_factor =
+            spreading_factor(settings.spreading_factor).ok_or(LoraError::InvalidConfig)?;
+
+        self.set_node_id(settings.node_id);
+        set_reporting(&settings);
+        self.config.frequency = settings.frequency;
+        self.set_spreading_factor(spreading_factor)?;
+
+        if let Err(e) = persist::store(&settings) {
+            defmt::warn!(
+                "Failed to store the radio settings: {:?}",
+                defmt::Debug2Format(&e)
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Send frames, relayed ones included, from `node_id`
+    fn set_node_id(&mut self, node_id: u32) {
+        self.node_id = node_id;
+        if let Some(relay) = &mut self.relay {
+            relay.set_node_id(node_id);
+        }
+    }
+
+    /// Make the radio settings in use available to `radio_settings`
+    fn publish_settings(&self) {
+        let settings = RadioSettings {
+            frequency: self.config.frequency,
+            spreading_factor: sf_number(self.config.spreading_factor),
+            node_id: self.node_id,
+            report_format: ReportFormat::try_from(REPORT_FORMAT.load(Ordering::Relaxed))
+                .unwrap_or_default(),
+            coordinate_precision: CoordinatePrecision::current(),
+        };
+        let bandwidth_hz = bandwidth_hz(self.config.bandwidth);
+
+        LIVE_SETTINGS.lock(|live| live.set(Some((settings, self.config.region, bandwidth_hz))));
+    }
+
+    /// Recreate the RX and TX packet parameters for the current modulation parameters
+    fn rebuild_packet_params(&mut self) -> Result<(), LoraError> {
+        self.rx_packet_params = self.lora.create_rx_packet_params(
+            PREAMBLE_LEN,
+            false,
+            RX_BUFFER_SIZE as u8,
+            self.config.crc_on,
+            false,
+            &self.modulation_params,
+        )?;
+        self.tx_packet_params = self.lora.create_tx_packet_params(
+            PREAMBLE_LEN,
+            false,
+            self.config.crc_on,
+            false,
+            &self.modulation_params,
+        )?;
+
+        Ok(())
+    }
+
+    /// Switch to another configuration without recreating the radio
+    ///
+    /// Puts the radio in standby first, so a receive left running by a cancelled listen doesn't
+    /// carry on with the old parameters; the new ones apply from the next receive or transmit.
+    /// Adaptive SF restarts from the new spreading factor. Airtime already spent keeps counting
+    /// against a new duty cycle limit, unless there was no limit before. `wiring` is only applied
+    /// by `new`, so a different one here is ignored. The config isn't checked against its region;
+    /// build it with `LoraConfig::builder` for that.
+    pub async fn reconfigure(&mut self, mut config: LoraConfig) -> Result<(), LoraError> {
+        self.lora.enter_standby().await?;
+
+        config.wiring = self.config.wiring;
+        self.modulation_params = self.lora.create_modulation_params(
+            config.spreading_factor,
+            config.bandwidth,
+            config.coding_rate,
+            config.frequency,
+        )?;
+        self.adaptive_sf = config.adaptive_sf.map(|adaptive_config| {
+            AdaptiveSf::new(adaptive_config, sf_number(config.spreading_factor))
+        });
+        if config.duty_cycle != self.config.duty_cycle {
+            self.duty_cycle = match (self.duty_cycle.take(), config.duty_cycle) {
+                (Some(mut limiter), Some(duty_cycle)) => {
+                    limiter.set_config(duty_cycle, Instant::now().as_millis());
+                    Some(limiter)
+                }
+                (_, duty_cycle) => duty_cycle.map(DutyCycleLimiter::new),
+            };
+        }
+        self.config = config;
+
+        self.rebuild_packet_params()?;
+        self.publish_settings();
+        defmt::info!(
+            "Reconfigured: {} Hz, SF{}",
+            self.config.frequency,
+            sf_number(self.config.spreading_factor)
+        );
+
+        Ok(())
+    }
+
+    /// Let the adaptive spreading factor react to the SNR of a received packet
+    fn adapt_spreading_factor(&mut self, snr: i16) {
+        let Some(adaptive_sf) = self.adaptive_sf.as_mut() else {
+            return;
+        };
+        let Some(change) = adaptive_sf.on_snr(snr as f32, Instant::now().as_millis()) else {
+            return;
+        };
+        let Some(spreading_factor) = spreading_factor(change.to) else {
+            return;
+        };
+
+        defmt::info!(
+            "Spreading factor SF{} -> SF{} (average SNR {} dB)",
+            change.from,
+            change.to,
+            change.snr
+        );
+
+        if let Err(e) = self.set_spreading_factor(spreading_factor) {
+            defmt::error!(
+                "Failed to apply SF{}: {:?}",
+                change.to,
+                defmt::Debug2Format(&e)
+            );
+        }
+    }
+
+    /// Transmit on the next channel of the plan, then return to the receive frequency
+    async fn broadcast(&mut self, data: &[u8]) -> Result<(), LoraError> {
+        let Some(frequency) = self.config.tx_channels.advance() else {
+            return self.send(data).await;
+        };
+
+        defmt::debug!("Broadcasting on {} Hz", frequency);
+        self.set_frequency(frequency)?;
+        let result = self.send(data).await;
+        self.set_frequency(self.config.frequency)?;
+
+        result
+    }
+
+    /// Broadcast `positioning` in the format currently selected in `REPORT_FORMAT`
+    async fn broadcast_position(&mut self, positioning: &GnssPositioning) -> Result<(), LoraError> {
+        let format =
+            ReportFormat::try_from(REPORT_FORMAT.load(Ordering::Relaxed)).unwrap_or_default();
+
+        let mut buffer = [0u8; 32];
+        let len = match format {
+            ReportFormat::Delta => self.delta_encoder.encode(
+                positioning.latitude,
+                positioning.longitude,
+                &mut buffer,
+            )?,
+            // The other formats are for receivers that want full precision
+            ReportFormat::Compact => {
+                let precision = CoordinatePrecision::current();
+                let report = PositionReport {
+                    latitude: precision.quantize(positioning.latitude),
+                    longitude: precision.quantize(positioning.longitude),
+                    ..PositionReport::from(positioning)
+                };
+
+                encode_report(format, &report, &mut buffer)?
+            }
+            _ => encode_report(format, &PositionReport::from(positioning), &mut buffer)?,
+        };
+
+        // Relays only pass on what comes wrapped, so with relaying on, this node's reports are too
+        if let Some(relay) = &mut self.relay {
+            let mut relayed = [0u8; 40];
+            let len = relay.originate(&buffer[..len], &mut relayed)?;
+
+            return self.broadcast(&relayed[..len]).await;
+        }
+
+        self.broadcast(&buffer[..len]).await
+    }
+
+    /// Carry out a command taken from `LORA_TX_QUEUE`
+    async fn handle_command(&mut self, command: LoraCommand) {
+        match command {
+            LoraCommand::Send(packet) => match self.broadcast(&packet).await {
+                Ok(()) => defmt::debug!("Sent queued packet ({} bytes)", packet.len()),
+                Err(e) => defmt::error!(
+                    "Failed to send queued packet: {:?}",
+                    defmt::Debug2Format(&e)
+                ),
+            },
+            LoraCommand::SetSpreadingFactor(number) => {
+                let result = spreading_factor(number)
+                    .ok_or(LoraError::InvalidConfig)
+                    .and_then(|sf| {
+                        let bandwidth = bandwidth_hz(self.config.bandwidth);
+                        self.config.region.check_modulation(number, bandwidth)?;
+                        self.set_spreading_factor(sf)
+                    });
+
+                match result {
+                    Ok(()) => defmt::info!("Spreading factor set to SF{}", number),
+                    Err(e) => defmt::warn!(
+                        "Not switching to SF{}: {:?}",
+                        number,
+                        defmt::Debug2Format(&e)
+                    ),
+                }
+            }
+            LoraCommand::Reconfigure(settings) => match self.apply_settings(settings) {
+                Ok(()) => defmt::info!(
+                    "Switched to {} Hz, SF{}, node {=u32:#x}",
+                    settings.frequency,
+                    settings.spreading_factor,
+                    settings.node_id
+                ),
+                Err(e) => defmt::warn!(
+                    "Not switching to {} Hz, SF{}: {:?}",
+                    settings.frequency,
+                    settings.spreading_factor,
+                    defmt::Debug2Format(&e)
+                ),
+            },
+        }
+    }
+
+    /// Transmit the ACK and the forwarded packet `receive_packet` left pending, if any
+    ///
+    /// The ACK goes first and right away: the sender is listening for it with a timeout, and
+    /// gains nothing from the jitter that keeps relays apart.
+    async fn send_pending(&mut self) {
+        if let Some((destination, sequence)) = self.pending_ack.take() {
+            match self.acknowledge(destination, sequence).await {
+                Ok(()) => defmt::debug!(
+                    "Acknowledged frame {} from {=u32:#x}",
+                    sequence,
+                    destination
+                ),
+                Err(e) => defmt::error!("Failed to send ACK: {:?}", defmt::Debug2Format(&e)),
+            }
+        }
+
+        let Some(len) = self.forward_len.take() else {
+            return;
+        };
+
+        Timer::after_millis(Instant::now().as_ticks() % RELAY_JITTER_MS).await;
+
+        let mut data = [0u8; RX_BUFFER_SIZE];
+        data[..len].copy_from_slice(&self.forward_buffer[..len]);
+        match self.broadcast(&data[..len]).await {
+            Ok(()) => defmt::debug!("Forwarded relayed packet ({} bytes)", len),
+            Err(e) => defmt::error!("Failed to forward packet: {:?}", defmt::Debug2Format(&e)),
+        }
+    }
+
+    /// Send `destination` a `PacketType::Ack` for its frame `sequence`
+    async fn acknowledge(&mut self, destination: u32, sequence: u8) -> Result<(), LoraError> {
+        let mut ack = [0u8; 2];
+        let len = encode_ack(sequence, &mut ack)?;
+        let flags = FrameFlags {
+            crc: true,
+            ..FrameFlags::default()
+        };
+
+        let own_sequence = self.next_sequence();
+        self.transmit(destination, own_sequence, &ack[..len], flags)
+            .await
+    }
+
+    /// Beacon with this node's battery level and how well it hears its peers
+    async fn broadcast_status(&mut self) -> Result<(), LoraError> {
+        let status = NodeStatus {
+            battery_percent: BATTERY_PERCENT.try_get(),
+            last_rssi: RADIO_STATS.lock(|stats| stats.get()).last_rssi,
+            flags: self.health.sample(),
+        };
+
+        let mut buffer = [0u8; 8];
+        let len = encode_status(&status, &mut buffer)?;
+
+        self.broadcast(&buffer[..len]).await
+    }
+
+    async fn receive(&mut self) {
+        self.lora
+            .prepare_for_rx(
+                RxMode::Continuous,
+                &self.modulation_params,
+                &self.rx_packet_params,
+            )
+            .await
+            .unwrap();
+
+        loop {
+            let result = self
+                .lora
+                .rx(&self.rx_packet_params, &mut self.rx_buffer)
+                .await;
+            self.receive_packet(result);
+        }
+    }
+
+    /// Log and count the outcome of a single receive
+    ///
+    /// lora-phy discards the payload of a frame that fails the CRC, so only the fact that one
+    /// arrived can be reported. With `LoraConfig::crc_on` off the radio doesn't check, and it's
+    /// left to the frame's own CRC.
+    fn receive_packet(&mut self, result: Result<(u8, PacketStatus), RadioError>) {
+        match result {
+            Ok((received_len, rx_pkt_status)) => {
+                update_stats(|stats| stats.record_packet(rx_pkt_status.rssi));
+                self.last_rx_stats = Some((rx_pkt_status.rssi, rx_pkt_status.snr));
+                defmt::info!(
+                    "Received {} bytes, RSSI {} dBm, SNR {} dB",
+                    received_len,
+                    rx_pkt_status.rssi,
+                    rx_pkt_status.snr
+                );
+                LAST_PACKET_AT.lock(|last_packet_at| last_packet_at.set(Some(Instant::now())));
+                self.adapt_spreading_factor(rx_pkt_status.snr);
+
+                let (header, payload) = match decode_frame(&self.rx_buffer[..received_len as usize])
+                {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        defmt::warn!("Dropping malformed frame: {:?}", defmt::Debug2Format(&e));
+                        return;
+                    }
+                };
+                // Frames for other nodes take sequence numbers too
+                let missed = self.sequence_tracker.record(header.source, header.sequence);
+                if missed > 0 {
+                    if let Some(node) = self.sequence_tracker.node(header.source) {
+                        defmt::warn!(
+                            "Missed {} frames from {=u32:#x}, {} of {} lost so far",
+                            missed,
+                            header.source,
+                            node.lost,
+                            node.lost + node.received
+                        );
+                    }
+                }
+
+                if !header.is_for(self.node_id) {
+                    defmt::debug!("Ignoring frame for {=u32:#x}", header.destination);
+                    return;
+                }
+
+                let mut decrypted = [0u8; RX_BUFFER_SIZE];
+                let payload = if payload.first() == Some(&(PacketType::Encrypted as u8)) {
+                    let Some(crypto) = &self.crypto else {
+                        defmt::debug!("Dropping encrypted frame, no key");
+                        return;
+                    };
+                    match crypto.decrypt(header.source, payload, &mut decrypted) {
+                        Ok(packet) => packet,
+                        Err(e) => {
+                            defmt::warn!(
+                                "Dropping undecryptable frame: {:?}",
+                                defmt::Debug2Format(&e)
+                            );
+                            return;
+                        }
+                    }
+                } else {
+                    payload
+                };
+
+                // Every copy is acknowledged, since a retransmission means the last ACK was lost
+                if header.flags.ack_requested {
+                    self.pending_ack = Some((header.source, header.sequence));
+                }
+                if header.destination != BROADCAST {
+                    if let Ok(sequence) = decode_ack(payload) {
+                        self.last_ack = Some(sequence);
+                    }
+                }
+
+                // The payload can't be longer than the buffer it was received into
+                LORA_RX.sender().send(ReceivedPacket {
+                    payload: heapless::Vec::from_slice(payload).unwrap_or_default(),
+                    status: rx_pkt_status,
+                });
+
+                if payload.first() != Some(&(PacketType::Relayed as u8)) {
+                    defmt::debug!("Frame {} from {=u32:#x}", header.sequence, header.source);
+                    Self::log_payload(&mut self.delta_decoder, payload);
+                    return;
+                }
+
+                // Forwarding doesn't depend on understanding what's inside
+                if let Some(relay) = &mut self.relay {
+                    match relay.handle(payload, &mut self.forward_buffer) {
+                        Ok(RelayVerdict::Forward(len)) => self.forward_len = Some(len),
+                        Ok(RelayVerdict::HopLimit) => defmt::debug!("Not forwarding: hop limit"),
+                        // Logged when it was first heard
+                        Ok(RelayVerdict::Duplicate | RelayVerdict::Own) => return,
+                        Err(e) => {
+                            defmt::warn!("Bad relayed packet: {:?}", defmt::Debug2Format(&e));
+                            return;
+                        }
+                    }
+                }
+
+                match decode_relayed(payload) {
+                    Ok((header, packet)) => {
+                        defmt::info!(
+                            "Relayed from {=u32:#x}, {} hops:",
+                            header.source,
+                            header.hops
+                        );
+                        Self::log_payload(&mut self.delta_decoder, packet);
+                    }
+                    Err(e) => defmt::warn!("Bad relayed packet: {:?}", defmt::Debug2Format(&e)),
+                }
+            }
+            Err(RadioError::CRCErrorOnReceive) => {
+                let stats = update_stats(RadioStats::record_crc_error);
+                defmt::warn!(
+                    "Dropped frame with bad CRC ({} of {} frames)",
+                    stats.crc_error_count,
+                    stats.crc_error_count + stats.packets_received
+                );
+            }
+            Err(err) => {
+                update_stats(RadioStats::record_other_error);
+                errorlog::recent::record(ErrorCode::LoraRadio);
+                defmt::error!("RX error: {}", err);
+            }
+        }
+    }
+
+    /// Log a received packet, decoded by its type byte
+    ///
+    /// Each type goes to its own decoder, and only `PacketType::Text` is taken for text, so a
+    /// binary packet that fails to decode is reported as such rather than tried as something
+    /// else. Takes the decoder rather than `self` so it can be given the inside of a relayed
+    /// packet, which is borrowed from the receive buffer.
+    fn log_payload(delta_decoder: &mut DeltaDecoder, payload: &[u8]) {
+        let Some(Ok(packet_type)) = payload.first().copied().map(PacketType::try_from) else {
+            defmt::warn!("Received packet of unknown type: {:?}", payload);
+            return;
+        };
+
+        let decoded = match packet_type {
+            PacketType::Position
+            | PacketType::CompactPosition
+            | PacketType::StandardPosition
+            | PacketType::FullPosition => decode_report(payload).map(|report| {
+                defmt::info!(
+                    "Peer position: {}, {} ({} bytes)",
+                    report.latitude,
+                    report.longitude,
+                    payload.len()
+                )
+            }),
+            PacketType::PositionKeyframe | PacketType::PositionDelta => {
+                match delta_decoder.decode(payload) {
+                    Ok((latitude, longitude)) => defmt::info!(
+                        "Peer position: {}, {} ({} bytes)",
+                        latitude,
+                        longitude,
+                        payload.len()
+                    ),
+                    Err(e) => {
+                        defmt::debug!("Skipping position delta: {:?}", defmt::Debug2Format(&e))
+                    }
+                }
+                Ok(())
+            }
+            PacketType::Status => decode_status(payload).map(|status| {
+                defmt::info!(
+                    "Peer status: battery {:?}%, last RSSI {:?} dBm, flags {=u8:#b}",
+                    status.battery_percent,
+                    status.last_rssi,
+                    u8::from(status.flags)
+                )
+            }),
+            PacketType::Ack => decode_ack(payload)
+                .map(|sequence| defmt::info!("Peer acknowledged frame {}", sequence)),
+            // Nothing answers polls yet; the next scheduled report goes out as usual
+            PacketType::Poll => {
+                defmt::info!("Polled for position");
+                Ok(())
+            }
+            PacketType::Text => decode_text(payload).map(|text| defmt::info!("Received: {}", text)),
+            // Unwrapped by `receive_packet` before they get here; one inside another isn't sent
+            PacketType::Relayed | PacketType::Encrypted => {
+                Err(LoraError::UnexpectedPacketType(packet_type as u8))
+            }
+        };
+
+        if let Err(e) = decoded {
+            defmt::warn!(
+                "Received undecodable {:?} packet: {:?}, {:?}",
+                defmt::Debug2Format(&packet_type),
+                defmt::Debug2Format(&e),
+                payload
+            );
+        }
+    }
+
+    /// Transmit `packet` to everyone in a frame with this node's header, see `header::Header`
+    async fn send(&mut self, packet: &[u8]) -> Result<(), LoraError> {
+        let sequence = self.next_sequence();
+        let flags = FrameFlags {
+            crc: true,
+            ..FrameFlags::default()
+        };
+
+        self.transmit(BROADCAST, sequence, packet, flags).await
+    }
+
+    /// Take the sequence number for the next frame sent
+    fn next_sequence(&mut self) -> u8 {
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+
+        sequence
+    }
+
+    /// Frame `packet`, encrypted if there's a key, and transmit it on the current frequency, or
+    /// the current channel while hopping
+    async fn transmit(
+        &mut self,
+        destination: u32,
+        sequence: u8,
+        packet: &[u8],
+        flags: FrameFlags,
+    ) -> Result<(), LoraError> {
+        let mut encrypted = [0u8; RX_BUFFER_SIZE];
+        let packet = match &mut self.crypto {
+            Some(crypto) => {
+                let len = crypto.encrypt(self.node_id, packet, &mut encrypted)?;
+                &encrypted[..len]
+            }
+            None => packet,
+        };
+
+        let mut frame = [0u8; RX_BUFFER_SIZE];
+        let len = encode_frame(
+            self.node_id,
+            destination,
+            sequence,
+            packet,
+            flags,
+            &mut frame,
+        )?;
+
+        // Waiting for a slot, or for the radio to be free, can run past a hop
+        self.hop()?;
+        let airtime_us = self.time_on_air_us(len);
+        if let Some(limiter) = &mut self.duty_cycle {
+            limiter.try_spend(Instant::now().as_millis(), airtime_us)?;
+        }
+
+        self.lora
+            .prepare_for_tx(
+                &self.modulation_params,
+                &mut self.tx_packet_params,
+                self.config.tx_power,
+                &frame[..len],
+            )
+            .await?;
+
+        match self.lora.tx().await {
+            Ok(()) => {
+                defmt::info!("TX DONE");
+                update_stats(RadioStats::record_sent);
+
+                Ok(())
+            }
+            Err(err) => {
+                errorlog::recent::record(ErrorCode::LoraRadio);
+                defmt::error!("Radio error = {}", err);
+                Err(LoraError::TransmissionError)
+            }
+        }
+    }
+
+    /// Transmit `packet` once channel activity detection finds the channel clear
+    ///
+    /// CAD listens for a LoRa preamble at the current spreading factor and bandwidth, taking a
+    /// few symbols. Transmissions at other spreading factors, and anything that isn't LoRa, go
+    /// unnoticed. After `CAD_ATTEMPTS` busy checks this gives up with `LoraError::ChannelBusy`.
+    pub async fn transmit_with_cad(&mut self, packet: &[u8]) -> Result<(), LoraError> {
+        for attempt in 1..=CAD_ATTEMPTS {
+            // Check the channel the packet will go out on
+            self.hop()?;
+            self.lora.prepare_for_cad(&self.modulation_params).await?;
+            if !self.lora.cad(&self.modulation_params).await? {
+                return self.send(packet).await;
+            }
+
+            let backoff_ms = CAD_BACKOFF_MIN_MS + Instant::now().as_ticks() % CAD_BACKOFF_SPREAD_MS;
+            defmt::debug!(
+                "Channel busy (attempt {} of {}), backing off {} ms",
+                attempt,
+                CAD_ATTEMPTS,
+                backoff_ms
+            );
+            Timer::after_millis(backoff_ms).await;
+        }
+
+        Err(LoraError::ChannelBusy)
+    }
+
+    /// Broadcast `data` and wait up to `timeout` for a peer to acknowledge it, retransmitting
+    /// up to `LoraConfig::ack_retries` times before giving up with `LoraError::Timeout`
+    ///
+    /// Stop-and-wait: the frame asks for an ACK, which receivers send back automatically, and
+    /// the first one that arrives is enough. Retransmissions keep the sequence number, so a
+    /// receiver can tell a repeat from a new packet. Anything else received while waiting is
+    /// handled as usual.
+    pub async fn send_reliable(&mut self, data: &[u8], timeout: Duration) -> Result<(), LoraError> {
+        let sequence = self.next_sequence();
+        let flags = FrameFlags {
+            crc: true,
+            ack_requested: true,
+        };
+        self.last_ack = None;
+
+        for attempt in 0..=self.config.ack_retries {
+            if attempt > 0 {
+                defmt::debug!("No ACK for frame {}, retransmitting", sequence);
+            }
+            self.transmit(BROADCAST, sequence, data, flags).await?;
+
+            let deadline = Instant::now() + timeout;
+            loop {
+                self.lora
+                    .prepare_for_rx(
+                        RxMode::Continuous,
+                        &self.modulation_params,
+                        &self.rx_packet_params,
+                    )
+                    .await?;
+
+                match select(
+                    self.lora.rx(&self.rx_packet_params, &mut self.rx_buffer),
+                    Timer::at(deadline),
+                )
+                .await
+                {
+                    Either::First(result) => {
+                        self.receive_packet(result);
+                        self.send_pending().await;
+
+                        if self.last_ack == Some(sequence) {
+                            return Ok(());
+                        }
+                    }
+                    Either::Second(_) => break,
+                }
+            }
+        }
+
+        Err(LoraError::Timeout)
+    }
+
+    /// Wait for a single packet, leaving it to the radio to give up after `timeout`
+    ///
+    /// `RxMode::Single` has the SX1262 time out by itself and drop back to standby, so nothing is
+    /// left running after an `Err(LoraError::Timeout)`. That suits a node that only needs to hear
+    /// replies within a known window, or sleeps between short windows. `receive` and
+    /// `receive_for_duration` hold the receiver open in `RxMode::Continuous` instead, which is
+    /// what catching peers that transmit at unknown times takes.
+    ///
+    /// The radio counts the timeout in symbols, so it's rounded up to whole symbols at the current
+    /// spreading factor and bandwidth, and capped at `u16::MAX` of them. The count only covers
+    /// waiting for a preamble; a packet that starts before it runs out is received in full.
+    pub async fn receive_with_timeout(&mut self, timeout: Duration) -> Result<(), LoraError> {
+        let symbols = timeout_symbols(
+            timeout,
+            sf_number(self.config.spreading_factor),
+            bandwidth_hz(self.config.bandwidth),
+        );
+
+        self.lora
+            .prepare_for_rx(
+                RxMode::Single(symbols),
+                &self.modulation_params,
+                &self.rx_packet_params,
+            )
+            .await?;
+
+        let result = self
+            .lora
+            .rx(&self.rx_packet_params, &mut self.rx_buffer)
+            .await;
+        let outcome = match &result {
+            Ok(_) => Ok(()),
+            Err(RadioError::ReceiveTimeout) => return Err(LoraError::Timeout),
+            Err(e) => Err(LoraError::Radio(*e)),
+        };
+        self.receive_packet(result);
+
+        outcome
+    }
+
+    async fn receive_for_duration(&mut self, duration: Duration) {
+        defmt::info!(
+            "Listening for incoming packets for {} ms",
+            duration.as_millis()
+        );
+
+        if self.config.single_shot_rx {
+            // Each packet ends the window early, so keep reopening it for whatever's left
+            let deadline = Instant::now() + duration;
+            while let Some(remaining) = deadline
+                .checked_duration_since(Instant::now())
+                .filter(|remaining| remaining.as_ticks() > 0)
+            {
+                let received = select(
+                    self.receive_with_timeout(remaining),
+                    LORA_TX_QUEUE.receive(),
+                )
+                .await;
+                match received {
+                    Either::First(Ok(())) => self.send_pending().await,
+                    Either::First(Err(LoraError::Timeout)) => {
+                        defmt::debug!("Receive window timed out")
+                    }
+                    // Already counted and logged, and likely to fail again right away
+                    Either::First(Err(_)) => return,
+                    Either::Second(command) => self.handle_command(command).await,
+                }
+            }
+
+            return;
+        }
+
+        // A packet or queued command interrupts listening, which then resumes for the rest of
+        // `duration`
+        let deadline = Instant::now() + duration;
+        loop {
+            if let Err(e) = self
+                .lora
+                .prepare_for_rx(
+                    RxMode::Continuous,
+                    &self.modulation_params,
+                    &self.rx_packet_params,
+                )
+                .await
+            {
+                errorlog::recent::record(ErrorCode::LoraRadio);
+                defmt::error!("Failed to prepare for RX: {}", e);
+                return;
+            }
+
+            match select3(
+                self.lora.rx(&self.rx_packet_params, &mut self.rx_buffer),
+                Timer::at(deadline),
+                LORA_TX_QUEUE.receive(),
+            )
+            .await
+            {
+                Either3::First(result) => {
+                    self.receive_packet(result);
+                    self.send_pending().await;
+                }
+                Either3::Second(_) => {
+                    // Timeout occurred, duration has elapsed
+                    defmt::debug!("Receive time elapsed");
+                    return;
+                }
+                Either3::Third(command) => self.handle_command(command).await,
+            }
+        }
+    }
+
+    /// Keep listening until this node's transmit slot opens
+    ///
+    /// Packets and commands handled meanwhile don't cut the wait short.
+    async fn wait_for_slot(&mut self, time_slots: &TimeSlots) {
+        let gps_ms = gps_time_ms();
+        if gps_ms.is_none() {
+            defmt::debug!("No GPS time, broadcasting at a random offset instead of a slot");
+        }
+        let wait = SlotWait::start(
+            time_slots,
+            Instant::now().as_millis(),
+            gps_ms,
+            Instant::now().as_ticks() as u32,
+        );
+
+        loop {
+            let delay_ms = wait.remaining_ms(time_slots, Instant::now().as_millis(), gps_time_ms());
+            if delay_ms == 0 {
+                break;
+            }
+
+            defmt::debug!(
+                "Waiting {} ms for transmit slot {}",
+                delay_ms,
+                time_slots.slot()
+            );
+            // Listen on the channel peers are on, which changes at each hop
+            if let Err(e) = self.hop() {
+                defmt::warn!("Failed to hop: {:?}", defmt::Debug2Format(&e));
+            }
+            let delay = Duration::from_millis(delay_ms);
+            let delay = self
+                .until_next_hop()
+                .map_or(delay, |until_hop| delay.min(until_hop));
+            self.receive_for_duration(delay).await;
+        }
+    }
+
+    /// Put the radio to sleep, keeping its configuration so that `wake` is quick
+    ///
+    /// Nothing is received until it's woken again.
+    pub async fn sleep(&mut self) -> Result<(), LoraError> {
+        self.lora.sleep(true).await?;
+
+        Ok(())
+    }
+
+    /// Bring the radio back from `sleep` to standby, ready to receive or transmit
+    ///
+    /// The SX1262 keeps its registers through a warm sleep, and `prepare_for_rx` and
+    /// `prepare_for_tx` write the modulation and packet parameters again on every call, so
+    /// there's nothing to restore here.
+    pub async fn wake(&mut self) -> Result<(), LoraError> {
+        self.lora.enter_standby().await?;
+
+        Ok(())
+    }
+
+    /// Sleep through `duration` instead of listening, see `LoraConfig::sleep_when_idle`
+    ///
+    /// A command queued meanwhile wakes the radio early; the caller goes back to sleep after.
+    async fn sleep_for_duration(&mut self, duration: Duration) {
+        if let Err(e) = self.sleep().await {
+            errorlog::recent::record(ErrorCode::LoraRadio);
+            defmt::error!(
+                "Failed to put the radio to sleep: {:?}",
+                defmt::Debug2Format(&e)
+            );
+            self.receive_for_duration(duration).await;
+            return;
+        }
+
+        let woken_by = select(Timer::after(duration), LORA_TX_QUEUE.receive()).await;
+
+        if let Err(e) = self.wake().await {
+            errorlog::recent::record(ErrorCode::LoraRadio);
+            defmt::error!("Failed to wake the radio: {:?}", defmt::Debug2Format(&e));
+        }
+        if let Either::Second(command) = woken_by {
+            self.handle_command(command).await;
+        }
+    }
+
+    /// Put the radio to sleep and blank the panel for `interval`, see
+    /// `PowerMode::SleepBetweenFixes`
+    async fn sleep_between_fixes(
+        &mut self,
+        interval: Duration,
+        light_sleep: bool,
+        watchdog: &TaskWatchdog,
+    ) {
+        defmt::info!("Sleeping {} ms until the next fix", interval.as_millis());
+        if let Err(e) = self.sleep().await {
+            errorlog::recent::record(ErrorCode::LoraRadio);
+            defmt::error!(
+                "Failed to put the radio to sleep: {:?}",
+                defmt::Debug2Format(&e)
+            );
+        }
+        power::set_power_saving(true);
+
+        if light_sleep {
+            power::light_sleep(interval).await;
+        } else {
+            // Fed along the way, as `interval` may well outlast `WATCHDOG_TIMEOUT`
+            let wake_at = Instant::now() + interval;
+            while Instant::now() < wake_at {
+                watchdog.feed();
+                Timer::at(wake_at.min(Instant::now() + WATCHDOG_TIMEOUT / 2)).await;
+            }
+        }
+
+        if let Err(e) = self.wake().await {
+            errorlog::recent::record(ErrorCode::LoraRadio);
+            defmt::error!("Failed to wake the radio: {:?}", defmt::Debug2Format(&e));
+        }
+        power::set_power_saving(false);
+    }
+
+    /// Main run loop - listens between broadcasts, spacing them out according to the current speed
+    pub async fn run(&mut self, broadcast: BroadcastConfig) {
+        defmt::info!("Starting LoRa operation - adaptive broadcast interval");
+
+        self.node_id = broadcast.node_id;
+        self.relay = broadcast.relay.map(Relay::new);
+        self.publish_settings();
+
+        let mut last_broadcast: Option<Instant> = None;
+        // Set on waking from `sleep_between_fixes`, until the next broadcast
+        let mut woke_at: Option<Instant> = None;
+        let mut reports_sent: u32 = 0;
+        let mut reports_confirmed: u32 = 0;
+        let watchdog = watchdog::supervisor::register("LoRa", WATCHDOG_TIMEOUT);
+
+        loop {
+            watchdog.feed();
+
+            if let Err(e) = self.hop() {
+                defmt::warn!("Failed to hop: {:?}", defmt::Debug2Format(&e));
+            }
+
+            let positioning = GNSS_WATCH.try_get().flatten();
+            let speed = positioning
+                .as_ref()
+                .and_then(|positioning| positioning.speed);
+            let interval = broadcast.interval_for(speed);
+            let elapsed = last_broadcast.map(|instant| instant.elapsed());
+            // Waking up is for a new fix, not to resend the one from before sleeping
+            let max_fix_age = woke_at.map_or(broadcast.max_fix_age, |woke_at| {
+                broadcast.max_fix_age.min(woke_at.elapsed())
+            });
+
+            // A docked device has nothing to report; it keeps listening, and the first wake-up
+            // after undocking finds the broadcast overdue
+            if is_docked() {
+                defmt::debug!("Docked, skipping broadcast");
+            } else if woke_at.is_some() || elapsed.map_or(true, |elapsed| elapsed >= interval) {
+                match positioning {
+                    Some(positioning) if has_fresh_fix(max_fix_age) => {
+                        defmt::info!(
+                            "Broadcasting after {} ms (interval {} ms)",
+                            elapsed.map_or(0, |elapsed| elapsed.as_millis()),
+                            interval.as_millis()
+                        );
+
+                        if let Some(time_slots) = &broadcast.time_slots {
+                            self.wait_for_slot(time_slots).await;
+                        }
+
+                        match self.broadcast_position(&positioning).await {
+                            Ok(()) => {
+                                reports_confirmed = reports_confirmed.wrapping_add(1);
+                                TX_CONFIRMED.sender().send(reports_confirmed);
+                            }
+                            Err(e) => defmt::error!(
+                                "Failed to send position: {:?}",
+                                defmt::Debug2Format(&e)
+                            ),
+                        }
+
+                        if reports_sent % STATUS_BEACON_EVERY == 0 {
+                            if let Err(e) = self.broadcast_status().await {
+                                defmt::error!(
+                                    "Failed to send beacon: {:?}",
+                                    defmt::Debug2Format(&e)
+                                );
+                            }
+                        }
+
+                        reports_sent = reports_sent.wrapping_add(1);
+                        last_broadcast = Some(Instant::now());
+                        woke_at = None;
+
+                        if let PowerMode::SleepBetweenFixes {
+                            interval,
+                            light_sleep,
+                        } = power::mode()
+                        {
+                            self.sleep_between_fixes(interval, light_sleep, &watchdog)
+                                .await;
+                            woke_at = Some(Instant::now());
+                            continue;
+                        }
+                    }
+                    _ => {
+                        // Retried on every wake-up, so the first fresh fix goes out promptly
+                        defmt::debug!("No fresh fix, skipping broadcast");
+                    }
+                }
+            }
+
+            // Listen, or sleep, until the next broadcast is due, but wake up at least every
+            // `min_interval` so that a change in speed takes effect promptly
+            let until_due = last_broadcast
+                .and_then(|instant| interval.checked_sub(instant.elapsed()))
+                .unwrap_or(broadcast.min_interval)
+                .min(broadcast.min_interval);
+            // Peers move on at the hop, so the window can't outlast it
+            let until_due = self
+                .until_next_hop()
+                .map_or(until_due, |until_hop| until_due.min(until_hop));
+
+            if self.config.sleep_when_idle && LORA_TX_QUEUE.is_empty() {
+                self.sleep_for_duration(until_due).await;
+            } else {
+                self.receive_for_duration(until_due).await;
+            }
+        }
+    }
+}
+
+fn sf_number(spreading_factor: SpreadingFactor) -> u8 {
+    match spreading_factor {
+        SpreadingFactor::_5 => 5,
+        SpreadingFactor::_6 => 6,
+        SpreadingFactor::_7 => 7,
+        SpreadingFactor::_8 => 8,
+        SpreadingFactor::_9 => 9,
+        SpreadingFactor::_10 => 10,
+        SpreadingFactor::_11 => 11,
+        SpreadingFactor::_12 => 12,
+    }
+}
+
+fn bandwidth_hz(bandwidth: Bandwidth) -> u32 {
+    match bandwidth {
+        Bandwidth::_7KHz => 7_810,
+        Bandwidth::_10KHz => 10_420,
+        Bandwidth::_15KHz => 15_630,
+        Bandwidth::_20KHz => 20_830,
+        Bandwidth::_31KHz => 31_250,
+        Bandwidth::_41KHz => 41_670,
+        Bandwidth::_62KHz => 62_500,
+        Bandwidth::_125KHz => 125_000,
+        Bandwidth::_250KHz => 250_000,
+        Bandwidth::_500KHz => 500_000,
+    }
+}
+
+/// 1 for 4/5 up to 4 for 4/8
+fn coding_rate_number(coding_rate: CodingRate) -> u8 {
+    match coding_rate {
+        CodingRate::_4_5 => 1,
+        CodingRate::_4_6 => 2,
+        CodingRate::_4_7 => 3,
+        CodingRate::_4_8 => 4,
+    }
+}
+
+/// `timeout` in LoRa symbols of `2^sf / bandwidth` seconds each, rounded up
+fn timeout_symbols(timeout: Duration, sf: u8, bandwidth_hz: u32) -> u16 {
+    let symbol_us = (1u64 << sf) * 1_000_000 / bandwidth_hz as u64;
+    let symbols = timeout.as_micros().div_ceil(symbol_us.max(1));
+
+    symbols.min(u16::MAX as u64) as u16
+}
+
+fn spreading_factor(number: u8) -> Option<SpreadingFactor> {
+    match number {
+        5 => Some(SpreadingFactor::_5),
+        6 => Some(SpreadingFactor::_6),
+        7 => Some(SpreadingFactor::_7),
+        8 => Some(SpreadingFactor::_8),
+        9 => Some(SpreadingFactor::_9),
+        10 => Some(SpreadingFactor::_10),
+        11 => Some(SpreadingFactor::_11),
+        12 => Some(SpreadingFactor::_12),
+        _ => None,
+    }
+}
+
+#[embassy_executor::task]
+pub async fn start(
+    spi_bus: &'static Mutex<CriticalSectionRawMutex, esp_hal::spi::master::Spi<'static, Async>>,
+    nss: Output<'static>,
+    reset: Output<'static>,
+    dio1: Input<'static>,
+    busy: Input<'static>,
+    mut broadcast: BroadcastConfig,
+    mut rng: Rng,
+) {
+    defmt::info!("Starting LoRa task");
+
+    let spi_device = embassy_embedded_hal::shared_bus::asynch::spi::SpiDevice::new(spi_bus, nss);
+    // Settings changed over BLE outlast a reset, unless the region no longer allows them
+    let config = match persist::load()
+        .map(|settings| (settings, LoraConfig::default().with_settings(settings)))
+    {
+        Some((settings, Ok(config))) => {
+            set_reporting(&settings);
+            broadcast.node_id = settings.node_id;
+            if let Some(relay) = &mut broadcast.relay {
+                relay.node_id = settings.node_id;
+            }
+
+            config
+        }
+        Some((settings, Err(e))) => {
+            defmt::warn!(
+                "Ignoring stored radio settings: {:?}",
+                defmt::Debug2Format(&e)
+            );
+            // Allowed in any region
+            set_reporting(&settings);
+            LoraConfig::default()
+        }
+        None => LoraConfig::default(),
+    };
+    let crypto = config.crypto;
+    let mut lora = Lora::new(spi_device, reset, dio1, busy, None, config)
+        .await
+        .unwrap();
+
+    if crypto {
+        match persist::load_key() {
+            // A random start keeps this boot's counters clear of the last one's
+            Some(key) => lora.set_crypto(LoraCrypto::new(key, rng.random())),
+            None => defmt::error!("Encryption is on but there's no key, sending in the clear"),
+        }
+    }
+
+    lora.run(broadcast).await;
+}
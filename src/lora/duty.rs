@@ -0,0 +1,226 @@
+use super::error::LoraError;
+
+/// Slices the window is tracked in; airtime leaves the window a slice at a time
+const WINDOW_SLICES: usize = 60;
+
+/// The window's slices, and the one it has partly slid past
+const SLICES_KEPT: usize = WINDOW_SLICES + 1;
+
+/// Symbols longer than this have the radio turn on low data rate optimisation, as lora-phy does
+const LOW_DATA_RATE_SYMBOL_US: u64 = 16_000;
+
+/// Share of a sliding window a node may spend transmitting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DutyCycleConfig {
+    /// Airtime allowed, in thousandths of the window: 10 for the usual 1%
+    pub limit_permille: u16,
+
+    /// Length of the window, in milliseconds
+    pub window_ms: u64,
+}
+
+impl DutyCycleConfig {
+    /// Airtime allowed within one window, in microseconds
+    pub fn budget_us(&self) -> u64 {
+        self.window_ms * self.limit_permille as u64
+    }
+}
+
+/// Time on air of a frame `payload_len` bytes long, in microseconds
+///
+/// Follows the SX126x datasheet for an explicit header, with `coding_rate` from 1 (4/5) to
+/// 4 (4/8) and `preamble_len` in symbols.
+pub fn time_on_air_us(
+    spreading_factor: u8,
+    bandwidth_hz: u32,
+    coding_rate: u8,
+    preamble_len: u16,
+    payload_len: usize,
+    crc_on: bool,
+) -> u64 {
+    let sf = spreading_factor as i64;
+    let symbol_us = (1u64 << spreading_factor) * 1_000_000 / bandwidth_hz.max(1) as u64;
+    let low_data_rate = symbol_us > LOW_DATA_RATE_SYMBOL_US;
+
+    // SF5 and SF6 have a shorter sync but a longer preamble, in quarter symbols
+    let (header_bits, preamble_quarters) = if spreading_factor < 7 {
+        (20, 25)
+    } else {
+        (28, 17)
+    };
+    let bits = 8 * payload_len as i64 + 16 * crc_on as i64 - 4 * sf + header_bits;
+    let bits_per_block = 4 * (sf - 2 * low_data_rate as i64);
+    let blocks = if bits > 0 {
+        (bits + bits_per_block - 1) / bits_per_block
+    } else {
+        0
+    };
+    let payload_symbols = 8 + blocks as u64 * (coding_rate as u64 + 4);
+
+    let quarters = 4 * preamble_len as u64 + preamble_quarters + 4 * payload_symbols;
+    quarters * (1u64 << spreading_factor) * 1_000_000 / (4 * bandwidth_hz.max(1) as u64)
+}
+
+/// Keeps the airtime spent within a sliding window under `DutyCycleConfig::limit_permille`
+///
+/// The window is kept as `WINDOW_SLICES` slices, each with the airtime spent during it, so a
+/// transmission counts against the budget for up to one slice longer than the window; never
+/// less. Times are milliseconds from any fixed point, such as boot, and never go backwards.
+#[derive(Debug, Clone)]
+pub struct DutyCycleLimiter {
+    config: DutyCycleConfig,
+
+    /// Airtime in microseconds spent in each slice, and which slice since the start it was
+    slices: [(u64, u64); SLICES_KEPT],
+}
+
+impl DutyCycleLimiter {
+    pub fn new(config: DutyCycleConfig) -> Self {
+        Self {
+            config,
+            slices: [(u64::MAX, 0); SLICES_KEPT],
+        }
+    }
+
+    /// Airtime spent within the window ending at `now_ms`, in microseconds
+    pub fn used_us(&self, now_ms: u64) -> u64 {
+        let current = self.slice(now_ms);
+        self.slices
+            .iter()
+            .filter(|(slice, _)| *slice != u64::MAX && current - *slice <= WINDOW_SLICES as u64)
+            .map(|(_, airtime_us)| airtime_us)
+            .sum()
+    }
+
+    /// Account for a transmission of `airtime_us` at `now_ms`, unless it would take the window
+    /// over budget, in which case it shouldn't be sent
+    pub fn try_spend(&mut self, now_ms: u64, airtime_us: u64) -> Result<(), LoraError> {
+        if self.used_us(now_ms) + airtime_us > self.config.budget_us() {
+            return Err(LoraError::DutyCycleExceeded);
+        }
+
+        let current = self.slice(now_ms);
+        let (slice, spent) = &mut self.slices[current as usize % SLICES_KEPT];
+        if *slice != current {
+            *slice = current;
+            *spent = 0;
+        }
+        *spent += airtime_us;
+
+        Ok(())
+    }
+
+    /// Switch to `config` at `now_ms`, still counting the airtime spent so far against it
+    ///
+    /// With another window length the airtime is moved to the new slice its old one ended in, or
+    /// the current one: an old slice doesn't say when within it the airtime was spent, so it's
+    /// taken as late as it could have been and counts for at least as long as it should.
+    pub fn set_config(&mut self, config: DutyCycleConfig, now_ms: u64) {
+        let old = core::mem::replace(self, Self::new(config));
+        let current = self.slice(now_ms);
+
+        for (slice, airtime_us) in old.slices {
+            if slice == u64::MAX {
+                continue;
+            }
+
+            let slice_end_ms = (slice + 1) * old.slice_ms() - 1;
+            let slice = self.slice(slice_end_ms.min(now_ms));
+            if current - slice > WINDOW_SLICES as u64 {
+                continue;
+            }
+            let (kept, spent) = &mut self.slices[slice as usize % SLICES_KEPT];
+            if *kept != slice {
+                *kept = slice;
+                *spent = 0;
+            }
+            *spent += airtime_us;
+        }
+    }
+
+    fn slice(&self, now_ms: u64) -> u64 {
+        now_ms / self.slice_ms()
+    }
+
+    fn slice_ms(&self) -> u64 {
+        (self.config.window_ms / WINDOW_SLICES as u64).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE_PERCENT_HOURLY: DutyCycleConfig = DutyCycleConfig {
+        limit_permille: 10,
+        window_ms: 3_600_000,
+    };
+
+    #[test]
+    fn test_time_on_air() {
+        // Semtech's calculator: SF7 / 125 kHz / 4/5, 8 symbol preamble, 10 bytes with CRC
+        assert_eq!(time_on_air_us(7, 125_000, 1, 8, 10, true), 41_216);
+        // SF12 / 125 kHz uses low data rate optimisation
+        assert_eq!(time_on_air_us(12, 125_000, 1, 8, 10, true), 991_232);
+        assert!(time_on_air_us(7, 250_000, 1, 8, 10, true) < 41_216);
+        assert!(time_on_air_us(7, 125_000, 4, 8, 10, true) > 41_216);
+    }
+
+    #[test]
+    fn test_budget_runs_out_and_recovers() {
+        let mut limiter = DutyCycleLimiter::new(ONE_PERCENT_HOURLY);
+        assert_eq!(ONE_PERCENT_HOURLY.budget_us(), 36_000_000);
+
+        // 36 one-second transmissions use up the hour's 36 seconds
+        for second in 0..36 {
+            assert!(limiter.try_spend(second * 1_000, 1_000_000).is_ok());
+        }
+        assert!(matches!(
+            limiter.try_spend(40_000, 1),
+            Err(LoraError::DutyCycleExceeded)
+        ));
+        assert_eq!(limiter.used_us(40_000), 36_000_000);
+
+        // Back once the window has slid past them, a slice late at most
+        assert!(limiter.try_spend(3_600_000, 1).is_err());
+        assert!(limiter.try_spend(3_660_000, 1_000_000).is_ok());
+    }
+
+    #[test]
+    fn test_spent_airtime_survives_new_config() {
+        let mut limiter = DutyCycleLimiter::new(ONE_PERCENT_HOURLY);
+        for second in 0..30 {
+            limiter.try_spend(second * 1_000, 1_000_000).unwrap();
+        }
+
+        // Tightened to 0.5%, the 30 s already spent are over the new 18 s budget
+        let half_percent = DutyCycleConfig {
+            limit_permille: 5,
+            ..ONE_PERCENT_HOURLY
+        };
+        limiter.set_config(half_percent, 40_000);
+        assert_eq!(limiter.used_us(40_000), 30_000_000);
+        assert!(limiter.try_spend(40_000, 1).is_err());
+
+        // A longer window keeps counting it
+        limiter.set_config(
+            DutyCycleConfig {
+                window_ms: 7_200_000,
+                ..ONE_PERCENT_HOURLY
+            },
+            40_000,
+        );
+        assert_eq!(limiter.used_us(40_000), 30_000_000);
+
+        // A shorter one too, until it has slid past
+        let mut limiter = DutyCycleLimiter::new(ONE_PERCENT_HOURLY);
+        limiter.try_spend(10_000, 1_000_000).unwrap();
+        let short = DutyCycleConfig {
+            window_ms: 60_000,
+            ..ONE_PERCENT_HOURLY
+        };
+        limiter.set_config(short, 40_000);
+        assert_eq!(limiter.used_us(40_000), 1_000_000);
+        assert_eq!(limiter.used_us(101_000), 0);
+    }
+}
@@ -0,0 +1,45 @@
+#[cfg(feature = "esp32")]
+use lora_phy::mod_params::RadioError;
+
+/// Error type for LoRa operations
+#[derive(Debug)]
+pub enum LoraError {
+    /// Radio hardware error
+    #[cfg(feature = "esp32")]
+    Radio(RadioError),
+    /// Timeout during operation
+    Timeout,
+    /// Invalid configuration
+    InvalidConfig,
+    /// Buffer error (too small, overflow, etc.)
+    BufferError,
+    /// No data available
+    NoData,
+    /// Transmission error
+    TransmissionError,
+    /// Packet type byte doesn't match any known packet
+    UnknownPacketType(u8),
+    /// Packet type is known, but not the kind this decoder handles
+    UnexpectedPacketType(u8),
+    /// Position delta relative to a keyframe (by ID) that hasn't been received
+    MissingKeyframe(u8),
+    /// Frame header carries a protocol version this firmware doesn't speak
+    UnsupportedVersion(u8),
+    /// Frame CRC doesn't match its contents
+    ChecksumMismatch,
+    /// Text packet isn't valid UTF-8
+    InvalidText,
+    /// Transmit queue has no room for another packet
+    QueueFull,
+    /// Channel activity detection kept finding the channel in use
+    ChannelBusy,
+    /// Sending would take the node over its duty cycle limit
+    DutyCycleExceeded,
+}
+
+#[cfg(feature = "esp32")]
+impl From<RadioError> for LoraError {
+    fn from(e: RadioError) -> Self {
+        LoraError::Radio(e)
+    }
+}
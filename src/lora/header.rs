@@ -0,0 +1,314 @@
+use super::error::LoraError;
+use super::packet::PacketType;
+
+/// Version written into every header; frames of any other version are rejected
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Destination ID of frames meant for every node in range
+pub const BROADCAST: u32 = 0xFF_FFFF;
+
+/// Bytes in front of the payload, the message type included
+pub const HEADER_LEN: usize = 10;
+
+/// Bytes of the CRC that follows the payload when `FrameFlags::crc` is set
+pub const CRC_LEN: usize = 2;
+
+/// Flag bits in byte 0
+const FLAG_CRC: u8 = 1 << 0;
+const FLAG_ACK_REQUESTED: u8 = 1 << 1;
+
+/// IDs are sent as 24 bits, which is all of `device_id()`
+const ID_MASK: u32 = 0xFF_FFFF;
+
+/// Start of every LoRa frame, saying what the frame is, who it's from and for, and how long
+///
+/// | Byte | Field                                                                      |
+/// |------|----------------------------------------------------------------------------|
+/// | 0    | `PROTOCOL_VERSION` in bits 4-7, `FrameFlags` in bits 0-3                   |
+/// | 1    | Source ID, `u24` little-endian                                             |
+/// | 4    | Destination ID, `u24` little-endian; `BROADCAST` for everyone              |
+/// | 7    | Sequence number, per source                                                |
+/// | 8    | Payload length, excluding the message type and the CRC                     |
+/// | 9    | Message type, a `PacketType`                                               |
+///
+/// The payload follows, then with `FrameFlags::crc` set a CRC-16/CCITT-FALSE over the header
+/// and payload, little-endian. The radio's own CRC already drops most corrupt frames; this one is
+/// for links where it's off, and for frames that pass through something else on the way, like a
+/// relay or a gateway.
+///
+/// The message type comes last so that it and the payload together are the packet exactly as the
+/// encoders in `packet`, `report`, `delta` and `relay` write it, see `encode_frame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub message_type: PacketType,
+    pub source: u32,
+    pub destination: u32,
+    pub sequence: u8,
+    pub payload_len: u8,
+    pub flags: FrameFlags,
+}
+
+/// Options carried in the header, bits 0-3 of byte 0
+///
+/// | Bit | Flag                                                                     |
+/// |-----|--------------------------------------------------------------------------|
+/// | 0   | `crc`: a CRC follows the payload                                         |
+/// | 1   | `ack_requested`: the destination answers with a `PacketType::Ack`        |
+///
+/// Bits 2-3 are reserved and sent as zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameFlags {
+    pub crc: bool,
+    pub ack_requested: bool,
+}
+
+impl From<FrameFlags> for u8 {
+    fn from(flags: FrameFlags) -> Self {
+        let mut byte = 0;
+        if flags.crc {
+            byte |= FLAG_CRC;
+        }
+        if flags.ack_requested {
+            byte |= FLAG_ACK_REQUESTED;
+        }
+
+        byte
+    }
+}
+
+impl From<u8> for FrameFlags {
+    fn from(byte: u8) -> Self {
+        Self {
+            crc: byte & FLAG_CRC != 0,
+            ack_requested: byte & FLAG_ACK_REQUESTED != 0,
+        }
+    }
+}
+
+impl Header {
+    /// Write the header's `HEADER_LEN` bytes, returning that many
+    pub fn write(&self, buffer: &mut [u8]) -> Result<usize, LoraError> {
+        let buffer = buffer.get_mut(..HEADER_LEN).ok_or(LoraError::BufferError)?;
+
+        let [source_0, source_1, source_2, _] = self.source.to_le_bytes();
+        let [destination_0, destination_1, destination_2, _] = self.destination.to_le_bytes();
+        buffer.copy_from_slice(&[
+            PROTOCOL_VERSION << 4 | u8::from(self.flags),
+            source_0,
+            source_1,
+            source_2,
+            destination_0,
+            destination_1,
+            destination_2,
+            self.sequence,
+            self.payload_len,
+            self.message_type as u8,
+        ]);
+
+        Ok(HEADER_LEN)
+    }
+
+    /// Read a header written by `write` from the start of `bytes`
+    pub fn parse(bytes: &[u8]) -> Result<Self, LoraError> {
+        let bytes = bytes.get(..HEADER_LEN).ok_or(LoraError::BufferError)?;
+
+        let version = bytes[0] >> 4;
+        if version != PROTOCOL_VERSION {
+            return Err(LoraError::UnsupportedVersion(version));
+        }
+
+        Ok(Self {
+            message_type: PacketType::try_from(bytes[9])?,
+            source: u32::from_le_bytes([bytes[1], bytes[2], bytes[3], 0]),
+            destination: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], 0]),
+            sequence: bytes[7],
+            payload_len: bytes[8],
+            flags: FrameFlags::from(bytes[0] & 0x0F),
+        })
+    }
+
+    /// Size of the whole frame this header describes
+    pub const fn frame_len(&self) -> usize {
+        HEADER_LEN + self.payload_len as usize + if self.flags.crc { CRC_LEN } else { 0 }
+    }
+
+    /// Whether the frame is addressed to `node_id` or to everyone
+    pub const fn is_for(&self, node_id: u32) -> bool {
+        self.destination == BROADCAST || self.destination == node_id & ID_MASK
+    }
+}
+
+/// Frame `packet`, its type byte followed by the payload, returning the number of bytes written
+pub fn encode_frame(
+    source: u32,
+    destination: u32,
+    sequence: u8,
+    packet: &[u8],
+    flags: FrameFlags,
+    buffer: &mut [u8],
+) -> Result<usize, LoraError> {
+    let (&message_type, payload) = packet.split_first().ok_or(LoraError::NoData)?;
+    let header = Header {
+        message_type: PacketType::try_from(message_type)?,
+        source: source & ID_MASK,
+        destination: destination & ID_MASK,
+        sequence,
+        payload_len: u8::try_from(payload.len()).map_err(|_| LoraError::BufferError)?,
+        flags,
+    };
+
+    let buffer = buffer
+        .get_mut(..header.frame_len())
+        .ok_or(LoraError::BufferError)?;
+    header.write(buffer)?;
+    buffer[HEADER_LEN..HEADER_LEN + payload.len()].copy_from_slice(payload);
+
+    if flags.crc {
+        let end = HEADER_LEN + payload.len();
+        let crc = crc16(&buffer[..end]);
+        buffer[end..].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    Ok(buffer.len())
+}
+
+/// Split a frame written by `encode_frame` into its header and the packet, type byte included
+///
+/// Bytes past the frame's length are ignored, so trailing padding doesn't make a frame invalid.
+pub fn decode_frame(bytes: &[u8]) -> Result<(Header, &[u8]), LoraError> {
+    let header = Header::parse(bytes)?;
+    let frame = bytes
+        .get(..header.frame_len())
+        .ok_or(LoraError::BufferError)?;
+
+    let end = HEADER_LEN + header.payload_len as usize;
+    if header.flags.crc && crc16(&frame[..end]).to_le_bytes() != frame[end..] {
+        return Err(LoraError::ChecksumMismatch);
+    }
+
+    Ok((header, &frame[HEADER_LEN - 1..end]))
+}
+
+/// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF, no reflection
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, &byte| {
+        (0..8).fold(crc ^ (byte as u16) << 8, |crc, _| {
+            if crc & 0x8000 != 0 {
+                crc << 1 ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lora::packet::{decode_status, encode_status, HealthFlags, NodeStatus};
+
+    const WITH_CRC: FrameFlags = FrameFlags {
+        crc: true,
+        ack_requested: false,
+    };
+
+    #[test]
+    fn test_header_round_trip() {
+        let header = Header {
+            message_type: PacketType::Ack,
+            source: 0x12_3456,
+            destination: BROADCAST,
+            sequence: 200,
+            payload_len: 1,
+            flags: FrameFlags {
+                crc: true,
+                ack_requested: true,
+            },
+        };
+
+        let mut buffer = [0u8; HEADER_LEN];
+        assert_eq!(header.write(&mut buffer).unwrap(), HEADER_LEN);
+        assert_eq!(buffer[0], 0x13);
+        assert_eq!(Header::parse(&buffer).unwrap(), header);
+        assert_eq!(header.frame_len(), HEADER_LEN + 1 + CRC_LEN);
+    }
+
+    #[test]
+    fn test_frame_carries_packet_unchanged() {
+        let status = NodeStatus {
+            battery_percent: Some(80),
+            last_rssi: Some(-90),
+            flags: HealthFlags::default(),
+        };
+        let mut packet = [0u8; 8];
+        let packet_len = encode_status(&status, &mut packet).unwrap();
+
+        let mut frame = [0u8; 32];
+        let len = encode_frame(1, 2, 7, &packet[..packet_len], WITH_CRC, &mut frame).unwrap();
+        assert_eq!(len, HEADER_LEN + packet_len - 1 + CRC_LEN);
+
+        // Padding after the frame is ignored
+        let (header, decoded) = decode_frame(&frame).unwrap();
+        assert_eq!(header.message_type, PacketType::Status);
+        assert_eq!((header.source, header.destination), (1, 2));
+        assert_eq!(header.sequence, 7);
+        assert_eq!(decoded, &packet[..packet_len]);
+        assert_eq!(decode_status(decoded).unwrap(), status);
+
+        assert!(header.is_for(2));
+        assert!(!header.is_for(3));
+    }
+
+    #[test]
+    fn test_crc_catches_corruption() {
+        let mut frame = [0u8; 32];
+        let len = encode_frame(1, BROADCAST, 0, &[0x0B, b'h', b'i'], WITH_CRC, &mut frame).unwrap();
+
+        frame[HEADER_LEN] ^= 0x01;
+        assert!(matches!(
+            decode_frame(&frame[..len]),
+            Err(LoraError::ChecksumMismatch)
+        ));
+
+        // Without a CRC the same corruption goes unnoticed
+        let len = encode_frame(
+            1,
+            BROADCAST,
+            0,
+            &[0x0B, b'h', b'i'],
+            FrameFlags::default(),
+            &mut frame,
+        )
+        .unwrap();
+        frame[HEADER_LEN] ^= 0x01;
+        assert_eq!(decode_frame(&frame[..len]).unwrap().1, &[0x0B, b'i', b'i']);
+    }
+
+    #[test]
+    fn test_rejects_malformed_frames() {
+        let mut frame = [0u8; 32];
+        let len = encode_frame(1, BROADCAST, 0, &[0x0B, b'h', b'i'], WITH_CRC, &mut frame).unwrap();
+
+        // Cut short
+        assert!(matches!(
+            decode_frame(&frame[..len - 1]),
+            Err(LoraError::BufferError)
+        ));
+        assert!(matches!(
+            decode_frame(&frame[..HEADER_LEN - 1]),
+            Err(LoraError::BufferError)
+        ));
+
+        // From a later protocol
+        frame[0] = 0x21;
+        assert!(matches!(
+            decode_frame(&frame[..len]),
+            Err(LoraError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn test_crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+}
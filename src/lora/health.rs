@@ -0,0 +1,48 @@
+use core::sync::atomic::Ordering;
+use embassy_time::Duration;
+
+use super::driver::{heard_within, RADIO_STATS};
+use super::packet::HealthFlags;
+use crate::battery::monitor::BATTERY_PERCENT;
+use crate::gnss::watch::{has_fresh_fix, DROPPED_SENTENCE_COUNT, UART_OVERFLOW_COUNT};
+
+/// A frame heard within this long counts as an active link
+pub const LINK_ACTIVE_WINDOW: Duration = Duration::from_secs(180);
+
+/// Oldest fix that still counts as having one
+const FIX_MAX_AGE: Duration = Duration::from_secs(10);
+
+/// Charge at or below which the battery is reported as needing charging
+const LOW_BATTERY_PERCENT: u8 = 20;
+
+/// Samples `HealthFlags` for one consumer, tracking which errors it has already reported
+///
+/// Each consumer keeps its own monitor, so the BLE characteristic and the LoRa beacon both see
+/// every error once. CRC failures aren't counted: at the edge of range they're routine.
+#[derive(Debug, Default)]
+pub struct HealthMonitor {
+    errors_seen: u32,
+}
+
+impl HealthMonitor {
+    pub const fn new() -> Self {
+        Self { errors_seen: 0 }
+    }
+
+    pub fn sample(&mut self) -> HealthFlags {
+        let errors = RADIO_STATS.lock(|stats| stats.get()).other_error_count
+            + UART_OVERFLOW_COUNT.load(Ordering::Relaxed)
+            + DROPPED_SENTENCE_COUNT.load(Ordering::Relaxed);
+        let error = errors != self.errors_seen;
+        self.errors_seen = errors;
+
+        HealthFlags {
+            gps_fix: has_fresh_fix(FIX_MAX_AGE),
+            lora_active: heard_within(LINK_ACTIVE_WINDOW),
+            battery_low: BATTERY_PERCENT
+                .try_get()
+                .is_some_and(|percent| percent <= LOW_BATTERY_PERCENT),
+            error,
+        }
+    }
+}
@@ -0,0 +1,152 @@
+/// Sequence numbers ahead of the last one by more than this are taken for a restarted source
+/// rather than lost frames; at one broadcast a second that's over two minutes of silence
+const MAX_GAP: u8 = 128;
+
+/// Frames heard from one source, and how many of its frames were missed in between
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeLoss {
+    pub source: u32,
+    pub received: u32,
+    pub lost: u32,
+
+    /// Sequence number of the latest frame heard
+    last_sequence: u8,
+
+    /// `SequenceTracker::clock` when it was heard, for evicting the quietest source
+    heard_at: u32,
+}
+
+impl NodeLoss {
+    /// Share of the source's frames that never arrived, `None` before any did
+    pub fn loss_rate(&self) -> Option<f32> {
+        let total = self.received + self.lost;
+        (self.received > 0).then(|| self.lost as f32 / total as f32)
+    }
+}
+
+/// Counts the frames missed from each source in range, by the gaps in their sequence numbers
+///
+/// Sequence numbers are per source and count every frame it sends, whoever it's for, so frames
+/// have to be recorded before they're filtered by destination. Only gaps of up to `MAX_GAP` are
+/// counted; anything else is a source that restarted, or a frame arriving long after it was
+/// sent, and the count picks up from there. Once `NODES` sources are tracked, the one heard from
+/// longest ago makes room for a new one.
+pub struct SequenceTracker<const NODES: usize> {
+    nodes: heapless::Vec<NodeLoss, NODES>,
+
+    /// Frames recorded so far, to order sources by when they were last heard
+    clock: u32,
+}
+
+impl<const NODES: usize> Default for SequenceTracker<NODES> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const NODES: usize> SequenceTracker<NODES> {
+    pub const fn new() -> Self {
+        Self {
+            nodes: heapless::Vec::new(),
+            clock: 0,
+        }
+    }
+
+    /// Note a frame `sequence` from `source`, returning how many of its frames were missed
+    /// just before it
+    ///
+    /// A repeat of the latest sequence number, a retransmission, counts as neither received nor
+    /// lost.
+    pub fn record(&mut self, source: u32, sequence: u8) -> u8 {
+        self.clock = self.clock.wrapping_add(1);
+        let clock = self.clock;
+
+        let Some(node) = self.nodes.iter_mut().find(|node| node.source == source) else {
+            self.add(NodeLoss {
+                source,
+                received: 1,
+                lost: 0,
+                last_sequence: sequence,
+                heard_at: clock,
+            });
+            return 0;
+        };
+
+        node.heard_at = clock;
+        let distance = sequence.wrapping_sub(node.last_sequence);
+        if distance == 0 {
+            return 0;
+        }
+
+        node.last_sequence = sequence;
+        node.received = node.received.saturating_add(1);
+        if distance > MAX_GAP {
+            return 0;
+        }
+
+        let missed = distance - 1;
+        node.lost = node.lost.saturating_add(missed as u32);
+
+        missed
+    }
+
+    /// Counts for `source`, `None` if it isn't tracked
+    pub fn node(&self, source: u32) -> Option<&NodeLoss> {
+        self.nodes.iter().find(|node| node.source == source)
+    }
+
+    fn add(&mut self, node: NodeLoss) {
+        if let Err(node) = self.nodes.push(node) {
+            if let Some(quietest) = self.nodes.iter_mut().min_by_key(|node| node.heard_at) {
+                *quietest = node;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_gaps() {
+        let mut tracker = SequenceTracker::<4>::new();
+
+        assert_eq!(tracker.record(0x12_3456, 10), 0);
+        assert_eq!(tracker.record(0x12_3456, 11), 0);
+        assert_eq!(tracker.record(0x12_3456, 14), 2);
+        // Retransmission
+        assert_eq!(tracker.record(0x12_3456, 14), 0);
+
+        let node = tracker.node(0x12_3456).unwrap();
+        assert_eq!((node.received, node.lost), (3, 2));
+        assert_eq!(node.loss_rate(), Some(0.4));
+    }
+
+    #[test]
+    fn test_wraps_and_resyncs() {
+        let mut tracker = SequenceTracker::<4>::new();
+
+        tracker.record(1, 254);
+        assert_eq!(tracker.record(1, 1), 2);
+
+        // Restarted from zero, not 155 frames lost
+        tracker.record(2, 100);
+        assert_eq!(tracker.record(2, 0), 0);
+        assert_eq!(tracker.node(2).unwrap().lost, 0);
+    }
+
+    #[test]
+    fn test_evicts_quietest_source() {
+        let mut tracker = SequenceTracker::<2>::new();
+
+        tracker.record(1, 0);
+        tracker.record(2, 0);
+        tracker.record(1, 1);
+        tracker.record(3, 0);
+
+        assert!(tracker.node(1).is_some());
+        assert!(tracker.node(2).is_none());
+        assert!(tracker.node(3).is_some());
+    }
+}
@@ -0,0 +1,540 @@
+use super::error::LoraError;
+
+/// 2^24, the number of steps in a 3-byte coordinate
+const COMPACT_STEPS: f64 = 16_777_216.0;
+
+/// Scale of the 4-byte fixed-point coordinates (1e-7 degree, ~1.1 cm)
+const FIXED_POINT_SCALE: f64 = 10_000_000.0;
+
+/// Battery level reported when the node can't measure it
+pub const BATTERY_UNKNOWN: u8 = 0xff;
+
+/// RSSI reported before the node has heard any frame; no real reading is this strong
+pub const RSSI_NONE: i8 = i8::MAX;
+
+/// First byte of every packet, selecting what follows and how it is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PacketType {
+    /// Latitude and longitude as 4-byte little-endian fixed point, 1e-7 degree per step
+    Position = 0x01,
+
+    /// Latitude and longitude quantized to 3 bytes each
+    ///
+    /// Latitude steps are 180° / 2^24 (~1.2 m), longitude steps are 360° / 2^24 (~2.4 m at the
+    /// equator, shrinking towards the poles), so rounding is off by at most ~0.6 m north-south and
+    /// ~1.2 m east-west.
+    CompactPosition = 0x02,
+
+    /// Node health for fleet dashboards, see `encode_status`
+    Status = 0x03,
+
+    /// Fixed-point coordinates plus speed and heading, see `report::ReportFormat::Standard`
+    StandardPosition = 0x04,
+
+    /// Standard position plus altitude, satellites and fix time, see `report::ReportFormat::Full`
+    FullPosition = 0x05,
+
+    /// Full position that later deltas are relative to, see `delta::DeltaEncoder`
+    PositionKeyframe = 0x06,
+
+    /// Position as an offset from the last keyframe, see `delta::DeltaEncoder`
+    PositionDelta = 0x07,
+
+    /// Another packet wrapped for relaying, see `relay::Relay`; the length given is the header's
+    Relayed = 0x08,
+
+    /// Receipt for the frame with the sequence number in the payload, see `encode_ack`
+    Ack = 0x09,
+
+    /// Asks the frame's destination to report its position, no payload
+    Poll = 0x0A,
+
+    /// UTF-8 text of any length that fits the frame; the length given is zero
+    Text = 0x0B,
+
+    /// Another packet encrypted with the network key, see `crypto::LoraCrypto`; the length given
+    /// is the header's
+    Encrypted = 0x0C,
+}
+
+impl TryFrom<u8> for PacketType {
+    type Error = LoraError;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        match byte {
+            0x01 => Ok(PacketType::Position),
+            0x02 => Ok(PacketType::CompactPosition),
+            0x03 => Ok(PacketType::Status),
+            0x04 => Ok(PacketType::StandardPosition),
+            0x05 => Ok(PacketType::FullPosition),
+            0x06 => Ok(PacketType::PositionKeyframe),
+            0x07 => Ok(PacketType::PositionDelta),
+            0x08 => Ok(PacketType::Relayed),
+            0x09 => Ok(PacketType::Ack),
+            0x0A => Ok(PacketType::Poll),
+            0x0B => Ok(PacketType::Text),
+            0x0C => Ok(PacketType::Encrypted),
+            _ => Err(LoraError::UnknownPacketType(byte)),
+        }
+    }
+}
+
+impl PacketType {
+    /// Size of the payload, excluding the type byte; the fixed part for variable-length packets
+    pub const fn payload_len(self) -> usize {
+        match self {
+            PacketType::Position => 8,
+            PacketType::CompactPosition => 6,
+            PacketType::Status => 3,
+            PacketType::StandardPosition => 12,
+            PacketType::FullPosition => 19,
+            PacketType::PositionKeyframe => 9,
+            PacketType::PositionDelta => 5,
+            PacketType::Relayed => 6,
+            PacketType::Ack => 1,
+            PacketType::Encrypted => 4,
+            PacketType::Poll | PacketType::Text => 0,
+        }
+    }
+}
+
+/// Round half away from zero; `f64::round` isn't available in `core`
+pub fn round(value: f64) -> f64 {
+    if value >= 0.0 {
+        (value + 0.5) as i64 as f64
+    } else {
+        (value - 0.5) as i64 as f64
+    }
+}
+
+pub fn encode_coordinate(degrees: f64) -> [u8; 4] {
+    (round(degrees * FIXED_POINT_SCALE) as i32).to_le_bytes()
+}
+
+pub fn decode_coordinate(bytes: [u8; 4]) -> f64 {
+    i32::from_le_bytes(bytes) as f64 / FIXED_POINT_SCALE
+}
+
+pub fn encode_compact_latitude(latitude: f64) -> [u8; 3] {
+    // Both poles must be representable, so latitude spans one step fewer than longitude
+    let steps = round((latitude + 90.0) / 180.0 * (COMPACT_STEPS - 1.0));
+
+    encode_u24(steps.clamp(0.0, COMPACT_STEPS - 1.0) as u32)
+}
+
+pub fn decode_compact_latitude(bytes: [u8; 3]) -> f64 {
+    decode_u24(bytes) as f64 * 180.0 / (COMPACT_STEPS - 1.0) - 90.0
+}
+
+pub fn encode_compact_longitude(longitude: f64) -> [u8; 3] {
+    let steps = round((longitude + 180.0) / 360.0 * COMPACT_STEPS);
+
+    // +180° and -180° are the same meridian, so wrap rather than clamp
+    encode_u24((steps as i64).rem_euclid(COMPACT_STEPS as i64) as u32)
+}
+
+pub fn decode_compact_longitude(bytes: [u8; 3]) -> f64 {
+    decode_u24(bytes) as f64 * 360.0 / COMPACT_STEPS - 180.0
+}
+
+fn encode_u24(value: u32) -> [u8; 3] {
+    let [b0, b1, b2, _] = value.to_le_bytes();
+    [b0, b1, b2]
+}
+
+fn decode_u24(bytes: [u8; 3]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0])
+}
+
+/// Write the type byte followed by the coordinates in the precision it selects
+///
+/// Returns the number of bytes written.
+pub fn encode_position(
+    packet_type: PacketType,
+    latitude: f64,
+    longitude: f64,
+    buffer: &mut [u8],
+) -> Result<usize, LoraError> {
+    let len = 1 + packet_type.payload_len();
+    let buffer = buffer.get_mut(..len).ok_or(LoraError::BufferError)?;
+
+    buffer[0] = packet_type as u8;
+
+    match packet_type {
+        PacketType::Position => {
+            buffer[1..5].copy_from_slice(&encode_coordinate(latitude));
+            buffer[5..9].copy_from_slice(&encode_coordinate(longitude));
+        }
+        PacketType::CompactPosition => {
+            buffer[1..4].copy_from_slice(&encode_compact_latitude(latitude));
+            buffer[4..7].copy_from_slice(&encode_compact_longitude(longitude));
+        }
+        _ => return Err(LoraError::UnexpectedPacketType(packet_type as u8)),
+    }
+
+    Ok(len)
+}
+
+/// Decode a position written by `encode_position`, picking the decoder from the type byte
+pub fn decode_position(bytes: &[u8]) -> Result<(f64, f64), LoraError> {
+    let packet_type = PacketType::try_from(*bytes.first().ok_or(LoraError::NoData)?)?;
+    let bytes = bytes
+        .get(1..1 + packet_type.payload_len())
+        .ok_or(LoraError::BufferError)?;
+
+    Ok(match packet_type {
+        PacketType::Position => (
+            decode_coordinate([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            decode_coordinate([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        ),
+        PacketType::CompactPosition => (
+            decode_compact_latitude([bytes[0], bytes[1], bytes[2]]),
+            decode_compact_longitude([bytes[3], bytes[4], bytes[5]]),
+        ),
+        _ => return Err(LoraError::UnexpectedPacketType(packet_type as u8)),
+    })
+}
+
+/// Device health in one byte, shared by the LoRa status beacon and the BLE status characteristic
+///
+/// | Bit | Flag                                                     |
+/// |-----|----------------------------------------------------------|
+/// | 0   | `gps_fix`: a fresh, valid GPS fix is available           |
+/// | 1   | `lora_active`: a LoRa frame was received recently        |
+/// | 2   | `battery_low`: the battery needs charging                |
+/// | 3   | `error`: an error counter went up since the last report  |
+///
+/// Bits 4-7 are reserved and sent as zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HealthFlags {
+    pub gps_fix: bool,
+    pub lora_active: bool,
+    pub battery_low: bool,
+    pub error: bool,
+}
+
+impl From<HealthFlags> for u8 {
+    fn from(flags: HealthFlags) -> Self {
+        flags.gps_fix as u8
+            | (flags.lora_active as u8) << 1
+            | (flags.battery_low as u8) << 2
+            | (flags.error as u8) << 3
+    }
+}
+
+impl From<u8> for HealthFlags {
+    fn from(byte: u8) -> Self {
+        Self {
+            gps_fix: byte & 1 != 0,
+            lora_active: byte & 1 << 1 != 0,
+            battery_low: byte & 1 << 2 != 0,
+            error: byte & 1 << 3 != 0,
+        }
+    }
+}
+
+/// Battery and link health, so a base station can spot nodes that are running flat or barely
+/// in range
+///
+/// | Byte | Field                                                                 |
+/// |------|-----------------------------------------------------------------------|
+/// | 0    | `PacketType::Status` (0x03)                                           |
+/// | 1    | Battery in percent; `BATTERY_UNKNOWN` (0xFF) when not measured        |
+/// | 2    | RSSI of the last frame heard, dBm as `i8`; `RSSI_NONE` (0x7F) if none |
+/// | 3    | `HealthFlags`                                                         |
+///
+/// RSSI below -128 dBm is clamped to -128; that is already at the SX1262's noise floor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeStatus {
+    pub battery_percent: Option<u8>,
+    pub last_rssi: Option<i16>,
+    pub flags: HealthFlags,
+}
+
+/// Write a `PacketType::Status` packet, returning the number of bytes written
+pub fn encode_status(status: &NodeStatus, buffer: &mut [u8]) -> Result<usize, LoraError> {
+    let len = 1 + PacketType::Status.payload_len();
+    let buffer = buffer.get_mut(..len).ok_or(LoraError::BufferError)?;
+
+    buffer[0] = PacketType::Status as u8;
+    buffer[1] = status
+        .battery_percent
+        .map_or(BATTERY_UNKNOWN, |percent| percent.min(100));
+    buffer[2] = status.last_rssi.map_or(RSSI_NONE, |rssi| {
+        rssi.clamp(i8::MIN as i16, i8::MAX as i16 - 1) as i8
+    }) as u8;
+    buffer[3] = status.flags.into();
+
+    Ok(len)
+}
+
+/// Decode a packet written by `encode_status`
+pub fn decode_status(bytes: &[u8]) -> Result<NodeStatus, LoraError> {
+    let packet_type = PacketType::try_from(*bytes.first().ok_or(LoraError::NoData)?)?;
+    if packet_type != PacketType::Status {
+        return Err(LoraError::UnexpectedPacketType(packet_type as u8));
+    }
+
+    let bytes = bytes
+        .get(1..1 + packet_type.payload_len())
+        .ok_or(LoraError::BufferError)?;
+
+    Ok(NodeStatus {
+        battery_percent: (bytes[0] != BATTERY_UNKNOWN).then_some(bytes[0]),
+        last_rssi: (bytes[1] as i8 != RSSI_NONE).then_some(bytes[1] as i8 as i16),
+        flags: HealthFlags::from(bytes[2]),
+    })
+}
+
+/// Write a `PacketType::Ack` for the frame numbered `sequence`, returning the number of bytes
+/// written
+pub fn encode_ack(sequence: u8, buffer: &mut [u8]) -> Result<usize, LoraError> {
+    let buffer = buffer
+        .get_mut(..1 + PacketType::Ack.payload_len())
+        .ok_or(LoraError::BufferError)?;
+
+    buffer.copy_from_slice(&[PacketType::Ack as u8, sequence]);
+
+    Ok(buffer.len())
+}
+
+/// The sequence number acknowledged by a packet written by `encode_ack`
+pub fn decode_ack(bytes: &[u8]) -> Result<u8, LoraError> {
+    let packet_type = PacketType::try_from(*bytes.first().ok_or(LoraError::NoData)?)?;
+    if packet_type != PacketType::Ack {
+        return Err(LoraError::UnexpectedPacketType(packet_type as u8));
+    }
+
+    bytes.get(1).copied().ok_or(LoraError::BufferError)
+}
+
+/// Write a `PacketType::Text` packet, returning the number of bytes written
+pub fn encode_text(text: &str, buffer: &mut [u8]) -> Result<usize, LoraError> {
+    let len = 1 + text.len();
+    let buffer = buffer.get_mut(..len).ok_or(LoraError::BufferError)?;
+
+    buffer[0] = PacketType::Text as u8;
+    buffer[1..].copy_from_slice(text.as_bytes());
+
+    Ok(len)
+}
+
+/// The text of a packet written by `encode_text`
+pub fn decode_text(bytes: &[u8]) -> Result<&str, LoraError> {
+    let packet_type = PacketType::try_from(*bytes.first().ok_or(LoraError::NoData)?)?;
+    if packet_type != PacketType::Text {
+        return Err(LoraError::UnexpectedPacketType(packet_type as u8));
+    }
+
+    core::str::from_utf8(&bytes[1..]).map_err(|_| LoraError::InvalidText)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Metres per degree of latitude (and of longitude at the equator)
+    const METRES_PER_DEGREE: f64 = 111_320.0;
+
+    /// Worst-case rounding error of the compact encoding: half of the ~1.2 m latitude step
+    const COMPACT_NORTH_ERROR_BOUND_METRES: f64 = 0.6;
+
+    /// Worst-case rounding error of the compact encoding: half of the ~2.4 m longitude step
+    const COMPACT_EAST_ERROR_BOUND_METRES: f64 = 1.2;
+
+    /// North-south and east-west error in metres after a compact round trip
+    fn compact_error_metres(latitude: f64, longitude: f64) -> (f64, f64) {
+        let mut buffer = [0u8; 7];
+        encode_position(
+            PacketType::CompactPosition,
+            latitude,
+            longitude,
+            &mut buffer,
+        )
+        .unwrap();
+        let (decoded_latitude, decoded_longitude) = decode_position(&buffer).unwrap();
+
+        let north = (decoded_latitude - latitude) * METRES_PER_DEGREE;
+        let east =
+            (decoded_longitude - longitude) * METRES_PER_DEGREE * latitude.to_radians().cos();
+
+        (north.abs(), east.abs())
+    }
+
+    #[test]
+    fn test_compact_error_within_bound() {
+        let positions = [
+            (0.0, 0.0),
+            (0.000_011, -0.000_011),
+            (37.774_929, -122.419_416),
+            (-33.868_820, 151.209_296),
+            (51.507_351, -0.127_758),
+            (64.146_582, -21.942_635),
+            (-77.846_323, 166.668_235),
+            (89.999_999, 179.999_999),
+        ];
+
+        for (latitude, longitude) in positions {
+            let (north, east) = compact_error_metres(latitude, longitude);
+
+            assert!(
+                north <= COMPACT_NORTH_ERROR_BOUND_METRES,
+                "{north} m north-south error at ({latitude}, {longitude})"
+            );
+            assert!(
+                east <= COMPACT_EAST_ERROR_BOUND_METRES,
+                "{east} m east-west error at ({latitude}, {longitude})"
+            );
+        }
+    }
+
+    #[test]
+    fn test_compact_longitude_wraps_at_antimeridian() {
+        assert_eq!(
+            encode_compact_longitude(180.0),
+            encode_compact_longitude(-180.0)
+        );
+    }
+
+    #[test]
+    fn test_fixed_point_round_trip() {
+        let mut buffer = [0u8; 9];
+        let len = encode_position(
+            PacketType::Position,
+            -33.868_820_1,
+            151.209_296_4,
+            &mut buffer,
+        )
+        .unwrap();
+        let (latitude, longitude) = decode_position(&buffer[..len]).unwrap();
+
+        assert_eq!(len, 9);
+        assert!((latitude - -33.868_820_1).abs() < 1e-7);
+        assert!((longitude - 151.209_296_4).abs() < 1e-7);
+    }
+
+    #[test]
+    fn test_type_byte_selects_length() {
+        let mut buffer = [0u8; 16];
+
+        assert_eq!(
+            encode_position(PacketType::CompactPosition, 1.0, 2.0, &mut buffer).unwrap(),
+            7
+        );
+        assert!(matches!(
+            decode_position(&[0x7f, 0, 0, 0, 0, 0, 0]),
+            Err(LoraError::UnknownPacketType(0x7f))
+        ));
+        assert!(matches!(
+            decode_position(&buffer[..5]),
+            Err(LoraError::BufferError)
+        ));
+    }
+
+    #[test]
+    fn test_status_round_trip() {
+        let mut buffer = [0u8; 8];
+        let status = NodeStatus {
+            battery_percent: Some(42),
+            last_rssi: Some(-97),
+            flags: HealthFlags {
+                gps_fix: true,
+                error: true,
+                ..HealthFlags::default()
+            },
+        };
+
+        let len = encode_status(&status, &mut buffer).unwrap();
+
+        assert_eq!(&buffer[..len], &[0x03, 42, (-97i8) as u8, 0b1001]);
+        assert_eq!(decode_status(&buffer[..len]).unwrap(), status);
+    }
+
+    #[test]
+    fn test_status_unknowns_and_clamping() {
+        let mut buffer = [0u8; 4];
+
+        encode_status(
+            &NodeStatus {
+                battery_percent: None,
+                last_rssi: None,
+                flags: HealthFlags::default(),
+            },
+            &mut buffer,
+        )
+        .unwrap();
+        assert_eq!(
+            decode_status(&buffer).unwrap(),
+            NodeStatus {
+                battery_percent: None,
+                last_rssi: None,
+                flags: HealthFlags::default(),
+            }
+        );
+
+        encode_status(
+            &NodeStatus {
+                battery_percent: Some(120),
+                last_rssi: Some(-140),
+                flags: HealthFlags::default(),
+            },
+            &mut buffer,
+        )
+        .unwrap();
+        assert_eq!(
+            decode_status(&buffer).unwrap(),
+            NodeStatus {
+                battery_percent: Some(100),
+                last_rssi: Some(-128),
+                flags: HealthFlags::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_ack_and_text_round_trip() {
+        let mut buffer = [0u8; 16];
+
+        let len = encode_ack(0xA5, &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &[0x09, 0xA5]);
+        assert_eq!(decode_ack(&buffer[..len]).unwrap(), 0xA5);
+
+        let len = encode_text("on my way", &mut buffer).unwrap();
+        assert_eq!(decode_text(&buffer[..len]).unwrap(), "on my way");
+        assert!(matches!(
+            decode_text(&[0x0B, 0xFF]),
+            Err(LoraError::InvalidText)
+        ));
+        assert!(encode_text("far too long to fit", &mut buffer).is_err());
+    }
+
+    #[test]
+    fn test_health_flags_bits() {
+        let flags = HealthFlags {
+            lora_active: true,
+            battery_low: true,
+            ..HealthFlags::default()
+        };
+
+        assert_eq!(u8::from(flags), 0b0110);
+        assert_eq!(HealthFlags::from(0b0110), flags);
+        assert_eq!(HealthFlags::from(0xf0), HealthFlags::default());
+    }
+
+    #[test]
+    fn test_status_and_position_decoders_reject_each_other() {
+        let mut buffer = [0u8; 9];
+
+        encode_position(PacketType::Position, 1.0, 2.0, &mut buffer).unwrap();
+        assert!(matches!(
+            decode_status(&buffer),
+            Err(LoraError::UnexpectedPacketType(0x01))
+        ));
+
+        assert!(matches!(
+            decode_position(&[0x03, 50, 0, 0]),
+            Err(LoraError::UnexpectedPacketType(0x03))
+        ));
+    }
+}
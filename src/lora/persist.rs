@@ -0,0 +1,55 @@
+use embedded_storage::{ReadStorage, Storage};
+use esp_storage::{FlashStorage, FlashStorageError};
+
+use super::crypto::{key_from_bytes, KEY_RECORD_SIZE, KEY_SIZE};
+use super::settings::{RadioSettings, SETTINGS_SIZE};
+use crate::flashlog::storage::{REGION_OFFSET, REGION_SECTORS};
+
+/// Where the radio settings are kept: the sector after the BLE identity, which follows the log
+pub const SETTINGS_OFFSET: u32 =
+    REGION_OFFSET + (REGION_SECTORS + 1) * FlashStorage::ERASE_SIZE as u32;
+
+/// Where the network key is kept: the sector after the BLE bonds, which follow the settings
+pub const KEY_OFFSET: u32 = SETTINGS_OFFSET + 2 * FlashStorage::ERASE_SIZE as u32;
+
+/// The settings last stored, `None` if there aren't any or they can't be read
+pub fn load() -> Option<RadioSettings> {
+    let mut bytes = [0u8; SETTINGS_SIZE];
+    if let Err(e) = FlashStorage::new().read(SETTINGS_OFFSET, &mut bytes) {
+        defmt::warn!(
+            "Failed to read the radio settings: {:?}",
+            defmt::Debug2Format(&e)
+        );
+        return None;
+    }
+
+    RadioSettings::from_bytes(&bytes)
+}
+
+/// Keep `settings` for the next boot
+///
+/// Rewrites the whole sector, which stalls the CPU for tens of milliseconds.
+pub fn store(settings: &RadioSettings) -> Result<(), FlashStorageError> {
+    FlashStorage::new().write(SETTINGS_OFFSET, &settings.to_bytes())
+}
+
+/// The network key provisioned to flash, `None` if there isn't one or it can't be read
+///
+/// The firmware never writes the key, it's put there along with the firmware, as the record
+/// `crypto::key_from_bytes` describes:
+///
+/// ```text
+/// espflash write-bin 0x503000 lora-key.bin
+/// ```
+pub fn load_key() -> Option<[u8; KEY_SIZE]> {
+    let mut bytes = [0u8; KEY_RECORD_SIZE];
+    if let Err(e) = FlashStorage::new().read(KEY_OFFSET, &mut bytes) {
+        defmt::warn!(
+            "Failed to read the network key: {:?}",
+            defmt::Debug2Format(&e)
+        );
+        return None;
+    }
+
+    key_from_bytes(&bytes)
+}
@@ -0,0 +1,182 @@
+use super::duty::DutyCycleConfig;
+use super::error::LoraError;
+
+/// Lowest output power the SX1262 can be set to, in dBm
+pub const MIN_TX_POWER: i32 = -9;
+
+/// Highest output power of the SX1262's high-power PA, in dBm
+pub const MAX_TX_POWER: i32 = 22;
+
+/// Longest symbol allowed under a 400 ms dwell time limit, as `2^sf / bandwidth` with the
+/// bandwidth in units of 125 kHz: SF10 at 125 kHz, about 8 ms per symbol
+///
+/// Keeps the largest position report comfortably under the limit, and matches the slowest data
+/// rates LoRaWAN allows in those regions.
+const MAX_DWELL_LIMITED_SYMBOL: u32 = 1 << 10;
+
+/// Regulatory region the radio operates in, which bounds its frequency and output power
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Region {
+    /// 902-928 MHz, 400 ms dwell time
+    #[default]
+    Us915,
+
+    /// 863-870 MHz, 14 dBm
+    Eu868,
+
+    /// 915-928 MHz, 16 dBm
+    As923,
+
+    /// 915-928 MHz, 400 ms dwell time
+    Au915,
+}
+
+impl Region {
+    /// Band edges in Hz, inclusive
+    pub const fn frequency_range(self) -> (u32, u32) {
+        match self {
+            Region::Us915 => (902_000_000, 928_000_000),
+            Region::Eu868 => (863_000_000, 870_000_000),
+            Region::As923 | Region::Au915 => (915_000_000, 928_000_000),
+        }
+    }
+
+    /// Centre frequency used unless configured otherwise, in Hz
+    ///
+    /// Each is a 125 kHz LoRaWAN default channel for the region, clear of the band edges.
+    pub const fn default_frequency(self) -> u32 {
+        match self {
+            Region::Us915 => 915_000_000,
+            Region::Eu868 => 868_100_000,
+            Region::As923 => 923_200_000,
+            Region::Au915 => 915_200_000,
+        }
+    }
+
+    /// Highest output power allowed, in dBm at the radio; antenna gain is not accounted for
+    pub const fn max_tx_power(self) -> i32 {
+        match self {
+            Region::Us915 | Region::Au915 => MAX_TX_POWER,
+            Region::Eu868 => 14,
+            Region::As923 => 16,
+        }
+    }
+
+    /// Most airtime allowed, where the band is duty cycle limited
+    ///
+    /// 1% an hour covers the default channels of `Region::Eu868`, in the g1 sub-band. Some of
+    /// the band allows as little as 0.1%, so a frequency outside 868.0-868.6 MHz may need a
+    /// stricter `LoraConfig::duty_cycle`. Elsewhere the limits vary by country, or there's a
+    /// dwell time limit instead.
+    pub const fn duty_cycle(self) -> Option<DutyCycleConfig> {
+        match self {
+            Region::Eu868 => Some(DutyCycleConfig {
+                limit_permille: 10,
+                window_ms: 3_600_000,
+            }),
+            Region::Us915 | Region::As923 | Region::Au915 => None,
+        }
+    }
+
+    /// Whether transmissions are limited to 400 ms on air
+    pub const fn has_dwell_limit(self) -> bool {
+        matches!(self, Region::Us915 | Region::Au915)
+    }
+
+    /// Check that a channel `bandwidth_hz` wide centred on `frequency` fits within the band
+    pub fn check_frequency(self, frequency: u32, bandwidth_hz: u32) -> Result<(), LoraError> {
+        let (low, high) = self.frequency_range();
+        let half_bandwidth = bandwidth_hz / 2;
+
+        if frequency.saturating_sub(half_bandwidth) < low
+            || frequency.saturating_add(half_bandwidth) > high
+        {
+            return Err(LoraError::InvalidConfig);
+        }
+
+        Ok(())
+    }
+
+    pub fn check_tx_power(self, tx_power: i32) -> Result<(), LoraError> {
+        if !(MIN_TX_POWER..=self.max_tx_power()).contains(&tx_power) {
+            return Err(LoraError::InvalidConfig);
+        }
+
+        Ok(())
+    }
+
+    /// Check that `spreading_factor` at `bandwidth_hz` is slow enough to stay within the dwell
+    /// time limit, where there is one
+    pub fn check_modulation(
+        self,
+        spreading_factor: u8,
+        bandwidth_hz: u32,
+    ) -> Result<(), LoraError> {
+        if !self.has_dwell_limit() {
+            return Ok(());
+        }
+
+        // 2^sf / bandwidth <= 2^10 / 125 kHz, kept in integers
+        let symbol = (1u64 << spreading_factor) * 125_000;
+        if symbol > MAX_DWELL_LIMITED_SYMBOL as u64 * bandwidth_hz as u64 {
+            return Err(LoraError::InvalidConfig);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frequency_outside_band() {
+        assert!(Region::Us915.check_frequency(915_000_000, 125_000).is_ok());
+        assert!(Region::Eu868.check_frequency(915_000_000, 125_000).is_err());
+
+        // Centred inside the band, but the upper half of the channel spills over the edge
+        assert!(Region::Eu868.check_frequency(869_950_000, 125_000).is_err());
+        assert!(Region::Eu868.check_frequency(869_525_000, 250_000).is_ok());
+    }
+
+    #[test]
+    fn test_default_frequencies_are_in_band() {
+        for region in [Region::Us915, Region::Eu868, Region::As923, Region::Au915] {
+            assert!(region
+                .check_frequency(region.default_frequency(), 125_000)
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_tx_power_limits() {
+        assert!(Region::Us915.check_tx_power(22).is_ok());
+        assert!(Region::Us915.check_tx_power(23).is_err());
+        assert!(Region::Eu868.check_tx_power(20).is_err());
+        assert!(Region::Eu868.check_tx_power(14).is_ok());
+        assert!(Region::As923.check_tx_power(MIN_TX_POWER - 1).is_err());
+    }
+
+    #[test]
+    fn test_dwell_limit_caps_spreading_factor() {
+        assert!(Region::Us915.check_modulation(10, 125_000).is_ok());
+        assert!(Region::Us915.check_modulation(12, 125_000).is_err());
+        assert!(Region::Us915.check_modulation(11, 250_000).is_ok());
+        assert!(Region::Au915.check_modulation(12, 500_000).is_ok());
+
+        // No dwell limit in Europe
+        assert!(Region::Eu868.check_modulation(12, 125_000).is_ok());
+    }
+
+    #[test]
+    fn test_duty_cycle_only_in_europe() {
+        assert_eq!(
+            Region::Eu868
+                .duty_cycle()
+                .map(|duty_cycle| duty_cycle.budget_us()),
+            Some(36_000_000)
+        );
+        assert_eq!(Region::Us915.duty_cycle(), None);
+    }
+}
@@ -0,0 +1,297 @@
+use heapless::HistoryBuffer;
+
+use super::error::LoraError;
+use super::packet::PacketType;
+
+/// Hops a relayed packet may take unless configured otherwise
+pub const DEFAULT_MAX_HOPS: u8 = 3;
+
+/// Recently seen packets remembered for duplicate detection
+///
+/// Has to cover every packet a node can hear within the time a packet takes to travel the mesh;
+/// sequence numbers wrap after 256 packets per source.
+const SEEN_SIZE: usize = 32;
+
+/// Bytes in front of the relayed packet, including the type byte
+pub const RELAY_HEADER_LEN: usize = 1 + PacketType::Relayed.payload_len();
+
+/// Store-and-forward envelope around another packet, see `Relay`
+///
+/// | Byte | Field                                               |
+/// |------|-----------------------------------------------------|
+/// | 0    | `PacketType::Relayed` (0x08)                        |
+/// | 1    | Source node ID, `u24` little-endian                 |
+/// | 4    | Sequence number, per source                         |
+/// | 5    | Hops taken so far, 0 as sent by the source          |
+/// | 6    | Most hops the source allows                         |
+/// | 7    | The original packet, type byte included             |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayHeader {
+    pub source: u32,
+    pub sequence: u8,
+    pub hops: u8,
+    pub max_hops: u8,
+}
+
+/// Write `header` followed by `packet`, returning the number of bytes written
+pub fn encode_relayed(
+    header: &RelayHeader,
+    packet: &[u8],
+    buffer: &mut [u8],
+) -> Result<usize, LoraError> {
+    let len = RELAY_HEADER_LEN + packet.len();
+    let buffer = buffer.get_mut(..len).ok_or(LoraError::BufferError)?;
+
+    let [source_0, source_1, source_2, _] = header.source.to_le_bytes();
+    buffer[..RELAY_HEADER_LEN].copy_from_slice(&[
+        PacketType::Relayed as u8,
+        source_0,
+        source_1,
+        source_2,
+        header.sequence,
+        header.hops,
+        header.max_hops,
+    ]);
+    buffer[RELAY_HEADER_LEN..].copy_from_slice(packet);
+
+    Ok(len)
+}
+
+/// Split a relayed packet into its header and the original packet
+pub fn decode_relayed(bytes: &[u8]) -> Result<(RelayHeader, &[u8]), LoraError> {
+    let packet_type = PacketType::try_from(*bytes.first().ok_or(LoraError::NoData)?)?;
+    if packet_type != PacketType::Relayed {
+        return Err(LoraError::UnexpectedPacketType(packet_type as u8));
+    }
+
+    let header = bytes
+        .get(..RELAY_HEADER_LEN)
+        .ok_or(LoraError::BufferError)?;
+    let packet = &bytes[RELAY_HEADER_LEN..];
+    if packet.is_empty() {
+        return Err(LoraError::NoData);
+    }
+
+    Ok((
+        RelayHeader {
+            source: u32::from_le_bytes([header[1], header[2], header[3], 0]),
+            sequence: header[4],
+            hops: header[5],
+            max_hops: header[6],
+        },
+        packet,
+    ))
+}
+
+/// Opt-in relaying, see `Relay`
+#[derive(Debug, Clone, Copy)]
+pub struct RelayConfig {
+    /// This node's ID as the source of its own packets, unique among the nodes in the mesh
+    ///
+    /// Only the low 24 bits are sent, which is all of `device_id()`.
+    pub node_id: u32,
+
+    /// Hops allowed on this node's own packets, and the most it forwards anyone else's for,
+    /// whatever their source asked for
+    pub max_hops: u8,
+}
+
+/// What became of a received relayed packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayVerdict {
+    /// New, with hops to spare; the envelope for rebroadcasting is this many bytes
+    Forward(usize),
+
+    /// New, but it has already taken all the hops it's allowed
+    HopLimit,
+
+    /// Already seen, directly or through another relay
+    Duplicate,
+
+    /// This node's own packet, relayed back to it
+    Own,
+}
+
+/// Wraps this node's packets so others can relay them, and relays theirs
+///
+/// Every packet is identified by its source and sequence number, so each node forwards it at
+/// most once however many relays it arrives through, and drops its own packets coming back.
+/// With the hop count capped as well, a packet can't circulate forever.
+pub struct Relay {
+    config: RelayConfig,
+    sequence: u8,
+    seen: HistoryBuffer<(u32, u8), SEEN_SIZE>,
+}
+
+impl Relay {
+    pub fn new(config: RelayConfig) -> Self {
+        Self {
+            config,
+            sequence: 0,
+            seen: HistoryBuffer::new(),
+        }
+    }
+
+    /// Originate this node's packets as `node_id` from now on, e.g. after it was changed over BLE
+    pub fn set_node_id(&mut self, node_id: u32) {
+        self.config.node_id = node_id;
+    }
+
+    /// Wrap one of this node's own packets, returning the number of bytes written
+    pub fn originate(&mut self, packet: &[u8], buffer: &mut [u8]) -> Result<usize, LoraError> {
+        let header = RelayHeader {
+            source: self.config.node_id,
+            sequence: self.sequence,
+            hops: 0,
+            max_hops: self.config.max_hops,
+        };
+        let len = encode_relayed(&header, packet, buffer)?;
+
+        self.sequence = self.sequence.wrapping_add(1);
+
+        Ok(len)
+    }
+
+    /// Decide whether to forward a received relayed packet, writing the envelope with the hop
+    /// count incremented into `buffer` if so
+    pub fn handle(&mut self, bytes: &[u8], buffer: &mut [u8]) -> Result<RelayVerdict, LoraError> {
+        let (header, packet) = decode_relayed(bytes)?;
+
+        if header.source == self.config.node_id & 0xFF_FFFF {
+            return Ok(RelayVerdict::Own);
+        }
+
+        let id = (header.source, header.sequence);
+        if self.seen.oldest_ordered().any(|&seen| seen == id) {
+            return Ok(RelayVerdict::Duplicate);
+        }
+        self.seen.write(id);
+
+        if header.hops >= header.max_hops.min(self.config.max_hops) {
+            return Ok(RelayVerdict::HopLimit);
+        }
+
+        let forwarded = RelayHeader {
+            hops: header.hops + 1,
+            ..header
+        };
+
+        let len = encode_relayed(&forwarded, packet, buffer)?;
+
+        Ok(RelayVerdict::Forward(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET: [u8; 3] = [PacketType::Status as u8, 0x50, 0xC4];
+
+    fn relay(node_id: u32) -> Relay {
+        Relay::new(RelayConfig {
+            node_id,
+            max_hops: DEFAULT_MAX_HOPS,
+        })
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let mut buffer = [0u8; 16];
+        let len = relay(0xABCDEF).originate(&PACKET, &mut buffer).unwrap();
+
+        let (header, packet) = decode_relayed(&buffer[..len]).unwrap();
+        assert_eq!(
+            header,
+            RelayHeader {
+                source: 0xABCDEF,
+                sequence: 0,
+                hops: 0,
+                max_hops: DEFAULT_MAX_HOPS,
+            }
+        );
+        assert_eq!(packet, PACKET);
+    }
+
+    #[test]
+    fn test_hop_limit() {
+        let mut source = relay(1);
+        let mut hops = [relay(2), relay(3), relay(4), relay(5)];
+
+        let mut packet = [0u8; 16];
+        let len = source.originate(&PACKET, &mut packet).unwrap();
+
+        // Three relays in a chain forward it, the fourth is one hop too many
+        for node in &mut hops[..3] {
+            let mut forwarded = [0u8; 16];
+            let verdict = node.handle(&packet[..len], &mut forwarded).unwrap();
+            assert_eq!(verdict, RelayVerdict::Forward(len));
+
+            packet = forwarded;
+        }
+        assert_eq!(decode_relayed(&packet[..len]).unwrap().0.hops, 3);
+
+        let verdict = hops[3].handle(&packet[..len], &mut [0u8; 16]).unwrap();
+        assert_eq!(verdict, RelayVerdict::HopLimit);
+    }
+
+    #[test]
+    fn test_relay_caps_hops_below_source() {
+        let mut source = Relay::new(RelayConfig {
+            node_id: 1,
+            max_hops: 10,
+        });
+        let mut cautious = Relay::new(RelayConfig {
+            node_id: 2,
+            max_hops: 0,
+        });
+
+        let mut packet = [0u8; 16];
+        let len = source.originate(&PACKET, &mut packet).unwrap();
+
+        let verdict = cautious.handle(&packet[..len], &mut [0u8; 16]).unwrap();
+        assert_eq!(verdict, RelayVerdict::HopLimit);
+    }
+
+    #[test]
+    fn test_loop_prevention() {
+        let mut source = relay(1);
+        let mut a = relay(2);
+        let mut b = relay(3);
+
+        let mut packet = [0u8; 16];
+        let len = source.originate(&PACKET, &mut packet).unwrap();
+
+        // A forwards it, B hears both the source and A's copy but forwards only once
+        let mut from_a = [0u8; 16];
+        assert_eq!(
+            a.handle(&packet[..len], &mut from_a).unwrap(),
+            RelayVerdict::Forward(len)
+        );
+        assert_eq!(
+            b.handle(&packet[..len], &mut [0u8; 16]).unwrap(),
+            RelayVerdict::Forward(len)
+        );
+        assert_eq!(
+            b.handle(&from_a[..len], &mut [0u8; 16]).unwrap(),
+            RelayVerdict::Duplicate
+        );
+
+        // Nor does A forward B's copy back, and the source ignores its own packet
+        assert_eq!(
+            a.handle(&from_a[..len], &mut [0u8; 16]).unwrap(),
+            RelayVerdict::Duplicate
+        );
+        assert_eq!(
+            source.handle(&from_a[..len], &mut [0u8; 16]).unwrap(),
+            RelayVerdict::Own
+        );
+
+        // The source's next packet is new again
+        let len = source.originate(&PACKET, &mut packet).unwrap();
+        assert_eq!(
+            b.handle(&packet[..len], &mut [0u8; 16]).unwrap(),
+            RelayVerdict::Forward(len)
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use crate::gnss::positioning::GnssPositioning;
+
+use super::error::LoraError;
+use super::packet::{
+    decode_coordinate, decode_position, encode_coordinate, encode_position, round, PacketType,
+};
+
+/// Centimetres per second in one knot
+pub const CM_PER_S_PER_KNOT: f64 = 51.444_444;
+
+/// Speed or heading field of a report that had none
+const U16_UNKNOWN: u16 = u16::MAX;
+
+/// Altitude field of a report that had none
+const ALTITUDE_UNKNOWN: i16 = i16::MIN;
+
+/// Satellite count field of a report that had none
+const SATELLITES_UNKNOWN: u8 = u8::MAX;
+
+/// Timestamp field of a report that had none; no real fix predates 1970
+const TIMESTAMP_UNKNOWN: u32 = 0;
+
+/// On-air position format, so a deployment can trade airtime for detail without reflashing
+///
+/// The format is carried in the packet type byte, so receivers decode whatever arrives
+/// regardless of their own setting. All multi-byte fields are little-endian; unknown values are
+/// sent as the sentinel noted next to each field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ReportFormat {
+    /// `PacketType::CompactPosition`: 3-byte latitude and longitude, 6 bytes
+    Compact = 0,
+
+    /// `PacketType::StandardPosition`, 12 bytes:
+    ///
+    /// | Offset | Type  | Field                                      |
+    /// |--------|-------|--------------------------------------------|
+    /// | 0      | `i32` | Latitude, 1e-7 degree                      |
+    /// | 4      | `i32` | Longitude, 1e-7 degree                     |
+    /// | 8      | `u16` | Speed over ground, cm/s (`0xFFFF` unknown) |
+    /// | 10     | `u16` | Heading, 0.01 degree (`0xFFFF` unknown)    |
+    #[default]
+    Standard = 1,
+
+    /// `PacketType::FullPosition`, the standard fields followed by these, 19 bytes:
+    ///
+    /// | Offset | Type  | Field                                            |
+    /// |--------|-------|--------------------------------------------------|
+    /// | 12     | `i16` | Altitude above sea level, m (`i16::MIN` unknown) |
+    /// | 14     | `u8`  | Satellites in use (`0xFF` unknown)               |
+    /// | 15     | `u32` | Fix time, Unix seconds (`0` unknown)             |
+    Full = 2,
+
+    /// `PacketType::PositionKeyframe` followed by `PacketType::PositionDelta`s, coordinates only
+    ///
+    /// Keyframes are 10 bytes on air and deltas 6; see `delta::DeltaEncoder` for the layout.
+    /// Stateful, so these go through a `DeltaEncoder` instead of `encode_report`.
+    Delta = 3,
+}
+
+impl TryFrom<u8> for ReportFormat {
+    type Error = LoraError;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        match byte {
+            0 => Ok(ReportFormat::Compact),
+            1 => Ok(ReportFormat::Standard),
+            2 => Ok(ReportFormat::Full),
+            3 => Ok(ReportFormat::Delta),
+            _ => Err(LoraError::InvalidConfig),
+        }
+    }
+}
+
+impl ReportFormat {
+    pub const fn packet_type(self) -> PacketType {
+        match self {
+            ReportFormat::Compact => PacketType::CompactPosition,
+            ReportFormat::Standard => PacketType::StandardPosition,
+            ReportFormat::Full => PacketType::FullPosition,
+            ReportFormat::Delta => PacketType::PositionKeyframe,
+        }
+    }
+}
+
+/// Everything a position report can carry; fields a format doesn't include decode as `None`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PositionReport {
+    pub latitude: f64,
+    pub longitude: f64,
+
+    /// Speed over ground, knots
+    pub speed: Option<f32>,
+
+    /// True course, degrees
+    pub heading: Option<f32>,
+
+    /// Metres above mean sea level
+    pub altitude: Option<f32>,
+
+    pub satellites: Option<u8>,
+
+    /// Fix time, Unix seconds
+    pub timestamp: Option<u32>,
+}
+
+impl From<&GnssPositioning> for PositionReport {
+    fn from(positioning: &GnssPositioning) -> Self {
+        Self {
+            latitude: positioning.latitude,
+            longitude: positioning.longitude,
+            speed: positioning.speed,
+            heading: positioning.heading,
+            altitude: positioning.altitude,
+            satellites: positioning
+                .satellites
+                .map(|satellites| satellites.min(SATELLITES_UNKNOWN as u32 - 1) as u8),
+            timestamp: u32::try_from(positioning.datetime.and_utc().timestamp()).ok(),
+        }
+    }
+}
+
+fn encode_u16(value: Option<f64>) -> [u8; 2] {
+    value
+        .map_or(U16_UNKNOWN, |value| {
+            round(value).clamp(0.0, (U16_UNKNOWN - 1) as f64) as u16
+        })
+        .to_le_bytes()
+}
+
+fn decode_u16(bytes: [u8; 2]) -> Option<f64> {
+    let value = u16::from_le_bytes(bytes);
+
+    (value != U16_UNKNOWN).then_some(value as f64)
+}
+
+/// Write `report` in the given format, returning the number of bytes written
+pub fn encode_report(
+    format: ReportFormat,
+    report: &PositionReport,
+    buffer: &mut [u8],
+) -> Result<usize, LoraError> {
+    let packet_type = format.packet_type();
+    match format {
+        ReportFormat::Compact => {
+            return encode_position(packet_type, report.latitude, report.longitude, buffer)
+        }
+        ReportFormat::Delta => return Err(LoraError::UnexpectedPacketType(packet_type as u8)),
+        ReportFormat::Standard | ReportFormat::Full => {}
+    }
+
+    let len = 1 + packet_type.payload_len();
+    let buffer = buffer.get_mut(..len).ok_or(LoraError::BufferError)?;
+
+    buffer[0] = packet_type as u8;
+    buffer[1..5].copy_from_slice(&encode_coordinate(report.latitude));
+    buffer[5..9].copy_from_slice(&encode_coordinate(report.longitude));
+    buffer[9..11].copy_from_slice(&encode_u16(
+        report.speed.map(|speed| speed as f64 * CM_PER_S_PER_KNOT),
+    ));
+    buffer[11..13].copy_from_slice(&encode_u16(
+        report.heading.map(|heading| heading as f64 * 100.0),
+    ));
+
+    if format == ReportFormat::Full {
+        let altitude = report.altitude.map_or(ALTITUDE_UNKNOWN, |altitude| {
+            round(altitude as f64).clamp(ALTITUDE_UNKNOWN as f64 + 1.0, i16::MAX as f64) as i16
+        });
+
+        buffer[13..15].copy_from_slice(&altitude.to_le_bytes());
+        buffer[15] = report.satellites.unwrap_or(SATELLITES_UNKNOWN);
+        buffer[16..20]
+            .copy_from_slice(&report.timestamp.unwrap_or(TIMESTAMP_UNKNOWN).to_le_bytes());
+    }
+
+    Ok(len)
+}
+
+/// Decode any position packet, whichever format the sender chose
+pub fn decode_report(bytes: &[u8]) -> Result<PositionReport, LoraError> {
+    let packet_type = PacketType::try_from(*bytes.first().ok_or(LoraError::NoData)?)?;
+
+    let full = match packet_type {
+        PacketType::Position | PacketType::CompactPosition => {
+            let (latitude, longitude) = decode_position(bytes)?;
+
+            return Ok(PositionReport {
+                latitude,
+                longitude,
+                ..PositionReport::default()
+            });
+        }
+        PacketType::StandardPosition => false,
+        PacketType::FullPosition => true,
+        PacketType::Status
+        | PacketType::PositionKeyframe
+        | PacketType::PositionDelta
+        | PacketType::Relayed
+        | PacketType::Ack
+        | PacketType::Poll
+        | PacketType::Text
+        | PacketType::Encrypted => return Err(LoraError::UnexpectedPacketType(packet_type as u8)),
+    };
+
+    let bytes = bytes
+        .get(1..1 + packet_type.payload_len())
+        .ok_or(LoraError::BufferError)?;
+
+    let mut report = PositionReport {
+        latitude: decode_coordinate([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        longitude: decode_coordinate([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        speed: decode_u16([bytes[8], bytes[9]]).map(|speed| (speed / CM_PER_S_PER_KNOT) as f32),
+        heading: decode_u16([bytes[10], bytes[11]]).map(|heading| (heading / 100.0) as f32),
+        ..PositionReport::default()
+    };
+
+    if full {
+        let altitude = i16::from_le_bytes([bytes[12], bytes[13]]);
+        let timestamp = u32::from_le_bytes([bytes[15], bytes[16], bytes[17], bytes[18]]);
+
+        report.altitude = (altitude != ALTITUDE_UNKNOWN).then_some(altitude as f32);
+        report.satellites = (bytes[14] != SATELLITES_UNKNOWN).then_some(bytes[14]);
+        report.timestamp = (timestamp != TIMESTAMP_UNKNOWN).then_some(timestamp);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> PositionReport {
+        PositionReport {
+            latitude: 36.231_367,
+            longitude: -116.821_283,
+            speed: Some(12.5),
+            heading: Some(271.25),
+            altitude: Some(-86.0),
+            satellites: Some(9),
+            timestamp: Some(1_740_830_400),
+        }
+    }
+
+    fn round_trip(format: ReportFormat, report: &PositionReport) -> (usize, PositionReport) {
+        let mut buffer = [0u8; 32];
+        let len = encode_report(format, report, &mut buffer).unwrap();
+
+        assert_eq!(buffer[0], format.packet_type() as u8);
+
+        (len, decode_report(&buffer[..len]).unwrap())
+    }
+
+    #[test]
+    fn test_compact_drops_everything_but_coordinates() {
+        let (len, decoded) = round_trip(ReportFormat::Compact, &report());
+
+        assert_eq!(len, 7);
+        assert!((decoded.latitude - report().latitude).abs() < 1e-5);
+        assert!((decoded.longitude - report().longitude).abs() < 1e-4);
+        assert_eq!(decoded.speed, None);
+        assert_eq!(decoded.timestamp, None);
+    }
+
+    #[test]
+    fn test_standard_carries_speed_and_heading() {
+        let (len, decoded) = round_trip(ReportFormat::Standard, &report());
+
+        assert_eq!(len, 13);
+        assert!((decoded.latitude - report().latitude).abs() < 1e-7);
+        assert!((decoded.speed.unwrap() - 12.5).abs() < 0.01);
+        assert!((decoded.heading.unwrap() - 271.25).abs() < 0.01);
+        assert_eq!(decoded.altitude, None);
+        assert_eq!(decoded.satellites, None);
+    }
+
+    #[test]
+    fn test_full_carries_everything() {
+        let (len, decoded) = round_trip(ReportFormat::Full, &report());
+
+        assert_eq!(len, 20);
+        assert_eq!(decoded.altitude, Some(-86.0));
+        assert_eq!(decoded.satellites, Some(9));
+        assert_eq!(decoded.timestamp, Some(1_740_830_400));
+    }
+
+    #[test]
+    fn test_unknown_fields_stay_unknown() {
+        let sparse = PositionReport {
+            latitude: 1.0,
+            longitude: 2.0,
+            ..PositionReport::default()
+        };
+
+        let (_, decoded) = round_trip(ReportFormat::Full, &sparse);
+
+        assert_eq!(decoded.speed, None);
+        assert_eq!(decoded.heading, None);
+        assert_eq!(decoded.altitude, None);
+        assert_eq!(decoded.satellites, None);
+        assert_eq!(decoded.timestamp, None);
+    }
+
+    #[test]
+    fn test_format_byte_validation() {
+        assert_eq!(ReportFormat::try_from(2).unwrap(), ReportFormat::Full);
+        assert_eq!(ReportFormat::try_from(3).unwrap(), ReportFormat::Delta);
+        assert!(matches!(
+            ReportFormat::try_from(4),
+            Err(LoraError::InvalidConfig)
+        ));
+        assert_eq!(ReportFormat::default(), ReportFormat::Standard);
+    }
+}
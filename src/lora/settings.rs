@@ -0,0 +1,173 @@
+use core::ops::RangeInclusive;
+
+use super::error::LoraError;
+use super::header::BROADCAST;
+use super::region::Region;
+use super::report::ReportFormat;
+use crate::gnss::precision::CoordinatePrecision;
+
+/// Bytes `RadioSettings::to_bytes` produces
+pub const SETTINGS_SIZE: usize = 15;
+
+/// Marks stored settings, so that erased flash isn't taken for them; older `SBRS`, `SBR2` and
+/// `SBR3` settings, without all of the fields, are ignored
+const SETTINGS_MAGIC: [u8; 4] = *b"SBR4";
+
+/// Spreading factors the SX1262 supports in LoRa mode
+const SPREADING_FACTORS: RangeInclusive<u8> = 5..=12;
+
+/// Radio parameters that can be changed at runtime, and are kept across resets
+///
+/// Stored as:
+///
+/// | Byte | Field                                 |
+/// |------|---------------------------------------|
+/// | 0    | `SBR4`                                |
+/// | 4    | `frequency`, `u32` LE                 |
+/// | 8    | `spreading_factor`                    |
+/// | 9    | `node_id`, `u24` LE                   |
+/// | 12   | `report_format`                       |
+/// | 13   | `coordinate_precision`, decimals      |
+/// | 14   | Inverted byte sum of bytes 0-13       |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RadioSettings {
+    /// Receive frequency in Hz, see `LoraConfig::frequency`
+    pub frequency: u32,
+
+    /// 5 to 12
+    pub spreading_factor: u8,
+
+    /// Source ID of the frames sent, 24 bits and anything but `BROADCAST`
+    pub node_id: u32,
+
+    /// Format position broadcasts are sent in, see `driver::REPORT_FORMAT`
+    pub report_format: ReportFormat,
+
+    /// Precision compact reports and the display round coordinates to, see
+    /// `precision::COORDINATE_PRECISION`
+    pub coordinate_precision: CoordinatePrecision,
+}
+
+impl RadioSettings {
+    /// Check that the settings are legal in `region` on a channel `bandwidth_hz` wide
+    pub fn check(&self, region: Region, bandwidth_hz: u32) -> Result<(), LoraError> {
+        if !SPREADING_FACTORS.contains(&self.spreading_factor) || self.node_id >= BROADCAST {
+            return Err(LoraError::InvalidConfig);
+        }
+
+        region.check_frequency(self.frequency, bandwidth_hz)?;
+        region.check_modulation(self.spreading_factor, bandwidth_hz)
+    }
+
+    pub fn to_bytes(self) -> [u8; SETTINGS_SIZE] {
+        let mut bytes = [0u8; SETTINGS_SIZE];
+        bytes[..4].copy_from_slice(&SETTINGS_MAGIC);
+        bytes[4..8].copy_from_slice(&self.frequency.to_le_bytes());
+        bytes[8] = self.spreading_factor;
+        bytes[9..12].copy_from_slice(&self.node_id.to_le_bytes()[..3]);
+        bytes[12] = self.report_format as u8;
+        bytes[13] = self.coordinate_precision.decimals();
+        bytes[14] = !checksum(&bytes[..14]);
+
+        bytes
+    }
+
+    /// Settings stored by `to_bytes`, `None` for anything else
+    pub fn from_bytes(bytes: &[u8; SETTINGS_SIZE]) -> Option<Self> {
+        if bytes[..4] != SETTINGS_MAGIC || bytes[14] != !checksum(&bytes[..14]) {
+            return None;
+        }
+
+        Some(Self {
+            frequency: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            spreading_factor: bytes[8],
+            node_id: u32::from_le_bytes([bytes[9], bytes[10], bytes[11], 0]),
+            report_format: ReportFormat::try_from(bytes[12]).ok()?,
+            coordinate_precision: CoordinatePrecision::new(bytes[13])?,
+        })
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let settings = RadioSettings {
+            frequency: 868_100_000,
+            spreading_factor: 9,
+            node_id: 0x12_3456,
+            report_format: ReportFormat::Delta,
+            coordinate_precision: CoordinatePrecision::new(3).unwrap(),
+        };
+
+        assert_eq!(
+            RadioSettings::from_bytes(&settings.to_bytes()),
+            Some(settings)
+        );
+    }
+
+    #[test]
+    fn test_rejects_erased_and_corrupt_bytes() {
+        assert_eq!(RadioSettings::from_bytes(&[0xFF; SETTINGS_SIZE]), None);
+
+        let mut bytes = RadioSettings {
+            frequency: 915_000_000,
+            spreading_factor: 7,
+            node_id: 1,
+            report_format: ReportFormat::Standard,
+            coordinate_precision: CoordinatePrecision::default(),
+        }
+        .to_bytes();
+        bytes[8] = 8;
+        assert_eq!(RadioSettings::from_bytes(&bytes), None);
+
+        // Intact, but with a format or precision this firmware doesn't know
+        bytes[8] = 7;
+        bytes[12] = 9;
+        bytes[14] = !checksum(&bytes[..14]);
+        assert_eq!(RadioSettings::from_bytes(&bytes), None);
+        bytes[12] = ReportFormat::Standard as u8;
+        bytes[13] = 8;
+        bytes[14] = !checksum(&bytes[..14]);
+        assert_eq!(RadioSettings::from_bytes(&bytes), None);
+    }
+
+    #[test]
+    fn test_check_against_region() {
+        let settings = RadioSettings {
+            frequency: 915_000_000,
+            spreading_factor: 7,
+            node_id: 0x12_3456,
+            report_format: ReportFormat::Standard,
+            coordinate_precision: CoordinatePrecision::default(),
+        };
+        assert!(settings.check(Region::Us915, 125_000).is_ok());
+        assert!(settings.check(Region::Eu868, 125_000).is_err());
+
+        // Over the dwell time limit, and outside what the radio supports
+        let slow = RadioSettings {
+            spreading_factor: 12,
+            ..settings
+        };
+        assert!(slow.check(Region::Us915, 125_000).is_err());
+        let unsupported = RadioSettings {
+            frequency: 868_100_000,
+            spreading_factor: 13,
+            ..settings
+        };
+        assert!(unsupported.check(Region::Eu868, 125_000).is_err());
+
+        // Frames from it would look addressed to everyone
+        let broadcast = RadioSettings {
+            node_id: BROADCAST,
+            ..settings
+        };
+        assert!(broadcast.check(Region::Us915, 125_000).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+/// Fixed transmit slots in a repeating frame, so nodes sharing a channel take turns
+///
+/// Each node owns slot `node_id % slot_count`; with node IDs that differ modulo the slot count
+/// the nodes never transmit over each other. All nodes need a common clock for this to work,
+/// which is GPS time: the receiver's timestamp of the latest fix plus the time elapsed since.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeSlots {
+    /// Length of one full round of slots, in milliseconds
+    pub frame_period_ms: u64,
+
+    /// Length of each node's slot, in milliseconds; has to fit the longest packet on air
+    pub slot_width_ms: u64,
+
+    /// Identifies this node; the device ID
+    pub node_id: u32,
+}
+
+impl TimeSlots {
+    pub fn slot_count(&self) -> u64 {
+        (self.frame_period_ms / self.slot_width_ms.max(1)).max(1)
+    }
+
+    /// Index of this node's slot within the frame
+    pub fn slot(&self) -> u64 {
+        self.node_id as u64 % self.slot_count()
+    }
+
+    /// Milliseconds from `now_ms` (GPS time) until this node's slot opens, 0 while it is open
+    pub fn delay_until_slot(&self, now_ms: u64) -> u64 {
+        let period = self.frame_period_ms.max(1);
+        let slot_start = self.slot() * self.slot_width_ms;
+        let position = now_ms % period;
+
+        if (slot_start..slot_start + self.slot_width_ms).contains(&position) {
+            0
+        } else {
+            (slot_start + period - position) % period
+        }
+    }
+
+    /// Random delay within one frame, for when there is no GPS time to find the slot with
+    ///
+    /// Nodes then collide only by chance rather than by transmitting in lockstep.
+    pub fn random_delay(&self, seed: u32) -> u64 {
+        xorshift32(seed ^ self.node_id) as u64 % self.frame_period_ms.max(1)
+    }
+}
+
+/// The wait for a node's slot, which may take several stretches of listening
+///
+/// Listening can end before the time asked for, so rather than trusting one stretch to cover
+/// the wait, ask `remaining_ms` again after each and transmit once it's 0.
+#[derive(Debug, Clone, Copy)]
+pub struct SlotWait {
+    /// Uptime in milliseconds the wait ends at, going by the delay found at the start
+    deadline_ms: u64,
+
+    /// Whether the slot was found with GPS time, which then keeps deciding when it opens
+    by_gps_time: bool,
+}
+
+impl SlotWait {
+    /// Start waiting at `uptime_ms`, for the slot if there is `gps_ms` time and otherwise for a
+    /// random delay from `seed`
+    pub fn start(slots: &TimeSlots, uptime_ms: u64, gps_ms: Option<u64>, seed: u32) -> Self {
+        let delay_ms = match gps_ms {
+            Some(gps_ms) => slots.delay_until_slot(gps_ms),
+            None => slots.random_delay(seed),
+        };
+
+        Self {
+            deadline_ms: uptime_ms + delay_ms,
+            by_gps_time: gps_ms.is_some(),
+        }
+    }
+
+    /// Milliseconds still to wait at `uptime_ms`, 0 once the slot is open
+    pub fn remaining_ms(&self, slots: &TimeSlots, uptime_ms: u64, gps_ms: Option<u64>) -> u64 {
+        match gps_ms.filter(|_| self.by_gps_time) {
+            Some(gps_ms) => slots.delay_until_slot(gps_ms),
+            None => self.deadline_ms.saturating_sub(uptime_ms),
+        }
+    }
+}
+
+/// Cheap pseudo-random scramble of `seed`; good enough to spread transmit times
+fn xorshift32(seed: u32) -> u32 {
+    let mut x = if seed == 0 { 0x9e37_79b9 } else { seed };
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slots(node_id: u32) -> TimeSlots {
+        TimeSlots {
+            frame_period_ms: 10_000,
+            slot_width_ms: 1_000,
+            node_id,
+        }
+    }
+
+    #[test]
+    fn test_slot_from_node_id() {
+        assert_eq!(slots(3).slot_count(), 10);
+        assert_eq!(slots(3).slot(), 3);
+        assert_eq!(slots(0xABCDE7).slot(), 0xABCDE7 % 10);
+    }
+
+    #[test]
+    fn test_delay_until_slot() {
+        let slots = slots(3);
+
+        // Slot 3 spans 3000..4000 ms into each frame
+        assert_eq!(slots.delay_until_slot(1_000), 2_000);
+        assert_eq!(slots.delay_until_slot(3_000), 0);
+        assert_eq!(slots.delay_until_slot(3_999), 0);
+        assert_eq!(slots.delay_until_slot(4_000), 9_000);
+        assert_eq!(slots.delay_until_slot(1_700_000_001_500), 1_500);
+    }
+
+    #[test]
+    fn test_neighbouring_nodes_never_overlap() {
+        for now_ms in (0..20_000).step_by(250) {
+            let open = (0..10)
+                .filter(|&node_id| slots(node_id).delay_until_slot(now_ms) == 0)
+                .count();
+
+            assert_eq!(open, 1, "{open} slots open at {now_ms} ms");
+        }
+    }
+
+    #[test]
+    fn test_slot_wait_outlasts_early_stops() {
+        let slots = slots(3);
+
+        // Waiting from 1000 ms into the frame, with a packet cutting listening short at 1500
+        let wait = SlotWait::start(&slots, 0, Some(1_000), 0);
+        assert_eq!(wait.remaining_ms(&slots, 500, Some(1_500)), 1_500);
+        assert_eq!(wait.remaining_ms(&slots, 2_000, Some(3_000)), 0);
+
+        // Without GPS time the random delay is kept to
+        let wait = SlotWait::start(&slots, 100, None, 7);
+        let delay_ms = slots.random_delay(7);
+        assert_eq!(wait.remaining_ms(&slots, 100, None), delay_ms);
+        assert_eq!(
+            wait.remaining_ms(&slots, 100 + delay_ms / 2, None),
+            delay_ms - delay_ms / 2
+        );
+        assert_eq!(wait.remaining_ms(&slots, 100 + delay_ms, Some(0)), 0);
+    }
+
+    #[test]
+    fn test_random_delay_within_frame() {
+        for seed in 0..100 {
+            assert!(slots(3).random_delay(seed) < 10_000);
+        }
+    }
+}
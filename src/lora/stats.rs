@@ -0,0 +1,84 @@
+/// Running totals of what the radio has sent and heard
+///
+/// Telling CRC failures apart from other radio errors separates "no signal" from "noisy signal":
+/// a CRC error means a LoRa preamble and header were decoded, so something was in range, but the
+/// payload got corrupted on the way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RadioStats {
+    /// Frames transmitted successfully
+    pub packets_sent: u32,
+
+    /// Frames that passed the hardware CRC
+    pub packets_received: u32,
+
+    /// Frames dropped because the hardware CRC failed
+    pub crc_error_count: u32,
+
+    /// Receive attempts that failed for any other reason
+    pub other_error_count: u32,
+
+    /// Signal strength of the last frame that passed the CRC, in dBm
+    pub last_rssi: Option<i16>,
+}
+
+impl RadioStats {
+    pub const fn new() -> Self {
+        Self {
+            packets_sent: 0,
+            packets_received: 0,
+            crc_error_count: 0,
+            other_error_count: 0,
+            last_rssi: None,
+        }
+    }
+
+    pub fn record_sent(&mut self) {
+        self.packets_sent = self.packets_sent.saturating_add(1);
+    }
+
+    pub fn record_packet(&mut self, rssi: i16) {
+        self.packets_received = self.packets_received.saturating_add(1);
+        self.last_rssi = Some(rssi);
+    }
+
+    pub fn record_crc_error(&mut self) {
+        self.crc_error_count = self.crc_error_count.saturating_add(1);
+    }
+
+    pub fn record_other_error(&mut self) {
+        self.other_error_count = self.other_error_count.saturating_add(1);
+    }
+
+    /// Fraction of decoded frames that failed the CRC, or `None` before any frame was heard
+    pub fn crc_error_rate(&self) -> Option<f32> {
+        let frames = self.packets_received as u64 + self.crc_error_count as u64;
+
+        (frames > 0).then(|| self.crc_error_count as f32 / frames as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_rate_before_any_frame() {
+        let mut stats = RadioStats::new();
+        stats.record_other_error();
+
+        assert_eq!(stats.crc_error_rate(), None);
+    }
+
+    #[test]
+    fn test_crc_error_rate_ignores_other_errors() {
+        let mut stats = RadioStats::new();
+        for _ in 0..3 {
+            stats.record_packet(-90);
+        }
+        stats.record_crc_error();
+        stats.record_other_error();
+
+        assert_eq!(stats.crc_error_rate(), Some(0.25));
+        assert_eq!(stats.last_rssi, Some(-90));
+    }
+}
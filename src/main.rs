@@ -1,6 +1,7 @@
 #![no_std]
 #![no_main]
 
+use core::sync::atomic::Ordering;
 use embassy_executor::Spawner;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::mutex::Mutex;
@@ -11,6 +12,7 @@ use esp_hal::gpio::Level;
 use esp_hal::gpio::Output;
 use esp_hal::gpio::OutputConfig;
 use esp_hal::gpio::Pin;
+use esp_hal::gpio::Pull;
 use esp_hal::spi::master::Config;
 use esp_hal::spi::master::Spi;
 use esp_hal::spi::Mode;
@@ -18,16 +20,26 @@ use esp_hal::time::Rate;
 use esp_hal::Async;
 use esp_hal::{clock::CpuClock, timer::timg::TimerGroup};
 use esp_println as _;
-use lora_phy::sx126x::Sx126x;
 use static_cell::StaticCell;
 
 use {esp_alloc as _, esp_backtrace as _};
 
+mod app_state;
+mod battery;
 mod ble;
+mod device;
 mod display;
+mod dock;
+mod errorlog;
+mod flashlog;
 mod gnss;
+mod indicator;
 mod log;
 mod lora;
+mod nav;
+mod power;
+mod selftest;
+mod watchdog;
 
 static SPI_BUS: StaticCell<
     Mutex<CriticalSectionRawMutex, esp_hal::spi::master::Spi<'static, Async>>,
@@ -39,26 +51,57 @@ async fn main(spawner: Spawner) {
     esp_alloc::heap_allocator!(size: 72 * 1024);
     let timer_group = TimerGroup::new(peripherals.TIMG0);
 
-    let init = esp_wifi::init(
-        timer_group.timer0,
-        esp_hal::rng::Rng::new(peripherals.RNG),
-        peripherals.RADIO_CLK,
-    )
-    .unwrap();
+    // The radio is only needed for BLE; GNSS, LoRa and the display keep working without it.
+    // `init` consumes the timer and radio clock, so there is nothing left to retry with.
+    // Shared with BLE, which seeds its pairing keys from it
+    let rng = esp_hal::rng::Rng::new(peripherals.RNG);
+    let init = match esp_wifi::init(timer_group.timer0, rng, peripherals.RADIO_CLK) {
+        Ok(init) => Some(init),
+        Err(e) => {
+            esp_println::println!("Radio init failed, continuing without BLE: {:?}", e);
+            ble::state::set_unavailable();
+
+            None
+        }
+    };
 
     esp_hal_embassy::init(timer_group.timer1);
 
+    // GPS, set up early for the self-test to listen to; its task starts last
+    let config = gnss::driver::Config {
+        rx_pin: peripherals.GPIO46.degrade(),
+        // Not wired on the stock board; give the pin to the receiver's RX to trim its output
+        tx_pin: None,
+        baud_rate: gnss::driver::GNSS_BAUD_RATE,
+        auto_baud: true,
+        min_satellites: gnss::driver::GNSS_MIN_SATELLITES,
+        max_speed: gnss::driver::GNSS_MAX_SPEED,
+        fifo_full_threshold: gnss::driver::DEFAULT_FIFO_FULL_THRESHOLD,
+        fix_timeout: gnss::driver::DEFAULT_FIX_TIMEOUT,
+        robust_parse: true,
+        smoothing: true,
+        retry: gnss::driver::RetryConfig::default(),
+    };
+
+    let mut gps = gnss::driver::Gnss::new(peripherals.UART1, config).unwrap();
+
     //
     // Initialize SPI
     //
-    let nss = Output::new(peripherals.GPIO8, Level::High, OutputConfig::default());
+    let mut nss = Output::new(peripherals.GPIO8, Level::High, OutputConfig::default());
     let sclk = peripherals.GPIO9;
     let mosi = peripherals.GPIO10;
     let miso = peripherals.GPIO11;
 
-    let reset = Output::new(peripherals.GPIO12, Level::Low, OutputConfig::default());
-    let busy = Input::new(peripherals.GPIO13, InputConfig::default());
-    let dio1 = Input::new(peripherals.GPIO14, InputConfig::default());
+    let mut reset = Output::new(peripherals.GPIO12, Level::Low, OutputConfig::default());
+    let mut busy = Input::new(peripherals.GPIO13, InputConfig::default());
+    // lora-phy awaits DIO1 and BUSY through `embedded_hal_async::digital::Wait`, which esp-hal
+    // backs with GPIO edge interrupts, so the LoRa task sleeps between radio events. The pull-down
+    // keeps DIO1 from floating into spurious wake-ups while the radio is held in reset.
+    let dio1 = Input::new(
+        peripherals.GPIO14,
+        InputConfig::default().with_pull(Pull::Down),
+    );
 
     let spi = Spi::new(
         peripherals.SPI2,
@@ -90,37 +133,144 @@ async fn main(spawner: Spawner) {
     let i2c = esp_hal::i2c::master::I2c::new(peripherals.I2C0, config).unwrap();
     let mut i2c = i2c.with_scl(scl).with_sda(sda).into_async();
 
-    esp_println::println!("Starting I2C bus stabilization...");
+    // Released from reset first, a panel held in it doesn't answer the scan
+    let oled_rst = Output::new(peripherals.GPIO21, Level::High, OutputConfig::default());
+    let i2c_devices = selftest::i2c_scan(&mut i2c).await;
+    let self_test = selftest::run(
+        &i2c_devices,
+        spi_bus,
+        &mut nss,
+        &mut reset,
+        &mut busy,
+        &mut gps,
+    )
+    .await;
+    self_test.log();
+
+    // Everything else runs without a panel, it only misses the status display
+    let mut display = if self_test.display {
+        let mut delay = esp_hal::delay::Delay::new();
 
-    for addr in 0x3A..=0x3E {
-        let _ = i2c.write(addr, &[0]);
-    }
+        match display::DisplayDevice::new(
+            i2c,
+            config,
+            oled_rst,
+            display::DisplaySize::Size128x64,
+            &mut delay,
+        ) {
+            Ok(display) => Some(display),
+            Err(e) => {
+                esp_println::println!("Display init failed, continuing without it: {:?}", e);
+                None
+            }
+        }
+    } else {
+        esp_println::println!(
+            "No display at {:#04x}, continuing without it",
+            display::DISPLAY_ADDRESS
+        );
+        None
+    };
 
-    let mut delay = esp_hal::delay::Delay::new();
+    // Holding the USER button while the firmware boots runs the panel test pattern
+    let user_button = Input::new(
+        peripherals.GPIO0,
+        InputConfig::default().with_pull(Pull::Up),
+    );
+    if let Some(display) = &mut display {
+        if let Err(e) = self_test.show(display) {
+            esp_println::println!("Failed to show the self-test report: {:?}", e);
+        }
+        embassy_time::Timer::after(selftest::REPORT_DURATION).await;
 
-    let display = display::DisplayDevice::new(
-        i2c,
-        Output::new(
-            peripherals.GPIO21,
-            esp_hal::gpio::Level::High,
-            OutputConfig::default(),
-        ),
-        &mut delay,
-    )
-    .unwrap();
+        if user_button.is_low() {
+            esp_println::println!("Running display test pattern...");
 
-    spawner.spawn(display::controller::start(display)).unwrap();
-    spawner.spawn(ble::start(peripherals.BT, init)).unwrap();
+            if let Err(e) = display.test_pattern() {
+                esp_println::println!("Display test pattern failed: {:?}", e);
+            }
+        }
+    }
+
+    // Set `mode` to save the battery between broadcasts, e.g.
+    // `PowerMode::SleepBetweenFixes { interval: Duration::from_secs(300), light_sleep: true }`
+    power::Config::default().apply(esp_hal::rtc_cntl::Rtc::new(peripherals.LPWR));
+
+    // Resets the chip if a task registered with it hangs
+    let watchdog_timer_group = TimerGroup::new(peripherals.TIMG1);
     spawner
-        .spawn(lora::start(spi_bus, nss, reset, dio1, busy))
+        .spawn(watchdog::supervisor::supervise(watchdog_timer_group.wdt))
         .unwrap();
 
-    // GPS
-    let config = gnss::driver::Config {
-        rx_pin: peripherals.GPIO46.degrade(),
-        baud_rate: gnss::driver::GNSS_BAUD_RATE,
+    let indicators = indicator::Config::default();
+    spawner.spawn(app_state::aggregate()).unwrap();
+    match display {
+        Some(display) => {
+            spawner
+                .spawn(display::controller::start(
+                    display,
+                    indicators.flash_display,
+                    Some(display::controller::DEFAULT_SLEEP_AFTER),
+                ))
+                .unwrap();
+            spawner
+                .spawn(display::page::cycle_on_button(user_button))
+                .unwrap();
+        }
+        None => display::controller::DISPLAY_PRESENT.store(false, Ordering::Relaxed),
+    }
+    if indicators.buzzer {
+        let buzzer = Output::new(peripherals.GPIO47, Level::Low, OutputConfig::default());
+        spawner.spawn(indicator::buzzer(buzzer)).unwrap();
+    }
+    let dock = dock::Config::default();
+    dock.apply();
+    if dock.power_detect {
+        let power_detect = Input::new(peripherals.GPIO48, InputConfig::default());
+        spawner.spawn(dock::power_detect(power_detect)).unwrap();
+    }
+    #[cfg(feature = "battery-sense")]
+    {
+        let battery = battery::monitor::Config::default();
+        // Heltec's ADC_Ctrl; the divider stays switched out between measurements
+        let control = Output::new(
+            peripherals.GPIO37,
+            !battery.enable_level,
+            OutputConfig::default(),
+        );
+        spawner
+            .spawn(battery::monitor::monitor(
+                peripherals.ADC1,
+                peripherals.GPIO1,
+                control,
+                battery,
+            ))
+            .unwrap();
+    }
+    if let Some(init) = init {
+        spawner
+            .spawn(ble::start(peripherals.BT, init, rng))
+            .unwrap();
+    }
+    // Set `time_slots` here to take turns on the channel with other nodes, e.g.
+    // `Some(TimeSlots { frame_period_ms: 10_000, slot_width_ms: 1_000, node_id: device_id() })`,
+    // and `relay` to pass positions on through the mesh, e.g.
+    // `Some(RelayConfig { node_id: device_id(), max_hops: DEFAULT_MAX_HOPS })`
+    let broadcast = lora::broadcast::BroadcastConfig {
+        node_id: device::device_id(),
+        ..Default::default()
     };
+    spawner
+        .spawn(lora::driver::start(
+            spi_bus, nss, reset, dio1, busy, broadcast, rng,
+        ))
+        .unwrap();
 
-    let gps = gnss::driver::Gnss::new(peripherals.UART1, config).unwrap();
     spawner.spawn(gnss::driver::start(gps)).unwrap();
+    spawner.spawn(gnss::watch::record_history()).unwrap();
+    spawner
+        .spawn(flashlog::storage::record(
+            flashlog::storage::Config::default(),
+        ))
+        .unwrap();
 }
@@ -0,0 +1,176 @@
+use libm::{atan2, cos, sin, sqrt};
+
+use crate::gnss::positioning::GnssPositioning;
+use crate::lora::packet::{decode_coordinate, encode_coordinate};
+
+#[cfg(feature = "esp32")]
+pub mod target;
+
+/// Mean Earth radius in metres, as used by the haversine formula
+pub const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// Size of the BLE `waypoint` characteristic, see `waypoint_from_ble_bytes`
+pub const WAYPOINT_SIZE: usize = 8;
+
+/// Speed over ground (knots) below which the reported course is mostly noise, ~4 km/h
+pub const MIN_COURSE_SPEED: f32 = 2.0;
+
+/// Initial great-circle bearing from the fix to `to` (latitude, longitude), degrees true 0..360
+pub fn bearing_to(from: &GnssPositioning, to: (f64, f64)) -> f32 {
+    let (from_latitude, to_latitude) = (from.latitude.to_radians(), to.0.to_radians());
+    let longitude_delta = (to.1 - from.longitude).to_radians();
+
+    let y = sin(longitude_delta) * cos(to_latitude);
+    let x = cos(from_latitude) * sin(to_latitude)
+        - sin(from_latitude) * cos(to_latitude) * cos(longitude_delta);
+
+    normalize_degrees(atan2(y, x).to_degrees() as f32)
+}
+
+/// Great-circle distance in metres from the fix to `to` (latitude, longitude), by haversine
+pub fn distance_to(from: &GnssPositioning, to: (f64, f64)) -> f32 {
+    let (from_latitude, to_latitude) = (from.latitude.to_radians(), to.0.to_radians());
+    let latitude_delta = to_latitude - from_latitude;
+    let longitude_delta = (to.1 - from.longitude).to_radians();
+
+    let haversine = sin(latitude_delta / 2.0) * sin(latitude_delta / 2.0)
+        + cos(from_latitude)
+            * cos(to_latitude)
+            * sin(longitude_delta / 2.0)
+            * sin(longitude_delta / 2.0);
+
+    (2.0 * EARTH_RADIUS_M * atan2(sqrt(haversine), sqrt(1.0 - haversine))) as f32
+}
+
+/// `bearing` relative to the direction of travel, 0..360 clockwise from straight ahead
+///
+/// `None` while the fix has no course, or is too slow for its course to mean anything; see
+/// `MIN_COURSE_SPEED`.
+pub fn relative_bearing(from: &GnssPositioning, bearing: f32) -> Option<f32> {
+    let heading = from.heading?;
+    if from.speed.is_none_or(|speed| speed < MIN_COURSE_SPEED) {
+        return None;
+    }
+
+    Some(normalize_degrees(bearing - heading))
+}
+
+/// Nearest of the eight compass points to `heading`, in degrees true
+pub fn heading_to_compass(heading: f32) -> &'static str {
+    const POINTS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+
+    // Each point covers 45°, centred on it, so N runs from 337.5° round to 22.5°
+    let sector = (normalize_degrees(heading + 22.5) / 45.0) as usize;
+    POINTS[sector % POINTS.len()]
+}
+
+/// Read a waypoint written to the BLE `waypoint` characteristic
+///
+/// Latitude then longitude, each an `i32` LE in 1e-7 degree, as in the `telemetry`
+/// characteristic. `None` if it's the wrong size or out of range.
+pub fn waypoint_from_ble_bytes(bytes: &[u8]) -> Option<(f64, f64)> {
+    let bytes: [u8; WAYPOINT_SIZE] = bytes.try_into().ok()?;
+    let latitude = decode_coordinate([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let longitude = decode_coordinate([bytes[4], bytes[5], bytes[6], bytes[7]]);
+
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+        .then_some((latitude, longitude))
+}
+
+/// The BLE `waypoint` characteristic's value for `waypoint`, zeroed for none
+pub fn waypoint_to_ble_bytes(waypoint: Option<(f64, f64)>) -> [u8; WAYPOINT_SIZE] {
+    let mut bytes = [0u8; WAYPOINT_SIZE];
+
+    if let Some((latitude, longitude)) = waypoint {
+        bytes[0..4].copy_from_slice(&encode_coordinate(latitude));
+        bytes[4..8].copy_from_slice(&encode_coordinate(longitude));
+    }
+
+    bytes
+}
+
+/// `degrees` brought into 0..360
+fn normalize_degrees(degrees: f32) -> f32 {
+    let degrees = degrees % 360.0;
+
+    if degrees < 0.0 {
+        degrees + 360.0
+    } else {
+        degrees
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::positioning::{assert_close, positioning_at};
+
+    #[test]
+    fn test_known_pair() {
+        // Big Ben to the Statue of Liberty
+        let london = positioning_at(0, 51.5007, -0.1246);
+        let new_york = (40.6892, -74.0445);
+
+        assert_close(distance_to(&london, new_york), 5_574_840.0, 10.0);
+        assert_close(bearing_to(&london, new_york), 288.337, 0.01);
+    }
+
+    #[test]
+    fn test_cardinal_directions() {
+        let origin = positioning_at(0, 0.0, 0.0);
+
+        // One degree along the equator
+        assert_close(distance_to(&origin, (0.0, 1.0)), 111_194.9, 1.0);
+        assert_close(bearing_to(&origin, (0.0, 1.0)), 90.0, 0.01);
+        assert_close(bearing_to(&origin, (1.0, 0.0)), 0.0, 0.01);
+        assert_close(bearing_to(&origin, (-1.0, 0.0)), 180.0, 0.01);
+        assert_close(bearing_to(&origin, (0.0, -1.0)), 270.0, 0.01);
+
+        assert_eq!(distance_to(&origin, (0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn test_across_antimeridian() {
+        let from = positioning_at(0, 0.0, 179.5);
+
+        assert_close(distance_to(&from, (0.0, -179.5)), 111_194.9, 1.0);
+        assert_close(bearing_to(&from, (0.0, -179.5)), 90.0, 0.01);
+    }
+
+    #[test]
+    fn test_relative_bearing() {
+        let mut from = positioning_at(0, 0.0, 0.0);
+        from.heading = Some(300.0);
+        assert_eq!(relative_bearing(&from, 90.0), None);
+
+        from.speed = Some(10.0);
+        assert_close(relative_bearing(&from, 90.0).unwrap(), 150.0, 0.001);
+        assert_close(relative_bearing(&from, 290.0).unwrap(), 350.0, 0.001);
+    }
+
+    #[test]
+    fn test_heading_to_compass() {
+        assert_eq!(heading_to_compass(0.0), "N");
+        assert_eq!(heading_to_compass(22.4), "N");
+        assert_eq!(heading_to_compass(22.5), "NE");
+        assert_eq!(heading_to_compass(180.0), "S");
+        assert_eq!(heading_to_compass(292.0), "W");
+        assert_eq!(heading_to_compass(359.9), "N");
+        assert_eq!(heading_to_compass(-45.0), "NW");
+    }
+
+    #[test]
+    fn test_waypoint_ble_round_trip() {
+        let waypoint = (-33.856_785_3, 151.215_289_2);
+        let bytes = waypoint_to_ble_bytes(Some(waypoint));
+
+        let (latitude, longitude) = waypoint_from_ble_bytes(&bytes).unwrap();
+        assert!((latitude - waypoint.0).abs() < 1e-7);
+        assert!((longitude - waypoint.1).abs() < 1e-7);
+
+        assert_eq!(waypoint_from_ble_bytes(&bytes[..4]), None);
+        let mut out_of_range = bytes;
+        out_of_range[0..4].copy_from_slice(&encode_coordinate(91.0));
+        assert_eq!(waypoint_from_ble_bytes(&out_of_range), None);
+    }
+}
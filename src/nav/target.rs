@@ -0,0 +1,16 @@
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+
+/// Waypoint being navigated to, latitude then longitude; set over BLE
+static TARGET: BlockingMutex<CriticalSectionRawMutex, Cell<Option<(f64, f64)>>> =
+    BlockingMutex::new(Cell::new(None));
+
+/// Navigate to `target`, or stop navigating if `None`
+pub fn set_target(target: Option<(f64, f64)>) {
+    TARGET.lock(|cell| cell.set(target));
+}
+
+pub fn target() -> Option<(f64, f64)> {
+    TARGET.lock(Cell::get)
+}
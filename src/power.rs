@@ -0,0 +1,111 @@
+use core::cell::{Cell, RefCell};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::watch::{Receiver, Watch};
+use embassy_time::{Duration, Timer};
+use esp_hal::rtc_cntl::sleep::TimerWakeupSource;
+use esp_hal::rtc_cntl::Rtc;
+
+/// Tasks that wait on power saving changes: the app state aggregator
+const POWER_RECEIVERS: usize = 1;
+
+/// Time for the panel to blank before light sleep stops the CPU under it
+const LIGHT_SLEEP_GRACE: Duration = Duration::from_millis(100);
+
+/// How the device spends the time between position broadcasts
+///
+/// Rough current from the battery on a Heltec V3, without the external GNSS receiver, which
+/// draws its usual 20 to 30 mA in every mode:
+///
+/// | State                                  | Current        |
+/// |----------------------------------------|----------------|
+/// | Awake, radio listening, panel lit      | 60 to 80 mA    |
+/// | Awake, radio and panel asleep          | 40 to 50 mA    |
+/// | Light sleep, radio and panel asleep    | 2 to 3 mA      |
+/// | Transmitting at +22 dBm                | 120 to 140 mA  |
+///
+/// BLE advertising adds a few mA on average while the CPU is awake. In light sleep most of what's
+/// left is the board's regulator and USB bridge rather than the chips themselves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerMode {
+    /// Everything stays powered, and the radio listens for peers between broadcasts
+    AlwaysOn,
+
+    /// After each position broadcast, put the radio to sleep and blank the panel for `interval`,
+    /// then wait for a new fix and broadcast it
+    ///
+    /// Peers transmitting meanwhile aren't heard, and the speed-based intervals in
+    /// `BroadcastConfig` no longer apply. The panel stays blank on waking, until a button press or
+    /// other activity turns it back on. With `light_sleep` the CPU sleeps through `interval` too,
+    /// on an RTC timer: every task stops, BLE drops its connection, and the receiver's output
+    /// is lost until the CPU wakes up.
+    SleepBetweenFixes {
+        interval: Duration,
+        light_sleep: bool,
+    },
+}
+
+/// Power management settings
+pub struct Config {
+    pub mode: PowerMode,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: PowerMode::AlwaysOn,
+        }
+    }
+}
+
+impl Config {
+    /// Apply the settings; call once before spawning anything. `rtc` is only used for light sleep
+    pub fn apply(&self, rtc: Rtc<'static>) {
+        MODE.lock(|mode| mode.set(self.mode));
+        RTC.lock(|slot| slot.replace(Some(rtc)));
+    }
+}
+
+static MODE: Mutex<CriticalSectionRawMutex, Cell<PowerMode>> =
+    Mutex::new(Cell::new(PowerMode::AlwaysOn));
+
+/// Taken out for the duration of each light sleep
+static RTC: Mutex<CriticalSectionRawMutex, RefCell<Option<Rtc<'static>>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Whether the device is sleeping between fixes; the panel stays blank while it is
+pub static POWER_SAVING: Watch<CriticalSectionRawMutex, bool, POWER_RECEIVERS> = Watch::new();
+
+pub type PowerSavingRx = Receiver<'static, CriticalSectionRawMutex, bool, POWER_RECEIVERS>;
+
+/// Current mode, `PowerMode::AlwaysOn` until `Config::apply`
+pub fn mode() -> PowerMode {
+    MODE.lock(|mode| mode.get())
+}
+
+pub fn set_power_saving(power_saving: bool) {
+    if POWER_SAVING.try_get() != Some(power_saving) {
+        defmt::info!("Power saving: {}", power_saving);
+        POWER_SAVING.sender().send(power_saving);
+    }
+}
+
+/// Stop the CPU for `duration`, waking on the RTC timer
+///
+/// The grace period before it lets other tasks act on `POWER_SAVING` first. Without an RTC from
+/// `Config::apply` this falls back to an ordinary timer.
+pub async fn light_sleep(duration: Duration) {
+    Timer::after(LIGHT_SLEEP_GRACE).await;
+    let remaining = duration.checked_sub(LIGHT_SLEEP_GRACE).unwrap_or_default();
+
+    let Some(mut rtc) = RTC.lock(|slot| slot.take()) else {
+        defmt::warn!("No RTC for light sleep, waiting awake instead");
+        Timer::after(remaining).await;
+        return;
+    };
+
+    let wakeup = TimerWakeupSource::new(core::time::Duration::from_micros(remaining.as_micros()));
+    rtc.sleep_light(&[&wakeup]);
+
+    RTC.lock(|slot| slot.replace(Some(rtc)));
+}
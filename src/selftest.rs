@@ -0,0 +1,176 @@
+use core::fmt::Write;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{with_timeout, Duration, Timer};
+use esp_hal::gpio::{Input, Output};
+use esp_hal::i2c::master::I2c;
+use esp_hal::spi::master::Spi;
+use esp_hal::Async;
+use heapless::{String, Vec};
+
+use crate::display::page::{draw_lines, Line};
+use crate::display::{DisplayDevice, DisplayInitError, DISPLAY_ADDRESS};
+use crate::gnss::driver::Gnss;
+
+/// Longest a probe of the radio may take; it answers within microseconds
+const PROBE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Addresses `i2c_scan` tries, all of them but the reserved ones
+const I2C_ADDRESSES: core::ops::RangeInclusive<u8> = 0x03..=0x77;
+
+/// Longest a single address may take to answer during `i2c_scan`, in case the bus is stuck
+const I2C_SCAN_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Devices `i2c_scan` reports, far more than the board has
+pub const I2C_SCAN_MAX: usize = 16;
+
+/// Longest to wait for the GNSS receiver, which sends a burst of sentences once a second
+const GNSS_PROBE_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// How long the report stays on the panel before the splash replaces it
+pub const REPORT_DURATION: Duration = Duration::from_secs(2);
+
+/// How long the radio's reset line is held low
+const RADIO_RESET_PULSE: Duration = Duration::from_millis(1);
+
+/// SX126x `ReadRegister` opcode, followed by the address, a status byte, then the data
+const READ_REGISTER: u8 = 0x1D;
+
+/// SX126x register holding the chip's version string, e.g. `SX1261 V2D 2D02`
+const VERSION_REGISTER: u16 = 0x0320;
+
+const VERSION_SIZE: usize = 16;
+
+/// Every SX126x version string starts with this
+const VERSION_PREFIX: &[u8] = b"SX126";
+
+/// Which peripherals answered at boot, from `run`
+#[derive(Debug, Clone)]
+pub struct SelfTestReport {
+    /// Whether the panel showed up in the I2C scan
+    pub display: bool,
+
+    /// The radio's version string, `None` if it didn't answer or isn't an SX126x
+    pub radio: Option<String<VERSION_SIZE>>,
+
+    /// Whether the GNSS receiver sent anything, at whatever baud rate
+    pub gnss: bool,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.display && self.radio.is_some() && self.gnss
+    }
+
+    pub fn log(&self) {
+        if self.passed() {
+            defmt::info!("Self-test passed: {}", defmt::Debug2Format(self));
+        } else {
+            defmt::error!("Self-test failed: {}", defmt::Debug2Format(self));
+        }
+    }
+
+    /// Show the report on the panel, one line per peripheral
+    pub fn show(&self, display: &mut DisplayDevice<'_>) -> Result<(), DisplayInitError> {
+        let mut lines: [Line; 4] = Default::default();
+        write!(
+            &mut lines[0],
+            "SELF TEST {}",
+            if self.passed() { "OK" } else { "FAILED" }
+        )
+        .unwrap_or_default();
+        write!(&mut lines[1], "OLED {}", status(self.display)).unwrap_or_default();
+        match &self.radio {
+            Some(version) => write!(&mut lines[2], "LORA {}", version.as_str()),
+            None => write!(&mut lines[2], "LORA {}", status(false)),
+        }
+        .unwrap_or_default();
+        write!(&mut lines[3], "GNSS {}", status(self.gnss)).unwrap_or_default();
+
+        draw_lines(display, &lines)
+    }
+}
+
+/// Addresses on the bus that acknowledge an empty write, lowest first
+///
+/// Nothing is written to the devices themselves, so this is safe to run on any bus.
+pub async fn i2c_scan(i2c: &mut I2c<'_, Async>) -> Vec<u8, I2C_SCAN_MAX> {
+    let mut found = Vec::new();
+
+    for address in I2C_ADDRESSES {
+        if let Ok(Ok(())) = with_timeout(I2C_SCAN_TIMEOUT, i2c.write_async(address, &[])).await {
+            if found.push(address).is_err() {
+                defmt::warn!("More than {} I2C devices, ignoring the rest", I2C_SCAN_MAX);
+                break;
+            }
+        }
+    }
+
+    defmt::info!("I2C devices: {=[u8]:#x}", found.as_slice());
+    found
+}
+
+/// Check the display against `i2c_devices` from `i2c_scan`, then probe the radio and GNSS
+/// receiver, each within its own timeout
+///
+/// Runs before the tasks that own them start. The radio is reset on the way, its driver resets
+/// it again anyway; the GNSS bytes read are lost, along with the sentence they belong to.
+pub async fn run(
+    i2c_devices: &[u8],
+    spi_bus: &Mutex<CriticalSectionRawMutex, Spi<'_, Async>>,
+    nss: &mut Output<'_>,
+    reset: &mut Output<'_>,
+    busy: &mut Input<'_>,
+    gnss: &mut Gnss,
+) -> SelfTestReport {
+    SelfTestReport {
+        display: i2c_devices.contains(&DISPLAY_ADDRESS),
+        radio: probe_radio(spi_bus, nss, reset, busy).await,
+        gnss: gnss.is_sending(GNSS_PROBE_TIMEOUT).await,
+    }
+}
+
+/// Bring the radio out of reset and read its version string
+async fn probe_radio(
+    spi_bus: &Mutex<CriticalSectionRawMutex, Spi<'_, Async>>,
+    nss: &mut Output<'_>,
+    reset: &mut Output<'_>,
+    busy: &mut Input<'_>,
+) -> Option<String<VERSION_SIZE>> {
+    reset.set_low();
+    Timer::after(RADIO_RESET_PULSE).await;
+    reset.set_high();
+    with_timeout(PROBE_TIMEOUT, busy.wait_for_low())
+        .await
+        .ok()?;
+
+    let [address_high, address_low] = VERSION_REGISTER.to_be_bytes();
+    let mut frame = [0u8; 4 + VERSION_SIZE];
+    frame[..3].copy_from_slice(&[READ_REGISTER, address_high, address_low]);
+
+    let mut spi = spi_bus.lock().await;
+    nss.set_low();
+    let transfer = with_timeout(PROBE_TIMEOUT, spi.transfer_in_place_async(&mut frame)).await;
+    nss.set_high();
+    transfer.ok()?.ok()?;
+
+    let version = &frame[4..];
+    if !version.starts_with(VERSION_PREFIX) {
+        return None;
+    }
+    let length = version
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(VERSION_SIZE);
+    let version = core::str::from_utf8(&version[..length]).ok()?;
+
+    String::try_from(version).ok()
+}
+
+fn status(ok: bool) -> &'static str {
+    if ok {
+        "ok"
+    } else {
+        "--"
+    }
+}
@@ -0,0 +1,98 @@
+use heapless::Vec;
+
+// ESP32-specific modules
+#[cfg(feature = "esp32")]
+pub mod supervisor;
+
+/// Tasks that can register to be watched
+pub const MAX_TASKS: usize = 8;
+
+/// A watched task and when it last checked in
+#[derive(Debug, Clone, Copy)]
+struct Checkin {
+    name: &'static str,
+
+    /// Longest the task may go without feeding before it counts as hung
+    timeout_ms: u64,
+    last_fed_ms: u64,
+}
+
+/// Long-running tasks and when each last fed the watchdog
+///
+/// Times are milliseconds on any monotonic clock, as long as it's the same one throughout.
+#[derive(Debug)]
+pub struct TaskRegistry {
+    tasks: Vec<Checkin, MAX_TASKS>,
+}
+
+impl TaskRegistry {
+    pub const fn new() -> Self {
+        Self { tasks: Vec::new() }
+    }
+
+    /// Start watching `name`, as fed at `now_ms`; the index to `feed` it by, `None` if full
+    pub fn register(&mut self, name: &'static str, timeout_ms: u64, now_ms: u64) -> Option<usize> {
+        self.tasks
+            .push(Checkin {
+                name,
+                timeout_ms,
+                last_fed_ms: now_ms,
+            })
+            .ok()?;
+
+        Some(self.tasks.len() - 1)
+    }
+
+    pub fn feed(&mut self, task: usize, now_ms: u64) {
+        if let Some(checkin) = self.tasks.get_mut(task) {
+            checkin.last_fed_ms = now_ms;
+        }
+    }
+
+    /// Name of the first task that hasn't fed within its timeout as of `now_ms`, if any
+    pub fn stalled(&self, now_ms: u64) -> Option<&'static str> {
+        self.tasks
+            .iter()
+            .find(|checkin| now_ms.saturating_sub(checkin.last_fed_ms) > checkin.timeout_ms)
+            .map(|checkin| checkin.name)
+    }
+}
+
+impl Default for TaskRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stalled_task_found() {
+        let mut registry = TaskRegistry::new();
+        let gnss = registry.register("gnss", 1_000, 0).unwrap();
+        let lora = registry.register("lora", 5_000, 0).unwrap();
+        assert_eq!(registry.stalled(1_000), None);
+
+        registry.feed(gnss, 900);
+        assert_eq!(registry.stalled(1_900), None);
+        assert_eq!(registry.stalled(1_901), Some("gnss"));
+
+        registry.feed(gnss, 5_000);
+        assert_eq!(registry.stalled(5_001), Some("lora"));
+        registry.feed(lora, 5_001);
+        assert_eq!(registry.stalled(5_001), None);
+    }
+
+    #[test]
+    fn test_register_when_full() {
+        let mut registry = TaskRegistry::new();
+        for _ in 0..MAX_TASKS {
+            assert!(registry.register("task", 1_000, 0).is_some());
+        }
+
+        assert_eq!(registry.register("one too many", 1_000, 0), None);
+        assert_eq!(registry.stalled(1_000), None);
+    }
+}
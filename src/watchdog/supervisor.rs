@@ -0,0 +1,80 @@
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::peripherals::TIMG1;
+use esp_hal::timer::timg::{MwdtStage, MwdtStageAction, Wdt};
+
+use super::TaskRegistry;
+
+/// How often the supervisor checks the tasks and feeds the hardware watchdog
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time without a feed before the hardware watchdog resets the chip
+///
+/// Also what it takes to reset once a task has hung, since the supervisor stops feeding then.
+const HARDWARE_TIMEOUT_SECS: u64 = 5;
+
+/// Every task being watched, see `register`
+static TASKS: BlockingMutex<CriticalSectionRawMutex, RefCell<TaskRegistry>> =
+    BlockingMutex::new(RefCell::new(TaskRegistry::new()));
+
+/// A watched task's handle on the watchdog
+pub struct TaskWatchdog {
+    /// `None` if there was no room left to register
+    task: Option<usize>,
+}
+
+impl TaskWatchdog {
+    /// Check in; call on every iteration of the task's loop
+    pub fn feed(&self) {
+        if let Some(task) = self.task {
+            TASKS.lock(|tasks| tasks.borrow_mut().feed(task, Instant::now().as_millis()));
+        }
+    }
+}
+
+/// Watch a long-running task, which counts as hung if it goes `timeout` without a `feed`
+///
+/// A hung task gets the chip reset by the hardware watchdog, see `supervise`.
+pub fn register(name: &'static str, timeout: Duration) -> TaskWatchdog {
+    let task = TASKS.lock(|tasks| {
+        tasks
+            .borrow_mut()
+            .register(name, timeout.as_millis(), Instant::now().as_millis())
+    });
+    if task.is_none() {
+        defmt::error!("No room to watch the {} task", name);
+    }
+
+    TaskWatchdog { task }
+}
+
+/// Feed the TIMG1 watchdog for as long as every registered task keeps feeding its own
+///
+/// The hardware watchdog also catches the executor itself stalling, e.g. a task that never
+/// yields, which would stop this task too.
+#[embassy_executor::task]
+pub async fn supervise(mut wdt: Wdt<TIMG1>) {
+    wdt.set_timeout(
+        MwdtStage::Stage0,
+        esp_hal::time::Duration::from_secs(HARDWARE_TIMEOUT_SECS),
+    );
+    wdt.set_stage_action(MwdtStage::Stage0, MwdtStageAction::ResetSystem);
+    wdt.enable();
+
+    loop {
+        let stalled = TASKS.lock(|tasks| tasks.borrow().stalled(Instant::now().as_millis()));
+        match stalled {
+            Some(name) => {
+                defmt::error!("The {} task has hung, resetting", name);
+
+                // Left to the hardware watchdog to reset the chip
+                core::future::pending::<()>().await;
+            }
+            None => wdt.feed(),
+        }
+
+        Timer::after(CHECK_INTERVAL).await;
+    }
+}